@@ -129,6 +129,7 @@ pub fn generate(output_dir: Path) -> IoResult<()> {
         status_n(417, "Expectation Failed"),
         status_c(418, "I'm a teapot", "RFC 2324"),
         status_n(419, "Authentication Timeout"),
+        status_c(421, "Misdirected Request", "RFC 7540"),
         status_c(422, "Unprocessable Entity", "WebDAV; RFC 4918"),
         status_c(423, "Locked", "WebDAV; RFC 4918"),
         status_c(424, "Failed Dependency", "WebDAV; RFC 4918"),
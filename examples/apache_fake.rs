@@ -19,7 +19,7 @@ struct ApacheFakeServer;
 
 impl Server for ApacheFakeServer {
     fn get_config(&self) -> Config {
-        Config { bind_address: SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 } }
+        Config::new(SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 })
     }
 
     fn handle_request(&self, _r: Request, w: &mut ResponseWriter) {
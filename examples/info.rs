@@ -19,7 +19,7 @@ struct InfoServer;
 
 impl Server for InfoServer {
     fn get_config(&self) -> Config {
-        Config { bind_address: SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 } }
+        Config::new(SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 })
     }
 
     fn handle_request(&self, r: Request, w: &mut ResponseWriter) {
@@ -24,7 +24,7 @@ struct RequestUriServer;
 
 impl Server for RequestUriServer {
     fn get_config(&self) -> Config {
-        Config { bind_address: SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 } }
+        Config::new(SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 })
     }
 
     fn handle_request(&self, r: Request, w: &mut ResponseWriter) {
@@ -76,8 +76,8 @@ impl Server for RequestUriServer {
                 println!("absoluteURI, {}", url);
                 //path =
             },
-            AbsolutePath(ref url) => {
-                println!("absolute path, {}", url);
+            AbsolutePath(ref path) => {
+                println!("absolute path, {:?} (segments {:?})", path, path.segments());
                 //w.status = a
             },
         }
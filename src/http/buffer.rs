@@ -1,6 +1,6 @@
 /// Memory buffers for the benefit of `std::io::net` which has slow read/write.
 
-use std::io::{IoResult, Stream};
+use std::io::{IoError, IoResult, Stream};
 use std::cmp::min;
 use std::slice;
 use std::fmt::radix;
@@ -22,6 +22,10 @@ pub struct BufferedStream<T> {
     pub write_len: usize,
 
     pub writing_chunked_body: bool,
+
+    // The error of the last read of the wrapped stream to fail, for those reading through
+    // something which cannot pass it on (such as the header parser)
+    pub read_error: Option<IoError>,
 }
 
 impl<T: Stream> BufferedStream<T> {
@@ -38,6 +42,7 @@ impl<T: Stream> BufferedStream<T> {
             write_buffer: write_buffer,
             write_len: 0us,
             writing_chunked_body: false,
+            read_error: None,
         }
     }
 }
@@ -67,6 +72,7 @@ impl<T: Reader> BufferedStream<T> {
             },
             Err(err) => {
                 self.read_max = 0;
+                self.read_error = Some(err.clone());
                 Err(err)
             },
         }
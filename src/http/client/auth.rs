@@ -0,0 +1,462 @@
+/*!
+
+HTTP authentication for the client (RFC 7235).
+
+An `AuthProvider` supplies the credentials for requests, and its `send` method sends requests on
+behalf of the caller, sending a request again if the server refuses it and the provider can do
+better. There are three providers:
+
+- `Authenticator`, for a user name and password, described below;
+- `Bearer`, for a fixed bearer token (RFC 6750);
+- `oauth2::ClientCredentials`, for bearer tokens fetched from an OAuth 2.0 token endpoint.
+
+An `Authenticator` holds a user name and password. When a server answers `401 Unauthorized` with
+a WWW-Authenticate header offering a scheme the authenticator understands, the request is sent
+again with credentials. Digest (RFC 7616, with SHA-256 preferred to MD5) is used if it is offered
+and the `ssl` feature is enabled; Basic (RFC 7617) otherwise. Basic sends the password in the
+clear, so it should only be used over HTTPS.
+
+Once a challenge has been answered, later requests through the same authenticator to the same
+origin (scheme, host and port) carry credentials from the start, counting up the Digest nonce count
+with each, so that only the first request pays for the extra round trip. Requests to any other
+origin are sent without credentials until that origin asks for them.
+
+Whatever the provider, requests cannot have a body, for it may need to be sent twice: `send`
+refuses a request whose body has been begun.
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::RequestWriter;
+use http::client::auth::{AuthProvider, Authenticator};
+use http::method::Get;
+use url::Url;
+
+fn main() {
+    let mut auth = Authenticator::new("Mufasa", "Circle of Life");
+    let url = Url::parse("http://example.com/dir/index.html").unwrap();
+    let request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+    let response = auth.send(request).unwrap();
+    println!("{:?}", response.status);
+}
+```
+
+*/
+
+use std::ascii::AsciiExt;
+use std::io::{IoResult, IoError, InvalidInput};
+use std::mem;
+use url::Url;
+
+use client::error::Error;
+use client::request::RequestWriter;
+use client::resolver::SystemResolver;
+use client::response::ResponseReader;
+use connecter::Connecter;
+use headers::authorization::{Credentials, Challenge, Challenges, AuthParams, DigestAlgorithm};
+use method::Method;
+use status::Status::Unauthorized;
+
+/// A source of credentials for requests.
+pub trait AuthProvider {
+    /// The credentials to send with a request before the server has asked for any, if there are
+    /// any. An error (such as a failure to fetch a token) stops the request from being sent.
+    fn credentials(&mut self, method: &Method, url: &Url) -> IoResult<Option<Credentials>>;
+
+    /// Called with the URL and challenges of a `401 Unauthorized` response, returning whether to
+    /// send the request again with fresh credentials. `sent_credentials` says whether the refused
+    /// request had any.
+    fn challenged(&mut self, url: &Url, challenges: &Challenges, sent_credentials: bool) -> bool;
+
+    /// Send a request with credentials, sending it a second time if the server refuses it and
+    /// `challenged` says to retry. The request is retried at most once; if it is refused again,
+    /// the `401 Unauthorized` response is returned like any other.
+    ///
+    /// A request whose headers have already been written (by writing a body) is refused with an
+    /// `InvalidInput` error, as it could not be sent again as it was.
+    fn send<S: Connecter + Reader + Writer>(&mut self, mut request: RequestWriter<S>)
+            -> Result<ResponseReader<S>, Error> {
+        if request.headers_written() {
+            return Err(Error::Io(IoError {
+                kind: InvalidInput,
+                desc: "request sent for authentication has a body",
+                detail: None,
+            }));
+        }
+        let method = request.method.clone();
+        let url = request.url.clone();
+        let headers = request.headers.clone();
+        let record_raw_headers = request.record_raw_headers;
+        let timeouts = request.timeouts;
+        let remote_addr = request.remote_addr;
+
+        let credentials = try!(self.credentials(&method, &url));
+        let sent_credentials = credentials.is_some();
+        if sent_credentials {
+            request.headers.authorization = credentials;
+        }
+        // Once connected, the request has no more use for its resolver; keep it for the retry.
+        try!(request.try_connect());
+        let resolver = mem::replace(&mut request.resolver, Box::new(SystemResolver));
+        // The retry has whatever is left of the time the first attempt had.
+        let deadline = request.deadline();
+        let response = match request.read_response() {
+            Ok(response) => response,
+            Err((_, err)) => return Err(err),
+        };
+        if response.status != Unauthorized {
+            return Ok(response);
+        }
+        let retry = match response.headers.www_authenticate {
+            Some(ref challenges) => self.challenged(&url, challenges, sent_credentials),
+            None => false,
+        };
+        if !retry {
+            return Ok(response);
+        }
+
+        let mut request: RequestWriter<S> = try!(RequestWriter::new(method.clone(), url.clone()));
+        request.headers = headers;
+        request.record_raw_headers = record_raw_headers;
+        request.timeouts = timeouts;
+        request.remote_addr = remote_addr;
+        request.resolver = resolver;
+        request.set_deadline(deadline);
+        match try!(self.credentials(&method, &url)) {
+            Some(credentials) => request.headers.authorization = Some(credentials),
+            None => (),
+        }
+        match request.read_response() {
+            Ok(response) => Ok(response),
+            Err((_, err)) => Err(err),
+        }
+    }
+}
+
+/// The scheme chosen in response to a challenge, and the origin which sent the challenge.
+#[derive(Clone)]
+enum Scheme {
+    Basic(Origin),
+    Digest(Origin, DigestState),
+}
+
+/// The scheme, host and port of a URL, to which credentials are confined.
+#[derive(Clone, PartialEq, Eq)]
+struct Origin {
+    scheme: String,
+    host: Option<String>,
+    port: Option<u16>,
+}
+
+impl Origin {
+    fn of(url: &Url) -> Origin {
+        Origin {
+            scheme: url.scheme.clone(),
+            host: url.serialize_host(),
+            port: url.port_or_default(),
+        }
+    }
+}
+
+/// What is needed to compute Digest credentials for further requests.
+#[derive(Clone)]
+struct DigestState {
+    algorithm: DigestAlgorithm,
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    /// Whether the server offered `qop=auth`; if not, RFC 2069 responses are sent.
+    qop_auth: bool,
+    /// The number of requests made with this nonce so far.
+    nonce_count: u32,
+}
+
+/// A user name and password, answering Digest or Basic challenges.
+pub struct Authenticator {
+    /// The user name to authenticate as.
+    pub username: String,
+    /// The password of the user.
+    pub password: String,
+    scheme: Option<Scheme>,
+}
+
+impl Authenticator {
+    /// An authenticator for the given user, which has not yet seen a challenge.
+    pub fn new(username: &str, password: &str) -> Authenticator {
+        Authenticator {
+            username: String::from_str(username),
+            password: String::from_str(password),
+            scheme: None,
+        }
+    }
+
+    /// Choose a scheme from a set of challenges sent for a request to `url`, returning whether
+    /// there was one which can be answered.
+    fn accept(&mut self, url: &Url, challenges: &Challenges) -> bool {
+        let origin = Origin::of(url);
+        let digest = best_digest(challenges).map(|(algorithm, challenge)| DigestState {
+            algorithm: algorithm,
+            realm: String::from_str(challenge.param("realm").unwrap_or("")),
+            nonce: String::from_str(challenge.param("nonce").unwrap_or("")),
+            opaque: challenge.param("opaque").map(|opaque| String::from_str(opaque)),
+            qop_auth: challenge.param("qop").map_or(false, |qop| {
+                qop.split(',').any(|qop| qop.trim() == "auth")
+            }),
+            nonce_count: 0,
+        });
+        self.scheme = match digest {
+            Some(digest) => Some(Scheme::Digest(origin, digest)),
+            None if challenges.find("Basic").is_some() => Some(Scheme::Basic(origin)),
+            None => return false,
+        };
+        true
+    }
+
+}
+
+impl AuthProvider for Authenticator {
+    /// Credentials are only sent once a challenge has been accepted, and only to the origin which
+    /// sent it.
+    fn credentials(&mut self, method: &Method, url: &Url) -> IoResult<Option<Credentials>> {
+        let origin = Origin::of(url);
+        Ok(match self.scheme {
+            None => None,
+            Some(Scheme::Basic(ref challenged)) | Some(Scheme::Digest(ref challenged, _))
+                if *challenged != origin => None,
+            Some(Scheme::Basic(_)) => Some(Credentials::basic(&self.username[], &self.password[])),
+            Some(Scheme::Digest(_, ref mut state)) => {
+                state.nonce_count += 1;
+                let uri = match url.query {
+                    Some(ref query) => format!("{}?{}", url.serialize_path().unwrap(), query),
+                    None => url.serialize_path().unwrap(),
+                };
+                Some(digest_credentials(state, &self.username[], &self.password[],
+                                        &format!("{:?}", method)[], &uri[]))
+            },
+        })
+    }
+
+    /// Rejected credentials are only retried if the server says that the Digest nonce used was
+    /// stale.
+    fn challenged(&mut self, url: &Url, challenges: &Challenges, sent_credentials: bool) -> bool {
+        self.accept(url, challenges) && (!sent_credentials || is_stale(challenges))
+    }
+}
+
+/// A fixed bearer token (RFC 6750), such as a personal access token. A refused token is not
+/// retried; see `oauth2::ClientCredentials` for tokens which are fetched and refreshed.
+#[derive(Clone)]
+pub struct Bearer {
+    pub token: String,
+}
+
+impl Bearer {
+    pub fn new(token: &str) -> Bearer {
+        Bearer {
+            token: String::from_str(token),
+        }
+    }
+}
+
+impl AuthProvider for Bearer {
+    fn credentials(&mut self, _: &Method, _: &Url) -> IoResult<Option<Credentials>> {
+        Ok(Some(Credentials::bearer(&self.token[])))
+    }
+
+    fn challenged(&mut self, _: &Url, _: &Challenges, _: bool) -> bool {
+        false
+    }
+}
+
+/// Whether any challenge says that the nonce of the credentials sent was stale.
+fn is_stale(challenges: &Challenges) -> bool {
+    challenges.0.iter().any(|challenge| {
+        challenge.is_scheme("Digest") &&
+            challenge.param("stale").map_or(false, |stale| stale.eq_ignore_ascii_case("true"))
+    })
+}
+
+/// The Digest challenge with the strongest supported algorithm, if there is one.
+#[cfg(feature = "ssl")]
+fn best_digest(challenges: &Challenges) -> Option<(DigestAlgorithm, &Challenge)> {
+    use headers::authorization::{Md5, Sha256};
+
+    let mut best: Option<(DigestAlgorithm, &Challenge)> = None;
+    for challenge in challenges.0.iter() {
+        if !challenge.is_scheme("Digest") || challenge.param("nonce").is_none() {
+            continue;
+        }
+        // The session variants need the server to remember the client nonce; few support them.
+        let algorithm = match DigestAlgorithm::from_param(challenge.param("algorithm")) {
+            Some(Sha256) => Sha256,
+            Some(Md5) => Md5,
+            _ => continue,
+        };
+        best = match best {
+            Some((Sha256, _)) => best,
+            Some(_) if algorithm == Md5 => best,
+            _ => Some((algorithm, challenge)),
+        };
+    }
+    best
+}
+
+/// Without the `ssl` feature there is no hashing, and so no Digest.
+#[cfg(not(feature = "ssl"))]
+fn best_digest(_: &Challenges) -> Option<(DigestAlgorithm, &Challenge)> {
+    None
+}
+
+#[cfg(feature = "ssl")]
+fn digest_credentials(state: &DigestState, username: &str, password: &str, method: &str,
+                      uri: &str) -> Credentials {
+    use std::rand::{OsRng, Rng};
+    use rustc_serialize::hex::ToHex;
+
+    let mut rng = OsRng::new().ok().expect("failed to open the OS random number generator");
+    let mut cnonce = [0u8; 16];
+    rng.fill_bytes(&mut cnonce);
+    let cnonce = cnonce.to_hex();
+    let nc = format!("{:08x}", state.nonce_count);
+    let qop = if state.qop_auth { Some("auth") } else { None };
+
+    let ha1 = state.algorithm.ha1(username, &state.realm[], password, &state.nonce[], &cnonce[]);
+    let response = state.algorithm.response(&ha1[], &state.nonce[], &nc[], &cnonce[], qop,
+                                            method, uri);
+
+    let param = |name: &str, value: &str| (String::from_str(name), String::from_str(value));
+    let mut params = vec!(param("username", username),
+                          param("realm", &state.realm[]),
+                          param("uri", uri),
+                          param("algorithm", state.algorithm.name()),
+                          param("nonce", &state.nonce[]));
+    if state.qop_auth {
+        params.push(param("nc", &nc[]));
+        params.push(param("cnonce", &cnonce[]));
+        params.push(param("qop", "auth"));
+    }
+    params.push(param("response", &response[]));
+    match state.opaque {
+        Some(ref opaque) => params.push(param("opaque", &opaque[])),
+        None => (),
+    }
+    Credentials {
+        scheme: String::from_str("Digest"),
+        data: AuthParams(params),
+    }
+}
+
+#[cfg(not(feature = "ssl"))]
+fn digest_credentials(_: &DigestState, _: &str, _: &str, _: &str, _: &str) -> Credentials {
+    unreachable!()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Listener, InvalidInput};
+    use std::io::net::tcp::TcpListener;
+    use client::error::Error;
+    use client::request::RequestWriter;
+    use headers::authorization::{Challenge, Challenges, AuthParams};
+    use method::Method::{Get, Put};
+    use url::Url;
+    use super::{AuthProvider, Authenticator, Bearer, is_stale};
+
+    fn challenge(scheme: &str, params: &[(&str, &str)]) -> Challenge {
+        Challenge {
+            scheme: String::from_str(scheme),
+            data: AuthParams(params.iter().map(|&(name, value)| {
+                (String::from_str(name), String::from_str(value))
+            }).collect()),
+        }
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut auth = Authenticator::new("Aladdin", "open sesame");
+        let url = Url::parse("http://example.com/").unwrap();
+        assert!(auth.credentials(&Get, &url).unwrap().is_none());
+        assert!(!auth.accept(&url, &Challenges(vec!(challenge("Newauth", &[("realm", "x")])))));
+        assert!(auth.accept(&url, &Challenges(vec!(challenge("Newauth", &[("realm", "x")]),
+                                                   Challenge::basic("WallyWorld")))));
+        assert_eq!(auth.credentials(&Get, &url).unwrap().unwrap().basic_user_pass(),
+                   Some((String::from_str("Aladdin"), String::from_str("open sesame"))));
+    }
+
+    #[test]
+    fn test_credentials_confined_to_origin() {
+        let mut auth = Authenticator::new("Aladdin", "open sesame");
+        let url = Url::parse("http://example.com/a").unwrap();
+        assert!(auth.challenged(&url, &Challenges(vec!(Challenge::basic("WallyWorld"))), false));
+        assert!(auth.credentials(&Get, &Url::parse("http://example.com:80/b").unwrap())
+                    .unwrap().is_some());
+        assert!(auth.credentials(&Get, &Url::parse("http://example.org/a").unwrap())
+                    .unwrap().is_none());
+        assert!(auth.credentials(&Get, &Url::parse("http://example.com:8080/a").unwrap())
+                    .unwrap().is_none());
+        assert!(auth.credentials(&Get, &Url::parse("https://example.com/a").unwrap())
+                    .unwrap().is_none());
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn test_digest() {
+        let mut auth = Authenticator::new("Mufasa", "Circle of Life");
+        let url = Url::parse("http://example.com/dir/index.html?x=1").unwrap();
+        assert!(auth.accept(&url, &Challenges(vec!(
+            challenge("Digest", &[("realm", "http-auth@example.org"), ("qop", "auth, auth-int"),
+                                  ("algorithm", "MD5"), ("nonce", "abc")]),
+            challenge("Digest", &[("realm", "http-auth@example.org"), ("qop", "auth"),
+                                  ("algorithm", "SHA-256"), ("nonce", "abc"),
+                                  ("opaque", "xyz")]),
+            Challenge::basic("http-auth@example.org")))));
+
+        let first = auth.credentials(&Get, &url).unwrap().unwrap();
+        assert!(first.is_scheme("Digest"));
+        assert_eq!(first.param("algorithm"), Some("SHA-256"));
+        assert_eq!(first.param("uri"), Some("/dir/index.html?x=1"));
+        assert_eq!(first.param("nc"), Some("00000001"));
+        assert_eq!(first.param("qop"), Some("auth"));
+        assert_eq!(first.param("opaque"), Some("xyz"));
+        assert_eq!(first.param("response").map(|r| r.len()), Some(64));
+
+        let second = auth.credentials(&Get, &url).unwrap().unwrap();
+        assert_eq!(second.param("nc"), Some("00000002"));
+        assert!(second.param("cnonce") != first.param("cnonce"));
+    }
+
+    #[test]
+    fn test_bearer() {
+        let mut bearer = Bearer::new("mF_9.B5f-4.1JqM");
+        let url = Url::parse("http://example.com/").unwrap();
+        let credentials = bearer.credentials(&Get, &url).unwrap().unwrap();
+        assert!(credentials.is_scheme("Bearer"));
+        assert!(!bearer.challenged(&url, &Challenges(vec!(challenge("Bearer", &[]))), true));
+    }
+
+    #[test]
+    fn test_refuses_body() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        let _acceptor = listener.listen().unwrap();
+
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let mut request: RequestWriter = RequestWriter::new(Put, url).unwrap();
+        request.headers.content_length = Some(1);
+        request.write(b"x").unwrap();
+        match Bearer::new("token").send(request) {
+            Err(Error::Io(ref err)) if err.kind == InvalidInput => (),
+            Ok(_) => panic!("a request with a body was sent"),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_is_stale() {
+        assert!(is_stale(&Challenges(vec!(challenge("Digest", &[("nonce", "a"),
+                                                                ("stale", "true")])))));
+        assert!(!is_stale(&Challenges(vec!(challenge("Digest", &[("nonce", "a")])))));
+        assert!(!is_stale(&Challenges(vec!(challenge("Basic", &[("stale", "true")])))));
+    }
+}
@@ -0,0 +1,651 @@
+/*!
+
+A private HTTP cache for the client, following RFC 7234.
+
+Responses to `GET` requests sent through `Cache::send` are kept in memory (and, if the cache was
+created with `Cache::with_disk_store`, in a directory on disk as well) whenever RFC 7234 permits
+them to be stored. A later request for the same resource is answered from the cache without
+touching the network while the stored response is fresh. Once it has gone stale it is revalidated
+with a conditional request built from its `ETag` and `Last-Modified` validators; a
+`304 Not Modified` answer refreshes the stored response rather than replacing it.
+
+Being a private cache, `s-maxage` and `proxy-revalidate` are ignored, and `private` responses and
+responses to requests with an Authorization header are stored. A cache must therefore not be shared
+between users.
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::RequestWriter;
+use http::client::cache::Cache;
+use http::method::Get;
+use url::Url;
+
+fn main() {
+    let mut cache = Cache::new();
+    for _ in range(0us, 2) {
+        let url = Url::parse("http://example.com/").unwrap();
+        let request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+        // The second time around, this will not make a request at all if the response was fresh.
+        let response = cache.send(request).unwrap();
+        println!("{:?}: {} bytes", response.status, response.body.len());
+    }
+}
+```
+
+*/
+
+use std::ascii::AsciiExt;
+use std::cmp::max;
+use std::collections::HashMap;
+use std::hash::{hash, SipHasher};
+use std::io::{self, BufferedReader, File, IoResult, IoError, OtherIoError};
+use std::io::fs;
+use time::{self, Tm};
+
+use client::error::Error;
+use client::request::RequestWriter;
+use connecter::Connecter;
+use headers::{HeaderEnum, parse_header_value};
+use headers::etag::EntityTags;
+use headers::cache_control::{MaxAge, MaxStale, MinFresh, NoCache, NoStore, OnlyIfCached,
+                             MustRevalidate, Public};
+use headers::serialization_utils::comma_split_iter;
+use headers::request;
+use headers::response;
+use method::Method::{Get, Head, Options, Trace};
+use status::Status;
+use status::Status::{NotModified, GatewayTimeout};
+
+/// A response as held by the cache.
+#[derive(Clone)]
+pub struct CachedResponse {
+    /// The HTTP version number of the response.
+    pub version: (usize, usize),
+
+    /// The HTTP status of the response.
+    pub status: Status,
+
+    /// The headers of the response. When served from the cache, `Age` is set to the current age.
+    pub headers: response::HeaderCollection,
+
+    /// The complete body of the response.
+    pub body: Vec<u8>,
+
+    /// When the request which produced this response was sent, in seconds since the epoch.
+    pub request_time: i64,
+
+    /// When this response was received, in seconds since the epoch.
+    pub response_time: i64,
+
+    /// The values of the request headers named by the response's Vary header at the time the
+    /// response was stored (`None` where the header was absent). A stored response can only be
+    /// used for a request whose values for those headers match.
+    pub vary: Vec<(String, Option<String>)>,
+}
+
+fn now() -> i64 {
+    time::get_time().sec
+}
+
+fn seconds(tm: &Tm) -> i64 {
+    tm.to_timespec().sec
+}
+
+/// The status codes which RFC 7231, section 6.1 defines as cacheable by default; responses with
+/// these may be given a heuristic freshness lifetime.
+fn is_heuristically_cacheable(status: &Status) -> bool {
+    match status.code() {
+        200 | 203 | 204 | 300 | 301 | 404 | 405 | 410 | 414 | 501 => true,
+        _ => false,
+    }
+}
+
+/// The header names listed in a response's Vary header.
+fn vary_names(headers: &response::HeaderCollection) -> Vec<String> {
+    match headers.vary {
+        Some(ref vary) => comma_split_iter(&vary[]).map(|name| String::from_str(name.trim()))
+                                                   .filter(|name| name.len() > 0)
+                                                   .collect(),
+        None => Vec::new(),
+    }
+}
+
+impl CachedResponse {
+    /// The freshness lifetime of the response in seconds, per RFC 7234, section 4.2.1.
+    pub fn freshness_lifetime(&self) -> i64 {
+        match self.headers.cache_control {
+            Some(ref directives) => for directive in directives.iter() {
+                match *directive {
+                    MaxAge(s) => return s as i64,
+                    _ => (),
+                }
+            },
+            None => (),
+        }
+
+        let date = match self.headers.date {
+            Some(ref date) => seconds(date),
+            None => self.response_time,
+        };
+
+        match self.headers.expires {
+            // An invalid Expires (most commonly "0") means the response is already expired.
+            Some(ref expires) => return match parse_header_value::<Tm>(&expires[]) {
+                Some(expires) => max(0, seconds(&expires) - date),
+                None => 0,
+            },
+            None => (),
+        }
+
+        // RFC 7234, section 4.2.2: a heuristic of a tenth of the time since last modification.
+        match self.headers.last_modified {
+            Some(ref last_modified) if is_heuristically_cacheable(&self.status) => {
+                max(0, (date - seconds(last_modified)) / 10)
+            },
+            _ => 0,
+        }
+    }
+
+    /// The current age of the response in seconds, per RFC 7234, section 4.2.3.
+    pub fn current_age(&self, now: i64) -> i64 {
+        let date = match self.headers.date {
+            Some(ref date) => seconds(date),
+            None => self.response_time,
+        };
+        let age_value = match self.headers.age {
+            Some(ref age) => age.trim().parse::<i64>().unwrap_or(0),
+            None => 0,
+        };
+        let apparent_age = max(0, self.response_time - date);
+        let response_delay = self.response_time - self.request_time;
+        let corrected_age_value = age_value + response_delay;
+        let corrected_initial_age = max(apparent_age, corrected_age_value);
+        let resident_time = now - self.response_time;
+        corrected_initial_age + resident_time
+    }
+
+    /// Whether the response may be used to satisfy a request with the given headers without
+    /// validating it first (RFC 7234, section 4.2 and section 5.2.1).
+    pub fn is_fresh_for(&self, request: &request::HeaderCollection, now: i64) -> bool {
+        let mut must_revalidate = false;
+        match self.headers.cache_control {
+            Some(ref directives) => for directive in directives.iter() {
+                match *directive {
+                    NoCache(_) => return false,
+                    MustRevalidate => must_revalidate = true,
+                    _ => (),
+                }
+            },
+            None => (),
+        }
+
+        let lifetime = self.freshness_lifetime();
+        let age = self.current_age(now);
+        let mut fresh = lifetime > age;
+        match request.cache_control {
+            Some(ref directives) => for directive in directives.iter() {
+                match *directive {
+                    NoCache(_) => return false,
+                    MaxAge(s) if age > s as i64 => return false,
+                    MinFresh(s) if lifetime - age < s as i64 => return false,
+                    MaxStale(limit) if !fresh && !must_revalidate => fresh = match limit {
+                        Some(s) => age - lifetime <= s as i64,
+                        None => true,
+                    },
+                    _ => (),
+                }
+            },
+            // RFC 7234, section 5.4: Pragma: no-cache is only honoured without Cache-Control.
+            None => match request.pragma {
+                Some(ref pragma) if pragma[].eq_ignore_ascii_case("no-cache") => return false,
+                _ => (),
+            },
+        }
+        fresh
+    }
+
+    /// Whether this response to a request with the given headers may be stored by a private
+    /// cache, per RFC 7234, section 3. (The restriction on responses to requests with an
+    /// Authorization header, in section 3.2, is for shared caches only.)
+    fn is_storable(&self, request: &request::HeaderCollection) -> bool {
+        // Partial and Not Modified responses are not the whole of the resource; this cache does
+        // not combine them into stored responses (RFC 7234, section 3.1), nor replay them alone.
+        match self.status.code() {
+            206 | 304 => return false,
+            code if code < 200 => return false,
+            _ => (),
+        }
+        let mut explicit = false;
+        for directives in [&request.cache_control, &self.headers.cache_control].iter() {
+            match **directives {
+                Some(ref directives) => for directive in directives.iter() {
+                    match *directive {
+                        NoStore => return false,
+                        MaxAge(_) | Public => explicit = true,
+                        _ => (),
+                    }
+                },
+                None => (),
+            }
+        }
+        if vary_names(&self.headers).iter().any(|name| &name[] == "*") {
+            return false;
+        }
+        explicit || self.headers.expires.is_some() || is_heuristically_cacheable(&self.status)
+    }
+
+    /// Whether this response was stored for a request whose varying headers match these.
+    fn matches_vary(&self, request: &request::HeaderCollection) -> bool {
+        self.vary.iter().all(|&(ref name, ref value)| request.get_raw(&name[]) == *value)
+    }
+
+    /// Update the stored response with the headers of a `304 Not Modified` response to a
+    /// validation request, per RFC 7234, section 4.3.4.
+    fn freshen(&mut self, not_modified: &response::HeaderCollection, request_time: i64,
+               response_time: i64) {
+        // The headers of the 304 replace those stored, but all occurrences of each are kept.
+        let mut replaced: Vec<String> = Vec::new();
+        for header in not_modified.iter() {
+            let name = header.header_name().to_ascii_lowercase();
+            if replaced.contains(&name) {
+                self.headers.insert(header);
+            } else {
+                self.headers.set(header);
+                replaced.push(name);
+            }
+        }
+        self.request_time = request_time;
+        self.response_time = response_time;
+    }
+}
+
+/// An HTTP cache; see the module documentation.
+pub struct Cache {
+    entries: HashMap<String, CachedResponse>,
+    disk: Option<Path>,
+}
+
+impl Cache {
+    /// Create a cache which holds responses in memory only.
+    pub fn new() -> Cache {
+        Cache {
+            entries: HashMap::new(),
+            disk: None,
+        }
+    }
+
+    /// Create a cache which holds responses in memory and also writes them to the given
+    /// directory, so that they survive the cache (or the process) going away. The directory is
+    /// created if it does not exist.
+    pub fn with_disk_store(directory: Path) -> IoResult<Cache> {
+        try!(fs::mkdir_recursive(&directory, io::USER_RWX));
+        Ok(Cache {
+            entries: HashMap::new(),
+            disk: Some(directory),
+        })
+    }
+
+    /// Send a request, using and updating the cache as appropriate.
+    ///
+    /// Requests with methods other than `GET` always go to the network; if their method is unsafe
+    /// and they succeed, any stored response for the URL is invalidated.
+    pub fn send<S: Connecter + Reader + Writer>(&mut self, mut request: RequestWriter<S>)
+            -> Result<CachedResponse, Error> {
+        let key = request.url.serialize();
+        let request_headers = request.headers.clone();
+
+        if request.method != Get {
+            let method = request.method.clone();
+            let response = try!(fetch(request));
+            let safe = match method {
+                Get | Head | Options | Trace => true,
+                _ => false,
+            };
+            if !safe && response.status.code() < 400 {
+                self.remove(&key[]);
+            }
+            return Ok(response);
+        }
+
+        let stored = self.lookup(&key[], &request_headers);
+        let now = now();
+        match stored {
+            Some(ref entry) if entry.is_fresh_for(&request_headers, now) => {
+                let mut entry = entry.clone();
+                entry.headers.age = Some(format!("{}", entry.current_age(now)));
+                return Ok(entry);
+            },
+            _ => (),
+        }
+
+        let only_if_cached = match request_headers.cache_control {
+            Some(ref directives) => directives.iter().any(|d| *d == OnlyIfCached),
+            None => false,
+        };
+        if only_if_cached {
+            // RFC 7234, section 5.2.1.7: respond with 504 (Gateway Timeout) if there is nothing
+            // suitable stored.
+            let mut headers = response::HeaderCollection::new();
+            headers.content_length = Some(0);
+            return Ok(CachedResponse {
+                version: (1, 1),
+                status: GatewayTimeout,
+                headers: headers,
+                body: Vec::new(),
+                request_time: now,
+                response_time: now,
+                vary: Vec::new(),
+            });
+        }
+
+        // The stored response is stale, so make the request conditional upon it.
+        match stored {
+            Some(ref entry) => {
+                match entry.headers.etag {
+                    Some(ref etag) => {
+                        request.headers.if_none_match = Some(EntityTags(vec!(etag.clone())));
+                    },
+                    None => (),
+                }
+                match entry.headers.last_modified {
+                    Some(ref last_modified) => {
+                        request.headers.if_modified_since = Some(last_modified.clone());
+                    },
+                    None => (),
+                }
+            },
+            None => (),
+        }
+
+        let mut response = try!(fetch(request));
+        match stored {
+            Some(mut entry) => if response.status == NotModified {
+                entry.freshen(&response.headers, response.request_time, response.response_time);
+                self.store(&key[], entry.clone());
+                entry.headers.age = Some(format!("{}", entry.current_age(now())));
+                return Ok(entry);
+            },
+            None => (),
+        }
+
+        if response.is_storable(&request_headers) {
+            response.vary = vary_names(&response.headers).into_iter().map(|name| {
+                let value = request_headers.get_raw(&name[]);
+                (name, value)
+            }).collect();
+            self.store(&key[], response.clone());
+        } else {
+            self.remove(&key[]);
+        }
+        Ok(response)
+    }
+
+    /// Remove any stored response for the given URL.
+    pub fn invalidate(&mut self, url: &::url::Url) {
+        self.remove(&url.serialize()[]);
+    }
+
+    fn lookup(&mut self, key: &str, request: &request::HeaderCollection)
+            -> Option<CachedResponse> {
+        if !self.entries.contains_key(key) {
+            let loaded = match self.disk {
+                Some(ref directory) => load_from_disk(directory, key).ok(),
+                None => None,
+            };
+            match loaded {
+                Some(entry) => { self.entries.insert(String::from_str(key), entry); },
+                None => return None,
+            }
+        }
+        match self.entries.get(key) {
+            Some(entry) if entry.matches_vary(request) => Some(entry.clone()),
+            _ => None,
+        }
+    }
+
+    fn store(&mut self, key: &str, entry: CachedResponse) {
+        match self.disk {
+            Some(ref directory) => match store_to_disk(directory, key, &entry) {
+                Ok(()) => (),
+                Err(err) => debug!("unable to write cache entry for {}: {}", key, err),
+            },
+            None => (),
+        }
+        self.entries.insert(String::from_str(key), entry);
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+        match self.disk {
+            Some(ref directory) => { let _ = fs::unlink(&disk_path(directory, key)); },
+            None => (),
+        }
+    }
+}
+
+/// Send a request and read the entire response.
+fn fetch<S: Connecter + Reader + Writer>(request: RequestWriter<S>)
+         -> Result<CachedResponse, Error> {
+    let request_time = now();
+    let mut response = match request.read_response() {
+        Ok(response) => response,
+        Err((_, err)) => return Err(err),
+    };
+    let body = try!(response.read_to_end());
+    Ok(CachedResponse {
+        version: response.version,
+        status: response.status.clone(),
+        headers: response.headers.clone(),
+        body: body,
+        request_time: request_time,
+        response_time: now(),
+        vary: Vec::new(),
+    })
+}
+
+fn disk_path(directory: &Path, key: &str) -> Path {
+    directory.join(format!("{:016x}", hash::<_, SipHasher>(&key)))
+}
+
+fn bad_cache_file() -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: "malformed cache file",
+        detail: None,
+    }
+}
+
+/// Write an entry to disk. The format is a line with the key, a line with the times, version and
+/// status, the number of Vary entries and a line for each, and then the headers as they would
+/// appear in a response and the body.
+fn store_to_disk(directory: &Path, key: &str, entry: &CachedResponse) -> IoResult<()> {
+    let mut file = try!(File::create(&disk_path(directory, key)));
+    try!(write!(&mut file, "{}\n", key));
+    try!(write!(&mut file, "{} {} {} {} {} {}\n", entry.request_time, entry.response_time,
+                entry.version.0, entry.version.1, entry.status.code(), entry.status.reason()));
+    try!(write!(&mut file, "{}\n", entry.vary.len()));
+    for &(ref name, ref value) in entry.vary.iter() {
+        try!(match *value {
+            Some(ref value) => write!(&mut file, "{}:{}\n", name, value),
+            None => write!(&mut file, "{}\n", name),
+        });
+    }
+    try!(entry.headers.write_all(&mut file));
+    file.write(&entry.body[])
+}
+
+fn load_from_disk(directory: &Path, key: &str) -> IoResult<CachedResponse> {
+    let mut reader = BufferedReader::new(try!(File::open(&disk_path(directory, key))));
+    if try!(reader.read_line()).trim_right_matches('\n') != key {
+        // A hash collision; the entry belongs to another URL.
+        return Err(bad_cache_file());
+    }
+
+    let line = try!(reader.read_line());
+    let fields: Vec<&str> = line.trim_right_matches('\n').splitn(5, ' ').collect();
+    if fields.len() != 6 {
+        return Err(bad_cache_file());
+    }
+    let mut numbers = Vec::with_capacity(5);
+    for field in fields[..5].iter() {
+        match field.parse::<i64>() {
+            Some(n) => numbers.push(n),
+            None => return Err(bad_cache_file()),
+        }
+    }
+    let (request_time, response_time) = (numbers[0], numbers[1]);
+    let version = (numbers[2] as usize, numbers[3] as usize);
+    let code = numbers[4] as u16;
+    let reason = String::from_str(fields[5]);
+
+    let vary_count = match try!(reader.read_line()).trim().parse::<usize>() {
+        Some(n) => n,
+        None => return Err(bad_cache_file()),
+    };
+    let mut vary = Vec::with_capacity(vary_count);
+    for _ in range(0, vary_count) {
+        let line = try!(reader.read_line());
+        let line = line.trim_right_matches('\n');
+        vary.push(match line.find(':') {
+            Some(i) => (String::from_str(&line[..i]), Some(String::from_str(&line[i + 1..]))),
+            None => (String::from_str(line), None),
+        });
+    }
+
+    let mut headers = response::HeaderCollection::new();
+    loop {
+        let line = try!(reader.read_line());
+        let line = line.trim_right_matches('\n').trim_right_matches('\r');
+        if line.is_empty() {
+            break;
+        }
+        match line.find(':') {
+            Some(i) => {
+                let name = String::from_str(&line[..i]);
+                let _ = headers.insert_raw(name, line[i + 1..].trim_left().as_bytes());
+            },
+            None => return Err(bad_cache_file()),
+        }
+    }
+
+    Ok(CachedResponse {
+        version: version,
+        status: Status::from_code_and_reason(code, reason),
+        headers: headers,
+        body: try!(reader.read_to_end()),
+        request_time: request_time,
+        response_time: response_time,
+        vary: vary,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use time;
+    use headers::HeaderConvertible;
+    use headers::authorization::Credentials;
+    use headers::cache_control::{MaxAge, NoCache, MaxStale, NoStore};
+    use headers::request;
+    use headers::response;
+    use status;
+    use super::CachedResponse;
+
+    fn response(headers: response::HeaderCollection) -> CachedResponse {
+        CachedResponse {
+            version: (1, 1),
+            status: status::Status::Ok,
+            headers: headers,
+            body: Vec::new(),
+            request_time: 1000,
+            response_time: 1002,
+            vary: Vec::new(),
+        }
+    }
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    #[test]
+    fn test_freshness_lifetime() {
+        let mut headers = response::HeaderCollection::new();
+        assert_eq!(response(headers.clone()).freshness_lifetime(), 0);
+
+        headers.date = Some(at(1000));
+        headers.last_modified = Some(at(0));
+        assert_eq!(response(headers.clone()).freshness_lifetime(), 100);
+
+        headers.expires = Some(String::from_str("0"));
+        assert_eq!(response(headers.clone()).freshness_lifetime(), 0);
+
+        headers.expires = Some(at(1060).http_value());
+        assert_eq!(response(headers.clone()).freshness_lifetime(), 60);
+
+        headers.cache_control = Some(vec!(MaxAge(30)));
+        assert_eq!(response(headers.clone()).freshness_lifetime(), 30);
+
+        let mut headers = response::HeaderCollection::new();
+        headers.last_modified = Some(at(0));
+        let mut r = response(headers);
+        r.status = status::Status::Found;
+        assert_eq!(r.freshness_lifetime(), 0);
+    }
+
+    #[test]
+    fn test_current_age() {
+        let mut headers = response::HeaderCollection::new();
+        headers.date = Some(at(990));
+        // apparent age 12, corrected age value 2; resident for 10
+        assert_eq!(response(headers.clone()).current_age(1012), 22);
+        headers.age = Some(String::from_str("20"));
+        assert_eq!(response(headers).current_age(1012), 32);
+    }
+
+    #[test]
+    fn test_is_fresh_for() {
+        let mut headers = response::HeaderCollection::new();
+        headers.date = Some(at(1002));
+        headers.cache_control = Some(vec!(MaxAge(60)));
+        let r = response(headers);
+        let mut request = request::HeaderCollection::new();
+        assert!(r.is_fresh_for(&request, 1030));
+        assert!(!r.is_fresh_for(&request, 1070));
+
+        request.cache_control = Some(vec!(MaxStale(None)));
+        assert!(r.is_fresh_for(&request, 1070));
+        request.cache_control = Some(vec!(MaxStale(Some(5))));
+        assert!(!r.is_fresh_for(&request, 1070));
+        request.cache_control = Some(vec!(NoCache(vec!())));
+        assert!(!r.is_fresh_for(&request, 1030));
+
+        request.cache_control = None;
+        request.pragma = Some(String::from_str("no-cache"));
+        assert!(!r.is_fresh_for(&request, 1030));
+    }
+
+    #[test]
+    fn test_is_storable() {
+        let mut headers = response::HeaderCollection::new();
+        let mut request = request::HeaderCollection::new();
+        headers.cache_control = Some(vec!(MaxAge(60)));
+        assert!(response(headers.clone()).is_storable(&request));
+        // A private cache may store responses to authenticated requests.
+        request.authorization = Some(Credentials::basic("Aladdin", "open sesame"));
+        assert!(response(headers.clone()).is_storable(&request));
+
+        request.cache_control = Some(vec!(NoStore));
+        assert!(!response(headers.clone()).is_storable(&request));
+
+        // Explicit freshness does not make a partial response storable.
+        request.cache_control = None;
+        let mut partial = response(headers);
+        partial.status = status::Status::PartialContent;
+        assert!(!partial.is_storable(&request));
+        partial.status = status::Status::NotModified;
+        assert!(!partial.is_storable(&request));
+        partial.status = status::Status::ServiceUnavailable;
+        assert!(partial.is_storable(&request));
+    }
+}
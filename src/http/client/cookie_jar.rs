@@ -0,0 +1,525 @@
+/*!
+
+A cookie jar for the client, following RFC 6265.
+
+A `CookieJar` takes in the cookies set by responses and attaches those that apply to later
+requests, so that a series of requests sent with `send` behaves like a browser session:
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::RequestWriter;
+use http::client::cookie_jar::CookieJar;
+use http::method::{Get, Post};
+use url::Url;
+
+fn main() {
+    let mut jar = CookieJar::new();
+
+    let url = Url::parse("https://example.com/login").unwrap();
+    let request: RequestWriter = RequestWriter::new(Post, url).unwrap();
+    jar.send(request).unwrap();
+
+    // Sent with the session cookie set by the login.
+    let url = Url::parse("https://example.com/account").unwrap();
+    let request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+    let response = jar.send(request).unwrap();
+    println!("{:?}", response.status);
+}
+```
+
+Requests sent some other way (through a `Cache` or an `AuthProvider`, say) can be given their
+cookies with `attach`, and their responses stored with `store_response`.
+
+Domain and path matching, host-only cookies, expiry (by `Max-Age` or `Expires`) and the `Secure`
+flag are all honoured. There is no public suffix list, so the only protection against a cookie
+being set for a whole top-level domain is that a `Domain` attribute must contain a dot.
+
+The jar can be saved to and loaded from the Netscape `cookies.txt` format used by curl, wget and
+browser extensions, with `write_netscape` and `read_netscape`.
+
+*/
+
+use std::ascii::AsciiExt;
+use std::cmp::Ordering::Equal;
+use std::io::{IoResult, IoError, InvalidInput};
+use std::io::net::ip::IpAddr;
+use std::i64;
+use time;
+use url::Url;
+
+use client::error::Error;
+use client::request::RequestWriter;
+use client::response::ResponseReader;
+use connecter::Connecter;
+use headers::cookie::{Cookie, SetCookie, SetCookies};
+
+/// A cookie as held by the jar.
+#[derive(Clone, PartialEq, Eq, Show)]
+pub struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    /// The domain, in lowercase, without any leading dot.
+    pub domain: String,
+    /// Whether the cookie is only to be sent to `domain` itself, rather than to its subdomains
+    /// too; this is the case for cookies set without a Domain attribute.
+    pub host_only: bool,
+    pub path: String,
+    /// When the cookie expires, in seconds since the epoch; `None` for a session cookie.
+    pub expires: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    creation_index: u64,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: i64) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        }
+    }
+
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        let domain_ok = if self.host_only {
+            host == &self.domain[]
+        } else {
+            domain_match(host, &self.domain[])
+        };
+        (secure || !self.secure) && domain_ok && path_match(path, &self.path[])
+    }
+}
+
+/// A collection of cookies received from servers.
+#[derive(Clone)]
+pub struct CookieJar {
+    cookies: Vec<StoredCookie>,
+    next_index: u64,
+}
+
+impl CookieJar {
+    /// Create an empty cookie jar.
+    pub fn new() -> CookieJar {
+        CookieJar {
+            cookies: Vec::new(),
+            next_index: 0,
+        }
+    }
+
+    /// All the cookies in the jar, including any which have expired but not yet been removed.
+    pub fn cookies(&self) -> &[StoredCookie] {
+        &self.cookies[]
+    }
+
+    /// Store the cookies set by a response to a request for `url`. Cookies which may not be set
+    /// by that URL are ignored, and cookies which have already expired remove any cookie of the
+    /// same name, domain and path.
+    pub fn store(&mut self, url: &Url, set_cookies: &SetCookies) {
+        let now = time::get_time().sec;
+        for cookie in set_cookies.0.iter() {
+            self.store_cookie(url, cookie, now);
+        }
+    }
+
+    /// Store the cookies set by a response, if any.
+    pub fn store_response<S>(&mut self, response: &ResponseReader<S>) {
+        match response.headers.set_cookie {
+            Some(ref set_cookies) => self.store(&response.request.url, set_cookies),
+            None => (),
+        }
+    }
+
+    fn store_cookie(&mut self, url: &Url, cookie: &SetCookie, now: i64) {
+        let host = match url.domain() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return,
+        };
+
+        let (domain, host_only) = match cookie.domain {
+            Some(ref domain) => {
+                // Without a public suffix list, refuse at least cookies for top-level domains.
+                if !domain.contains_char('.') || !domain_match(&host[], &domain[]) {
+                    return;
+                }
+                (domain.clone(), false)
+            },
+            None => (host, true),
+        };
+        let path = match cookie.path {
+            Some(ref path) => path.clone(),
+            None => default_path(url),
+        };
+        let expires = match (cookie.max_age, &cookie.expires) {
+            // A Max-Age too great to add is as good as forever (RFC 6265, section 5.2.2).
+            (Some(max_age), _) => match now.checked_add(max_age) {
+                Some(expires) => Some(expires),
+                None if max_age > 0 => Some(i64::MAX),
+                None => Some(i64::MIN),
+            },
+            (None, &Some(ref expires)) => Some(expires.to_timespec().sec),
+            (None, &None) => None,
+        };
+
+        let existing = self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == domain && c.path == path
+        });
+        let creation_index = match existing {
+            Some(i) => self.cookies.remove(i).creation_index,
+            None => {
+                self.next_index += 1;
+                self.next_index
+            },
+        };
+
+        let stored = StoredCookie {
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain: domain,
+            host_only: host_only,
+            path: path,
+            expires: expires,
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            creation_index: creation_index,
+        };
+        if !stored.is_expired(now) {
+            self.cookies.push(stored);
+        }
+    }
+
+    /// The cookies to send with a request for `url`, ordered as RFC 6265 recommends: longer paths
+    /// first, and then earlier-created cookies first.
+    pub fn cookies_for(&self, url: &Url) -> Vec<&StoredCookie> {
+        self.cookies_for_at(url, time::get_time().sec)
+    }
+
+    fn cookies_for_at(&self, url: &Url, now: i64) -> Vec<&StoredCookie> {
+        let host = match url.domain() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return Vec::new(),
+        };
+        let path = url.serialize_path().unwrap_or(String::from_str("/"));
+        let secure = &url.scheme[] == "https";
+
+        let mut cookies: Vec<&StoredCookie> = self.cookies.iter().filter(|c| {
+            !c.is_expired(now) && c.matches(&host[], &path[], secure)
+        }).collect();
+        cookies.sort_by(|a, b| match b.path.len().cmp(&a.path.len()) {
+            Equal => a.creation_index.cmp(&b.creation_index),
+            order => order,
+        });
+        cookies
+    }
+
+    /// The Cookie header to send with a request for `url`, if any cookies apply.
+    pub fn cookie_header(&self, url: &Url) -> Option<Cookie> {
+        let cookies = self.cookies_for(url);
+        if cookies.is_empty() {
+            None
+        } else {
+            Some(Cookie {
+                pairs: cookies.iter().map(|c| (c.name.clone(), c.value.clone())).collect(),
+            })
+        }
+    }
+
+    /// Set the Cookie header of a request to the cookies that apply to its URL. Any Cookie header
+    /// already set is replaced if there are cookies to send, and left alone otherwise.
+    pub fn attach<S>(&self, request: &mut RequestWriter<S>) {
+        match self.cookie_header(&request.url) {
+            Some(cookie) => request.headers.cookie = Some(cookie),
+            None => (),
+        }
+    }
+
+    /// Send a request with the cookies that apply to it, storing the cookies set by its response.
+    pub fn send<S: Connecter + Reader + Writer>(&mut self, mut request: RequestWriter<S>)
+            -> Result<ResponseReader<S>, Error> {
+        self.attach(&mut request);
+        match request.read_response() {
+            Ok(response) => {
+                self.store_response(&response);
+                Ok(response)
+            },
+            Err((_, err)) => Err(err),
+        }
+    }
+
+    /// Remove all expired cookies.
+    pub fn remove_expired(&mut self) {
+        let now = time::get_time().sec;
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+
+    /// Remove all session cookies, as happens when a browser is closed.
+    pub fn remove_session_cookies(&mut self) {
+        self.cookies.retain(|c| c.expires.is_some());
+    }
+
+    /// Write the jar in the Netscape cookies.txt format. Session cookies are written with an
+    /// expiry time of zero, as curl does.
+    pub fn write_netscape<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        try!(writer.write_str("# Netscape HTTP Cookie File\n"));
+        for c in self.cookies.iter() {
+            try!(write!(writer, "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                        if c.http_only { "#HttpOnly_" } else { "" },
+                        if c.host_only { "" } else { "." },
+                        c.domain,
+                        if c.host_only { "FALSE" } else { "TRUE" },
+                        c.path,
+                        if c.secure { "TRUE" } else { "FALSE" },
+                        c.expires.unwrap_or(0),
+                        c.name,
+                        c.value));
+        }
+        Ok(())
+    }
+
+    /// Read a jar in the Netscape cookies.txt format. Comments, blank lines and expired cookies
+    /// are skipped; a malformed line is an error.
+    pub fn read_netscape<B: Buffer>(reader: &mut B) -> IoResult<CookieJar> {
+        let now = time::get_time().sec;
+        let mut jar = CookieJar::new();
+        for line in reader.lines() {
+            let line = try!(line);
+            let line = line.trim_right_matches(['\r', '\n'].as_slice());
+            let (line, http_only) = if line.starts_with("#HttpOnly_") {
+                (&line["#HttpOnly_".len()..], true)
+            } else {
+                (line, false)
+            };
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 7 {
+                return Err(bad_cookie_line(line));
+            }
+            let include_subdomains = match fields[1] {
+                "TRUE" => true,
+                "FALSE" => false,
+                _ => return Err(bad_cookie_line(line)),
+            };
+            let secure = match fields[3] {
+                "TRUE" => true,
+                "FALSE" => false,
+                _ => return Err(bad_cookie_line(line)),
+            };
+            let expires = match fields[4].parse::<i64>() {
+                Some(0) => None,
+                Some(expires) => Some(expires),
+                None => return Err(bad_cookie_line(line)),
+            };
+
+            jar.next_index += 1;
+            let cookie = StoredCookie {
+                name: String::from_str(fields[5]),
+                value: String::from_str(fields[6]),
+                domain: fields[0].trim_left_matches('.').to_ascii_lowercase(),
+                host_only: !include_subdomains,
+                path: String::from_str(fields[2]),
+                expires: expires,
+                secure: secure,
+                http_only: http_only,
+                creation_index: jar.next_index,
+            };
+            if !cookie.is_expired(now) {
+                jar.cookies.push(cookie);
+            }
+        }
+        Ok(jar)
+    }
+}
+
+fn bad_cookie_line(line: &str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "malformed line in cookies.txt",
+        detail: Some(String::from_str(line)),
+    }
+}
+
+/// Domain matching, as defined in RFC 6265, section 5.1.3. Both strings must be in lowercase.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(domain) &&
+                       host.as_bytes()[host.len() - domain.len() - 1] == b'.' &&
+                       host.parse::<IpAddr>().is_none())
+}
+
+/// Path matching, as defined in RFC 6265, section 5.1.4.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path || (request_path.starts_with(cookie_path) &&
+                                    (cookie_path.ends_with("/") ||
+                                     request_path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// The default path of a cookie set by a response to a request for `url` (RFC 6265, section
+/// 5.1.4): the directory of the request path.
+fn default_path(url: &Url) -> String {
+    let path = url.serialize_path().unwrap_or(String::new());
+    match path.rfind('/') {
+        Some(0) | None => String::from_str("/"),
+        Some(i) => String::from_str(&path[..i]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::i64;
+    use std::io::{Acceptor, Listener, BufReader, MemWriter};
+    use std::io::net::tcp::TcpListener;
+    use std::sync::mpsc::channel;
+    use std::thread::Thread;
+    use url::Url;
+    use client::request::RequestWriter;
+    use headers::cookie::SetCookie;
+    use method::Method::Get;
+    use super::{CookieJar, domain_match, path_match, default_path};
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn cookie(name: &str, value: &str) -> SetCookie {
+        SetCookie::new(String::from_str(name), String::from_str(value))
+    }
+
+    fn names(jar: &CookieJar, u: &str, now: i64) -> Vec<String> {
+        jar.cookies_for_at(&url(u), now).iter().map(|c| c.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_matching() {
+        assert!(domain_match("example.com", "example.com"));
+        assert!(domain_match("www.example.com", "example.com"));
+        assert!(!domain_match("wwwexample.com", "example.com"));
+        assert!(!domain_match("example.com", "www.example.com"));
+        assert!(!domain_match("192.168.0.1", "168.0.1"));
+
+        assert!(path_match("/", "/"));
+        assert!(path_match("/docs/a", "/docs"));
+        assert!(path_match("/docs/a", "/docs/"));
+        assert!(!path_match("/docsets", "/docs"));
+        assert!(!path_match("/", "/docs"));
+
+        assert_eq!(default_path(&url("http://example.com/")), String::from_str("/"));
+        assert_eq!(default_path(&url("http://example.com/login")), String::from_str("/"));
+        assert_eq!(default_path(&url("http://example.com/a/b/c")), String::from_str("/a/b"));
+    }
+
+    #[test]
+    fn test_domains() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://www.example.com/");
+        jar.store_cookie(&origin, &cookie("host", "1"), 0);
+        let mut domain = cookie("domain", "2");
+        domain.domain = Some(String::from_str("example.com"));
+        jar.store_cookie(&origin, &domain, 0);
+        let mut other = cookie("other", "3");
+        other.domain = Some(String::from_str("example.org"));
+        jar.store_cookie(&origin, &other, 0);
+        let mut tld = cookie("tld", "4");
+        tld.domain = Some(String::from_str("com"));
+        jar.store_cookie(&origin, &tld, 0);
+
+        assert_eq!(names(&jar, "http://www.example.com/", 0),
+                   vec!(String::from_str("host"), String::from_str("domain")));
+        assert_eq!(names(&jar, "http://a.www.example.com/", 0), vec!(String::from_str("domain")));
+        assert_eq!(names(&jar, "http://example.com/", 0), vec!(String::from_str("domain")));
+        assert_eq!(names(&jar, "http://example.org/", 0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_paths_secure_and_order() {
+        let mut jar = CookieJar::new();
+        let origin = url("https://example.com/a/b");
+        jar.store_cookie(&origin, &cookie("default", "1"), 0);
+        let mut root = cookie("root", "2");
+        root.path = Some(String::from_str("/"));
+        root.secure = true;
+        jar.store_cookie(&origin, &root, 0);
+
+        assert_eq!(names(&jar, "https://example.com/a/c", 0),
+                   vec!(String::from_str("default"), String::from_str("root")));
+        assert_eq!(names(&jar, "http://example.com/a/c", 0), vec!(String::from_str("default")));
+        assert_eq!(names(&jar, "https://example.com/b", 0), vec!(String::from_str("root")));
+    }
+
+    #[test]
+    fn test_expiry_and_replacement() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/");
+        let mut c = cookie("a", "1");
+        c.max_age = Some(60);
+        jar.store_cookie(&origin, &c, 1000);
+        assert_eq!(names(&jar, "http://example.com/", 1059), vec!(String::from_str("a")));
+        assert_eq!(names(&jar, "http://example.com/", 1060), Vec::<String>::new());
+
+        jar.store_cookie(&origin, &cookie("a", "2"), 1000);
+        assert_eq!(jar.cookies().len(), 1);
+        assert_eq!(jar.cookies()[0].value, String::from_str("2"));
+        assert_eq!(jar.cookies()[0].expires, None);
+
+        c.max_age = Some(i64::MAX);
+        jar.store_cookie(&origin, &c, 1000);
+        assert_eq!(jar.cookies()[0].expires, Some(i64::MAX));
+
+        let mut delete = cookie("a", "");
+        delete.max_age = Some(0);
+        jar.store_cookie(&origin, &delete, 1000);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn test_netscape_format() {
+        let text = "# Netscape HTTP Cookie File\n\
+                    \n\
+                    .example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+                    #HttpOnly_example.org\tFALSE\t/app\tTRUE\t4102444800\tsid\txyz\n";
+        let jar = CookieJar::read_netscape(&mut BufReader::new(text.as_bytes())).unwrap();
+        assert_eq!(jar.cookies().len(), 2);
+        assert!(!jar.cookies()[0].host_only);
+        assert_eq!(jar.cookies()[0].expires, None);
+        assert!(jar.cookies()[1].http_only && jar.cookies()[1].secure);
+        assert_eq!(jar.cookies()[1].path, String::from_str("/app"));
+
+        let mut writer = MemWriter::new();
+        jar.write_netscape(&mut writer).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(),
+                   String::from_str("# Netscape HTTP Cookie File\n\
+                                     .example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+                                     #HttpOnly_example.org\tFALSE\t/app\tTRUE\t4102444800\tsid\
+                                     \txyz\n"));
+
+        assert!(CookieJar::read_netscape(&mut BufReader::new(b"example.com\tTRUE\t/\n")).is_err());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        let mut acceptor = listener.listen().unwrap();
+        let (sender, receiver) = channel();
+        Thread::spawn(move || {
+            for _ in range(0us, 2) {
+                let mut stream = acceptor.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let read = stream.read(&mut buf).unwrap_or(0);
+                sender.send(String::from_utf8_lossy(&buf[..read]).into_owned()).unwrap();
+                stream.write_str("HTTP/1.1 200 OK\r\nSet-Cookie: id=1\r\n\
+                                  Content-Length: 0\r\n\r\n").unwrap();
+            }
+        });
+
+        let mut jar = CookieJar::new();
+        for _ in range(0us, 2) {
+            let url = url(&format!("http://{}/", addr)[]);
+            let request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+            jar.send(request).unwrap();
+        }
+        assert!(!receiver.recv().unwrap().contains("Cookie:"));
+        assert!(receiver.recv().unwrap().contains("Cookie: id=1\r\n"));
+    }
+}
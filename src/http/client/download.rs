@@ -0,0 +1,295 @@
+/*!
+
+Resumable downloads to a file.
+
+A `Download` fetches a URL with `GET` and writes the body to a file. Should the connection drop
+part-way through, the request is sent again with a `Range: bytes=N-` header asking for just the
+bytes not yet received, and an `If-Range` header carrying the strong `ETag` (or failing that the
+`Last-Modified` date) of the first response, so that the server only sends the remainder if the
+resource has not changed in the mean time (RFC 7233).
+
+A `206 Partial Content` answer is only accepted if its `Content-Range` starts exactly where the
+file left off. If the server answers `200 OK` instead (because it does not support ranges, or
+because the resource has changed), the file is truncated and the download starts over from the
+beginning.
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::download::Download;
+use url::Url;
+
+fn main() {
+    let download = Download::new(Url::parse("http://example.com/big.tar.gz").unwrap());
+    let length = download.to_file(&Path::new("big.tar.gz")).unwrap();
+    println!("downloaded {} bytes", length);
+}
+```
+
+*/
+
+use std::error::FromError;
+use std::io::{File, IoResult, IoError, OtherIoError, EndOfFile, SeekSet};
+use url::Url;
+
+use client::request::RequestWriter;
+use headers::content_range::ByteRange;
+use headers::if_range::{IfRange, EntityTagRange, DateRange};
+use headers::range::{Bytes, AllFrom};
+use headers::request;
+use headers::response;
+use method::Method::Get;
+use status::Status;
+
+/// A resumable download of a single URL.
+pub struct Download {
+    /// The URL to download.
+    pub url: Url,
+
+    /// Headers to send with each request, in addition to the Range and If-Range headers managed by
+    /// the download itself.
+    pub headers: request::HeaderCollection,
+
+    /// The number of requests to make before giving up. Each request that makes no progress at all
+    /// counts against this, whether it failed or its body merely ended early; a request that
+    /// receives some data before failing does not.
+    pub max_attempts: usize,
+}
+
+impl Download {
+    /// Create a download of `url`, which will make up to five attempts.
+    pub fn new(url: Url) -> Download {
+        Download {
+            url: url,
+            headers: request::HeaderCollection::new(),
+            max_attempts: 5,
+        }
+    }
+
+    /// Download to the file at `path`, creating or truncating it, and return the number of bytes
+    /// written.
+    ///
+    /// The error returned is that of the final failed attempt, or a description of the response
+    /// if the server answers with a status other than `200 OK` or `206 Partial Content`.
+    pub fn to_file(&self, path: &Path) -> IoResult<u64> {
+        let mut file = try!(File::create(path));
+        let mut written = 0u64;
+        let mut total = None;
+        let mut validator = None;
+        let mut failures = 0us;
+        // The furthest into the body any attempt has got.
+        let mut furthest = 0u64;
+
+        loop {
+            let mut request: RequestWriter = try!(RequestWriter::new(Get, self.url.clone()));
+            // The Host header was set from the URL; keep it unless the caller gave their own.
+            let host = request.headers.host.take();
+            request.headers = self.headers.clone();
+            if request.headers.host.is_none() {
+                request.headers.host = host;
+            }
+            if written > 0 {
+                request.headers.range = Some(Bytes(vec!(AllFrom(written))));
+                request.headers.if_range = validator.clone();
+            }
+
+            let result = match request.read_response() {
+                Ok(mut response) => {
+                    let resumed = written > 0 && response.status == Status::PartialContent;
+                    if resumed {
+                        match partial_length(&response.headers, written) {
+                            Some(length) => total = length,
+                            None => {
+                                let detail = response.headers.content_range.as_ref()
+                                                     .map(|range| format!("{:?}", range));
+                                return Err(download_error("mismatched Content-Range", detail));
+                            },
+                        }
+                    } else {
+                        if response.status != Status::Ok {
+                            return Err(download_error("unexpected response status",
+                                                      Some(format!("{:?}", response.status))));
+                        }
+                        // A full response: start (or start over) from the beginning.
+                        if written > 0 {
+                            try!(file.truncate(0));
+                            try!(file.seek(0, SeekSet));
+                            written = 0;
+                        }
+                        total = response.headers.content_length.map(|length| length as u64);
+                        validator = resume_validator(&response.headers);
+                    }
+                    copy_body(&mut response, &mut file, &mut written)
+                },
+                Err((_, err)) => Err(FromError::from_error(err)),
+            };
+
+            let err = match result {
+                Ok(()) => match total {
+                    Some(total) if written < total => {
+                        download_error("response ended early",
+                                       Some(format!("{} of {} bytes received", written, total)))
+                    },
+                    _ => return Ok(written),
+                },
+                Err(err) => err,
+            };
+            // An attempt which gets no further than any before it (having perhaps started over)
+            // makes no progress, however it ended.
+            if written <= furthest {
+                failures += 1;
+            } else {
+                furthest = written;
+            }
+            if failures >= self.max_attempts {
+                return Err(err);
+            }
+
+            // Without a validator it would not be safe to ask for a range: the remainder might
+            // belong to a different version of the resource.
+            if validator.is_none() {
+                try!(file.truncate(0));
+                try!(file.seek(0, SeekSet));
+                written = 0;
+            }
+        }
+    }
+}
+
+fn download_error(desc: &'static str, detail: Option<String>) -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: desc,
+        detail: detail,
+    }
+}
+
+/// Copy a response body to the file until the end of the stream.
+fn copy_body<R: Reader>(response: &mut R, file: &mut File, written: &mut u64) -> IoResult<()> {
+    let mut buf = [0u8; 8192];
+    loop {
+        match response.read(&mut buf) {
+            Ok(read) => {
+                try!(file.write(&buf[..read]));
+                *written += read as u64;
+            },
+            Err(ref err) if err.kind == EndOfFile => return Ok(()),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The If-Range value with which a download of the response with these headers can be resumed:
+/// its ETag if it is strong, or else its Last-Modified date.
+fn resume_validator(headers: &response::HeaderCollection) -> Option<IfRange> {
+    match (&headers.etag, &headers.last_modified) {
+        (&Some(ref etag), _) if !etag.weak => Some(EntityTagRange(etag.clone())),
+        (_, &Some(ref last_modified)) => Some(DateRange(last_modified.clone())),
+        _ => None,
+    }
+}
+
+/// Check that a 206 response continues a download at `offset`, returning the complete length of
+/// the representation if it is known.
+fn partial_length(headers: &response::HeaderCollection, offset: u64) -> Option<Option<u64>> {
+    match headers.content_range {
+        Some(ByteRange(first, _, length)) if first == offset => Some(length),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Acceptor, Listener, File, TempDir};
+    use std::io::net::ip::SocketAddr;
+    use std::io::net::tcp::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread::Thread;
+    use time;
+    use url::Url;
+    use headers::content_range::{ByteRange, UnsatisfiedRange};
+    use headers::etag::{strong_etag, weak_etag};
+    use headers::if_range::{EntityTagRange, DateRange};
+    use headers::response::HeaderCollection;
+    use super::{Download, resume_validator, partial_length};
+
+    #[test]
+    fn test_resume_validator() {
+        let mut headers = HeaderCollection::new();
+        assert_eq!(resume_validator(&headers), None);
+
+        let date = time::at_utc(time::Timespec::new(1000, 0));
+        headers.last_modified = Some(date);
+        assert_eq!(resume_validator(&headers), Some(DateRange(date)));
+
+        // A weak ETag cannot be used in If-Range.
+        headers.etag = Some(weak_etag(String::from_str("a")));
+        assert_eq!(resume_validator(&headers), Some(DateRange(date)));
+
+        headers.etag = Some(strong_etag(String::from_str("a")));
+        assert_eq!(resume_validator(&headers),
+                   Some(EntityTagRange(strong_etag(String::from_str("a")))));
+    }
+
+    #[test]
+    fn test_partial_length() {
+        let mut headers = HeaderCollection::new();
+        assert_eq!(partial_length(&headers, 100), None);
+
+        headers.content_range = Some(ByteRange(100, 199, Some(200)));
+        assert_eq!(partial_length(&headers, 100), Some(Some(200)));
+        assert_eq!(partial_length(&headers, 50), None);
+
+        headers.content_range = Some(ByteRange(100, 199, None));
+        assert_eq!(partial_length(&headers, 100), Some(None));
+
+        headers.content_range = Some(UnsatisfiedRange(200));
+        assert_eq!(partial_length(&headers, 100), None);
+    }
+
+    /// Serve the given responses to one connection each, sending back the head of each request.
+    fn serve(responses: Vec<&'static str>) -> (SocketAddr, Receiver<String>) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        let mut acceptor = listener.listen().unwrap();
+        let (sender, receiver) = channel();
+        Thread::spawn(move || {
+            for response in responses.into_iter() {
+                let mut stream = acceptor.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let read = stream.read(&mut buf).unwrap_or(0);
+                sender.send(String::from_utf8_lossy(&buf[..read]).into_owned()).unwrap();
+                stream.write_str(response).unwrap();
+            }
+        });
+        (addr, receiver)
+    }
+
+    #[test]
+    fn test_to_file() {
+        let dir = TempDir::new("download").unwrap();
+        let path = dir.path().join("file");
+
+        // The first response ends early; the second carries on from where it left off.
+        let (addr, requests) = serve(vec!(
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nETag: \"a\"\r\n\r\nhello",
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10\r\n\r\nworld"));
+        let download = Download::new(Url::parse(&format!("http://{}/", addr)[]).unwrap());
+        assert_eq!(download.to_file(&path).unwrap(), 10);
+        assert_eq!(File::open(&path).read_to_end().unwrap(), b"helloworld".to_vec());
+        let first = requests.recv().unwrap();
+        assert!(first.contains("Host: 127.0.0.1"));
+        assert!(!first.contains("Range:"));
+        let second = requests.recv().unwrap();
+        assert!(second.contains("Range: bytes=5-\r\n"));
+        assert!(second.contains("If-Range: \"a\"\r\n"));
+
+        // A server which ends every response at the same place is given up on.
+        let short = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        let (addr, _requests) = serve(vec!(short, short, short));
+        let mut download = Download::new(Url::parse(&format!("http://{}/", addr)[]).unwrap());
+        download.max_attempts = 2;
+        assert!(download.to_file(&path).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+/*!
+
+The errors of making a request with the client.
+
+`RequestWriter::read_response` fails with an `Error` saying which stage of the request went wrong:
+looking up the host, connecting, setting up SSL, running out of time, or reading the response.
+Errors of input and output which belong to no particular stage are kept as `Io`, and each error
+from below is kept as the `cause` of the error wrapping it.
+
+An `Error` converts to an `IoError` (so `try!` works on it in functions returning an `IoResult`),
+and an `IoError` to an `Error`.
+
+*/
+
+use std::error::{self, FromError};
+use std::fmt;
+use std::io::{IoError, OtherIoError, EndOfFile};
+
+use client::timeout::Timeout;
+use headers::RawHeader;
+
+/// Something going wrong in making a request.
+#[derive(Show)]
+pub enum Error {
+    /// The host of the URL could not be looked up, or has no addresses.
+    Dns(IoError),
+    /// No connection could be made to any address of the host.
+    Connect(IoError),
+    /// SSL could not be set up over the connection, or this build has no SSL support.
+    Tls(IoError),
+    /// One of the time limits of the request was reached.
+    Timeout(Timeout),
+    /// The status line of the response could not be parsed.
+    MalformedStatusLine,
+    /// A header line of the response was not valid syntax, or the value of a header which
+    /// determines how the body is read (Content-Length or Transfer-Encoding) was malformed, or
+    /// Content-Length was given twice with different values. Other malformed headers are left out
+    /// of the response instead.
+    MalformedHeader(RawHeader),
+    /// The connection was closed before the end of the head of the response.
+    UnexpectedEof,
+    /// Redirects were followed as many times as allowed, and yet another came; the number of
+    /// redirects followed.
+    TooManyRedirects(usize),
+    /// The body of the response could not be decoded according to its encoding.
+    Body(IoError),
+    /// Any other error of input or output, such as the connection being reset.
+    Io(IoError),
+}
+
+impl Error {
+    /// The error of reading from or writing to the connection, recognising the timeouts of the
+    /// request and the connection being closed.
+    pub fn from_io(err: IoError) -> Error {
+        match Timeout::of(&err) {
+            Some(timeout) => Error::Timeout(timeout),
+            None if err.kind == EndOfFile => Error::UnexpectedEof,
+            None => Error::Io(err),
+        }
+    }
+
+    /// The `IoError` underlying this error, if there is one.
+    pub fn io_error(&self) -> Option<&IoError> {
+        match *self {
+            Error::Dns(ref err) | Error::Connect(ref err) | Error::Tls(ref err) |
+            Error::Body(ref err) | Error::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Dns(_) => "could not look up host",
+            Error::Connect(_) => "could not connect to host",
+            Error::Tls(_) => "could not set up SSL",
+            Error::Timeout(timeout) => timeout.error().desc,
+            Error::MalformedStatusLine => "malformed status line in response",
+            Error::MalformedHeader(_) => "malformed header in response",
+            Error::UnexpectedEof => "connection closed before end of response",
+            Error::TooManyRedirects(_) => "too many redirects",
+            Error::Body(_) => "could not decode response body",
+            Error::Io(ref err) => err.desc,
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match *self {
+            Error::MalformedHeader(ref raw) => {
+                Some(format!("{}:{}", raw.name, String::from_utf8_lossy(&raw.value[])))
+            },
+            Error::TooManyRedirects(count) => Some(format!("{} redirects followed", count)),
+            Error::Io(ref err) => err.detail.clone(),
+            _ => self.io_error().map(|err| format!("{}", err)),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            // An `Io` error is only a wrapper; its cause is its own.
+            Error::Io(_) => None,
+            _ => self.io_error().map(|err| err as &error::Error),
+        }
+    }
+}
+
+impl fmt::String for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str(error::Error::description(self)));
+        match error::Error::detail(self) {
+            Some(detail) => write!(f, " ({})", detail),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromError<IoError> for Error {
+    fn from_error(err: IoError) -> Error {
+        Error::from_io(err)
+    }
+}
+
+impl FromError<Error> for IoError {
+    fn from_error(err: Error) -> IoError {
+        match err {
+            Error::Dns(err) | Error::Connect(err) | Error::Tls(err) | Error::Body(err) |
+            Error::Io(err) => err,
+            Error::Timeout(timeout) => timeout.error(),
+            Error::UnexpectedEof => IoError {
+                kind: EndOfFile,
+                desc: "connection closed before end of response",
+                detail: None,
+            },
+            err => IoError {
+                kind: OtherIoError,
+                desc: "Server returned malformed HTTP response",
+                detail: error::Error::detail(&err),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::{Error as StdError, FromError};
+    use std::io::{IoError, ConnectionRefused, EndOfFile, standard_error};
+    use client::timeout::Timeout;
+    use super::Error;
+
+    #[test]
+    fn test_conversions() {
+        let refused = IoError { kind: ConnectionRefused, desc: "refused", detail: None };
+        let err = Error::Connect(refused.clone());
+        assert_eq!(err.cause().unwrap().description(), "refused");
+        let io: IoError = FromError::from_error(err);
+        assert_eq!(io, refused);
+
+        match Error::from_io(Timeout::Idle.error()) {
+            Error::Timeout(Timeout::Idle) => (),
+            err => panic!("{:?}", err),
+        }
+        match Error::from_io(standard_error(EndOfFile)) {
+            Error::UnexpectedEof => (),
+            err => panic!("{:?}", err),
+        }
+        let io: IoError = FromError::from_error(Error::from_io(Timeout::Total.error()));
+        assert_eq!(Timeout::of(&io), Some(Timeout::Total));
+    }
+}
@@ -19,6 +19,15 @@ pub use self::request::RequestWriter;
 pub use self::response::ResponseReader;
 pub use self::sslclients::NetworkStream;
 
+pub mod auth;
+pub mod cache;
+pub mod cookie_jar;
+pub mod download;
+pub mod error;
+pub mod oauth2;
 pub mod request;
+pub mod resolver;
+pub mod retry;
 pub mod response;
+pub mod timeout;
 mod sslclients;
@@ -0,0 +1,226 @@
+/*!
+
+The OAuth 2.0 client credentials grant (RFC 6749, section 4.4), for services authenticating to
+each other.
+
+A `ClientCredentials` provider fetches an access token from the token endpoint of the
+authorization server, authenticating with the client ID and secret, and sends it as a bearer
+token (RFC 6750) with each request. The token is kept until it expires (or shortly before, so
+that it does not expire in flight), and then a new one is fetched. Should a server refuse the
+token anyway, with `WWW-Authenticate: Bearer error="invalid_token"` (because it has been revoked,
+say), a new token is fetched and the request sent once more.
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::RequestWriter;
+use http::client::auth::AuthProvider;
+use http::client::oauth2::ClientCredentials;
+use http::method::Get;
+use url::Url;
+
+fn main() {
+    let mut auth = ClientCredentials::new(Url::parse("https://auth.example.com/token").unwrap(),
+                                          "my-service", "s3cr3t");
+    auth.scope = Some(String::from_str("inventory:read"));
+    let url = Url::parse("https://inventory.example.com/items").unwrap();
+    let request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+    let response = auth.send(request).unwrap();
+    println!("{:?}", response.status);
+}
+```
+
+*/
+
+use std::ascii::AsciiExt;
+use std::io::{IoResult, IoError, OtherIoError};
+use rustc_serialize::json::Json;
+use time;
+use url::Url;
+
+use client::auth::AuthProvider;
+use client::request::RequestWriter;
+use form::{Form, encode_component};
+use headers::authorization::{Credentials, Challenges};
+use method::Method;
+use method::Method::Post;
+use status::Status;
+
+/// An access token, with when it expires in seconds since the epoch, if it does.
+#[derive(Clone, PartialEq, Show)]
+struct Token {
+    access_token: String,
+    expires: Option<i64>,
+}
+
+/// Bearer tokens obtained with the client credentials grant.
+#[derive(Clone)]
+pub struct ClientCredentials {
+    /// The URL of the token endpoint of the authorization server.
+    pub token_url: Url,
+    /// The client identifier.
+    pub client_id: String,
+    /// The client secret.
+    pub client_secret: String,
+    /// The scope of the access requested, as a space-separated list; by default none is given,
+    /// leaving it to the authorization server.
+    pub scope: Option<String>,
+    /// How many seconds before it expires a token is replaced; thirty by default.
+    pub expiry_margin: i64,
+    token: Option<Token>,
+}
+
+impl ClientCredentials {
+    /// A provider for the client `client_id`, fetching tokens from `token_url`.
+    pub fn new(token_url: Url, client_id: &str, client_secret: &str) -> ClientCredentials {
+        ClientCredentials {
+            token_url: token_url,
+            client_id: String::from_str(client_id),
+            client_secret: String::from_str(client_secret),
+            scope: None,
+            expiry_margin: 30,
+            token: None,
+        }
+    }
+
+    /// The current access token, fetching a new one if there is none or it is about to expire.
+    pub fn access_token(&mut self) -> IoResult<String> {
+        let now = time::get_time().sec;
+        let valid = match self.token {
+            Some(Token { expires: Some(expires), .. }) => now + self.expiry_margin < expires,
+            Some(Token { expires: None, .. }) => true,
+            None => false,
+        };
+        if !valid {
+            self.token = Some(try!(self.fetch_token(now)));
+        }
+        Ok(self.token.as_ref().unwrap().access_token.clone())
+    }
+
+    /// Forget the current access token, so that a new one is fetched for the next request.
+    pub fn invalidate(&mut self) {
+        self.token = None;
+    }
+
+    /// Request a new token from the token endpoint (RFC 6749, section 4.4.2).
+    fn fetch_token(&self, now: i64) -> IoResult<Token> {
+        let mut form = Form::new();
+        form.append(String::from_str("grant_type"), String::from_str("client_credentials"));
+        match self.scope {
+            Some(ref scope) => form.append(String::from_str("scope"), scope.clone()),
+            None => (),
+        }
+
+        let mut request: RequestWriter = try!(RequestWriter::new(Post, self.token_url.clone()));
+        // The client ID and secret are form-urlencoded before being used as the user name and
+        // password of Basic authentication (RFC 6749, section 2.3.1).
+        request.headers.authorization = Some(Credentials::basic(
+            &encode_component(&self.client_id[])[], &encode_component(&self.client_secret[])[]));
+        try!(request.write_form(&form));
+        let mut response = try!(request.read_response().map_err(|(_, err)| err));
+        let body = try!(response.read_to_end());
+        let body = String::from_utf8_lossy(&body[]).into_owned();
+        parse_token_response(&response.status, &body[], now)
+    }
+}
+
+fn token_error(desc: &'static str, detail: Option<String>) -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: desc,
+        detail: detail,
+    }
+}
+
+/// Interpret the response of the token endpoint (RFC 6749, sections 5.1 and 5.2).
+fn parse_token_response(status: &Status, body: &str, now: i64) -> IoResult<Token> {
+    let json = match Json::from_str(body) {
+        Ok(json) => json,
+        Err(_) => return Err(token_error("malformed token response",
+                                         Some(format!("{:?}: {}", status, body)))),
+    };
+    if *status != Status::Ok {
+        let error = json.find("error").and_then(|error| error.as_string()).unwrap_or("");
+        let description = json.find("error_description").and_then(|d| d.as_string())
+                              .unwrap_or("");
+        return Err(token_error("token request refused",
+                               Some(format!("{:?}: {} {}", status, error, description))));
+    }
+    let token_type = json.find("token_type").and_then(|t| t.as_string()).unwrap_or("");
+    if !token_type.eq_ignore_ascii_case("bearer") {
+        return Err(token_error("unsupported token type", Some(String::from_str(token_type))));
+    }
+    let access_token = match json.find("access_token").and_then(|t| t.as_string()) {
+        Some(access_token) => String::from_str(access_token),
+        None => return Err(token_error("malformed token response", Some(String::from_str(body)))),
+    };
+    Ok(Token {
+        access_token: access_token,
+        expires: json.find("expires_in").and_then(|e| e.as_i64()).map(|e| now + e),
+    })
+}
+
+impl AuthProvider for ClientCredentials {
+    fn credentials(&mut self, _: &Method, _: &Url) -> IoResult<Option<Credentials>> {
+        let token = try!(self.access_token());
+        Ok(Some(Credentials::bearer(&token[])))
+    }
+
+    /// A token refused as invalid (RFC 6750, section 3.1) is replaced and the request retried.
+    fn challenged(&mut self, _: &Url, challenges: &Challenges, _: bool) -> bool {
+        let invalid = challenges.0.iter().any(|challenge| {
+            challenge.is_scheme("Bearer") && challenge.param("error") == Some("invalid_token")
+        });
+        if invalid {
+            self.invalidate();
+        }
+        invalid
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use headers::authorization::{Challenge, Challenges, AuthParams};
+    use status::Status;
+    use url::Url;
+    use client::auth::AuthProvider;
+    use super::{ClientCredentials, Token, parse_token_response};
+
+    #[test]
+    fn test_parse_token_response() {
+        let parse = |status: Status, body: &str| parse_token_response(&status, body, 1000);
+        assert_eq!(parse(Status::Ok, "{\"access_token\":\"2YotnFZFEjr1zCsicMWpAA\",\
+                                      \"token_type\":\"Bearer\",\"expires_in\":3600}").unwrap(),
+                   Token {
+                       access_token: String::from_str("2YotnFZFEjr1zCsicMWpAA"),
+                       expires: Some(4600),
+                   });
+        assert_eq!(parse(Status::Ok, "{\"access_token\":\"abc\",\"token_type\":\"bearer\"}")
+                       .unwrap().expires,
+                   None);
+        assert!(parse(Status::Ok, "{\"access_token\":\"abc\",\"token_type\":\"mac\"}").is_err());
+        assert!(parse(Status::Ok, "{\"token_type\":\"bearer\"}").is_err());
+        assert!(parse(Status::BadRequest, "{\"error\":\"invalid_client\"}").is_err());
+        assert!(parse(Status::Ok, "<html>").is_err());
+    }
+
+    #[test]
+    fn test_challenged() {
+        let mut auth = ClientCredentials::new(Url::parse("https://example.com/token").unwrap(),
+                                              "client", "secret");
+        auth.token = Some(Token { access_token: String::from_str("abc"), expires: None });
+        assert_eq!(auth.access_token().unwrap(), "abc");
+
+        let challenge = |error: &str| Challenges(vec!(Challenge {
+            scheme: String::from_str("Bearer"),
+            data: AuthParams(vec!((String::from_str("realm"), String::from_str("example")),
+                                  (String::from_str("error"), String::from_str(error)))),
+        }));
+        let url = Url::parse("https://example.com/resource").unwrap();
+        assert!(!auth.challenged(&url, &challenge("insufficient_scope"), true));
+        assert!(auth.token.is_some());
+        assert!(auth.challenged(&url, &challenge("invalid_token"), true));
+        assert!(auth.token.is_none());
+    }
+}
@@ -59,15 +59,19 @@ let response = match request.read_response() {
 
 use url::Url;
 use method::Method;
-use std::io::{IoError, IoResult};
-use std::io::net::get_host_addresses;
-use std::io::net::ip::{SocketAddr, Ipv4Addr};
+use std::io::{InvalidInput, IoError, IoResult, OtherIoError};
+use std::io::net::ip::SocketAddr;
 use buffer::BufferedStream;
 use headers::request::HeaderCollection;
-use headers::host::Host;
-use connecter::Connecter;
+use headers::host::{Host, HostName, Domain, Ip};
+use connecter::{Connecter, sort_addresses};
+use form::Form;
+use multipart::MultipartBody;
 
+use client::error::Error;
+use client::resolver::{Resolver, SystemResolver};
 use client::response::ResponseReader;
+use client::timeout::{self, Timeout, Timeouts, Connect, FirstByte, Idle, Total};
 
 /*impl ResponseReader {
     {
@@ -92,9 +96,20 @@ pub struct RequestWriter<S = super::NetworkStream> {
     stream: Option<BufferedStream<S>>,
     headers_written: bool,
 
-    /// The originating IP address of the request.
+    /// The address to connect to, if it is to be fixed; otherwise the host of the URL is looked
+    /// up with `resolver` when connecting.
     pub remote_addr: Option<SocketAddr>,
 
+    /// The resolver used to look up the host of the URL; by default, that of the operating
+    /// system.
+    pub resolver: Box<Resolver + Send>,
+
+    /// The time limits of the request; by default there are none.
+    pub timeouts: Timeouts,
+
+    /// When the request must be finished by, if it has a total timeout and has been started.
+    deadline: Option<u64>,
+
     /// The host name and IP address that the request was sent to; this must always be specified for
     /// HTTP/1.1 requests (or the request will be rejected), but for HTTP/1.0 requests the Host
     /// header was not defined, and so this field will probably be None in such cases.
@@ -109,6 +124,10 @@ pub struct RequestWriter<S = super::NetworkStream> {
     /// The URL being requested.
     pub url: Url,
 
+    /// Whether to record the header lines of the response exactly as received, in
+    /// `ResponseReader::raw_headers`.
+    pub record_raw_headers: bool,
+
     /// Should we use SSL?
     use_ssl: bool,
 }
@@ -126,47 +145,30 @@ impl<S: Reader + Writer = super::NetworkStream> RequestWriter<S> {
     }
 
     pub fn new_request(method: Method, url: Url, use_ssl: bool, auto_detect_ssl: bool) -> IoResult<RequestWriter<S>> {
+        let name = match url.serialize_host().and_then(|host| HostName::parse(&host[])) {
+            Some(name) => name,
+            None => return Err(IoError {
+                kind: InvalidInput,
+                desc: "URL has no valid host",
+                detail: Some(url.serialize()),
+            }),
+        };
         let host = Host {
-            name: url.domain().unwrap().to_string(),
+            name: name,
             port: url.port(),
         };
 
-        let remote_addr = try!(url_to_socket_addr(&url, &host));
-        info!("using ip address {} for {}", remote_addr, host.name);
-
-        fn url_to_socket_addr(url: &Url, host: &Host) -> IoResult<SocketAddr> {
-            // Just grab the first IPv4 address
-            let addrs = try!(get_host_addresses(&host.name[]));
-            let addr = addrs.into_iter().find(|&a| {
-                match a {
-                    Ipv4Addr(..) => true,
-                    _ => false
-                }
-            });
-
-            // TODO: Error handling
-            let addr = addr.unwrap();
-
-            // Default to 80, using the port specified or 443 if the protocol is HTTPS.
-            let port = match host.port {
-                Some(p) => p,
-                // FIXME: case insensitivity?
-                None => if &url.scheme[] == "https" { 443 } else { 80 },
-            };
-
-            Ok(SocketAddr {
-                ip: addr,
-                port: port
-            })
-        }
-
         let mut request = RequestWriter {
             stream: None,
             headers_written: false,
-            remote_addr: Some(remote_addr),
+            remote_addr: None,
+            resolver: Box::new(SystemResolver),
+            timeouts: Timeouts::new(),
+            deadline: None,
             headers: HeaderCollection::new(),
             method: method,
             url: url,
+            record_raw_headers: false,
             use_ssl: use_ssl,
         };
 
@@ -183,7 +185,7 @@ impl<S: Reader + Writer = super::NetworkStream> RequestWriter<S> {
 impl<S: Connecter + Reader + Writer = super::NetworkStream> RequestWriter<S> {
 
     /// Connect to the remote host if not already connected.
-    pub fn try_connect(&mut self) -> IoResult<()> {
+    pub fn try_connect(&mut self) -> Result<(), Error> {
         if self.stream.is_none() {
             self.connect()
         } else {
@@ -192,23 +194,75 @@ impl<S: Connecter + Reader + Writer = super::NetworkStream> RequestWriter<S> {
     }
 
     /// Connect to the remote host; fails if already connected.
-    /// Returns ``true`` upon success and ``false`` upon failure (also use conditions).
-    pub fn connect(&mut self) -> IoResult<()> {
+    pub fn connect(&mut self) -> Result<(), Error> {
         if !self.stream.is_none() {
             panic!("I don't think you meant to call connect() twice, you know.");
         }
 
-        self.stream = match self.remote_addr {
-            Some(addr) => {
-                let stream = try!(Connecter::connect(
-                    addr, &self.headers.host.as_ref().unwrap().name[], self.use_ssl));
-                Some(BufferedStream::new(stream))
-            },
-            None => panic!("connect() called before remote_addr was set"),
+        if self.deadline.is_none() {
+            self.deadline = self.timeouts.total.map(|total| timeout::now_ms() + total);
+        }
+        let host = self.headers.host.clone().unwrap();
+        let name = format!("{:?}", host.name);
+        let addrs = match self.remote_addr {
+            Some(addr) => vec!(addr),
+            None => try!(self.resolve(&host)),
         };
+        let limit = timeout::limit(self.timeouts.connect, Connect, self.deadline);
+        let mut stream: S = match Connecter::connect_any(&addrs[], &name[], self.use_ssl,
+                                                         limit.map(|(ms, _)| ms)) {
+            Ok(stream) => stream,
+            Err(Error::Connect(err)) => return Err(connect_error(err, limit, Error::Connect)),
+            Err(Error::Tls(err)) => return Err(connect_error(err, limit, Error::Tls)),
+            Err(err) => return Err(err),
+        };
+        // Writing the request counts towards the total time only.
+        stream.set_timeout(timeout::limit(None, Total, self.deadline).map(|(ms, _)| ms));
+        self.stream = Some(BufferedStream::new(stream));
         Ok(())
     }
 
+    /// The time by which the request must be finished, in milliseconds as from
+    /// `timeout::now_ms`, if it has a total timeout and has been started.
+    pub fn deadline(&self) -> Option<u64> {
+        self.deadline
+    }
+
+    /// Set the deadline of the request before it is started, in place of the one worked out from
+    /// `timeouts.total`; so a request sent in place of another can have the time the other had.
+    pub fn set_deadline(&mut self, deadline: Option<u64>) {
+        self.deadline = deadline;
+    }
+
+    /// Whether the Request-Line and headers have been written, as they are by the first write of
+    /// the body.
+    pub fn headers_written(&self) -> bool {
+        self.headers_written
+    }
+
+    /// The socket addresses of the host, in the order in which they should be tried.
+    fn resolve(&self, host: &Host) -> Result<Vec<SocketAddr>, Error> {
+        let ips = match host.name {
+            Ip(ip) => vec!(ip),
+            Domain(ref name) => try!(self.resolver.resolve(&name[]).map_err(Error::Dns)),
+        };
+        if ips.is_empty() {
+            return Err(Error::Dns(IoError {
+                kind: OtherIoError,
+                desc: "host name has no addresses",
+                detail: Some(format!("{:?}", host.name)),
+            }));
+        }
+
+        // Default to 80, using the port specified or 443 if the protocol is HTTPS.
+        let port = match host.port {
+            Some(p) => p,
+            // FIXME: case insensitivity?
+            None => if &self.url.scheme[] == "https" { 443 } else { 80 },
+        };
+        Ok(sort_addresses(ips).into_iter().map(|ip| SocketAddr { ip: ip, port: port }).collect())
+    }
+
     /// Write the Request-Line and headers of the response, if we have not already done so.
     pub fn try_write_headers(&mut self) -> IoResult<()> {
         if !self.headers_written {
@@ -245,24 +299,58 @@ impl<S: Connecter + Reader + Writer = super::NetworkStream> RequestWriter<S> {
         Ok(())
     }
 
+    /// Send a form as the body of the request, setting the Content-Type and Content-Length
+    /// headers. The headers must not have been written yet.
+    pub fn write_form(&mut self, form: &Form) -> IoResult<()> {
+        let body = form.encode();
+        self.headers.content_type = Some(Form::media_type());
+        self.headers.content_length = Some(body.len());
+        self.write(body.as_bytes())
+    }
+
+    /// Send a `multipart/form-data` body, setting the Content-Type and Content-Length headers.
+    /// The headers must not have been written yet.
+    pub fn write_multipart(&mut self, body: &MultipartBody) -> IoResult<()> {
+        self.headers.content_type = Some(body.media_type());
+        self.headers.content_length = Some(body.len() as usize);
+        body.write_to(self)
+    }
+
     /**
      * Send the request and construct a `ResponseReader` out of it.
      *
-     * If the request sending fails in any way, a condition will be raised; if handled, the original
-     * request will be returned as an `Err`.
+     * If anything goes wrong, from looking up the host to reading the head of the response, the
+     * original request is returned as an `Err` along with the `Error`.
      */
-    pub fn read_response(mut self) -> Result<ResponseReader<S>, (RequestWriter<S>, IoError)> {
-        match self.try_write_headers() {
+    pub fn read_response(mut self) -> Result<ResponseReader<S>, (RequestWriter<S>, Error)> {
+        match self.try_connect() {
             Ok(()) => (),
             Err(err) => return Err((self, err)),
         };
-        match self.flush() {
+        match self.try_write_headers().and_then(|()| self.flush()) {
             Ok(()) => (),
-            Err(err) => return Err((self, err)),
+            Err(err) => return Err((self, Error::from_io(err))),
         };
-        match self.stream.take() {
-            Some(stream) => ResponseReader::construct(stream, self),
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
             None => unreachable!(), // TODO: is it genuinely unreachable?
+        };
+
+        // Wait for the response to begin, then allow the idle timeout for the rest of its head.
+        let limit = timeout::limit(self.timeouts.first_byte, FirstByte, self.deadline);
+        stream.wrapped.set_timeout(limit.map(|(ms, _)| ms));
+        match stream.read_byte() {
+            Ok(b) => stream.poke_byte(b),
+            Err(err) => return Err((self, Error::from_io(timeout::reached(err, limit)))),
+        }
+        let limit = timeout::limit(self.timeouts.idle, Idle, self.deadline);
+        stream.wrapped.set_timeout(limit.map(|(ms, _)| ms));
+        match ResponseReader::construct(stream, self) {
+            Ok(response) => Ok(response),
+            Err((request, Error::Io(err))) => {
+                Err((request, Error::from_io(timeout::reached(err, limit))))
+            },
+            Err((request, err)) => Err((request, err)),
         }
     }
 }
@@ -275,11 +363,24 @@ impl<S: Reader + Writer + Connecter = super::NetworkStream> Writer for RequestWr
         }
         // TODO: decide whether using get_mut_ref() is sound
         // (it will cause failure if None)
-        self.stream.as_mut().unwrap().write(buf)
+        let limit = timeout::limit(None, Total, self.deadline);
+        self.stream.as_mut().unwrap().write(buf).map_err(|err| timeout::reached(err, limit))
     }
 
     fn flush(&mut self) -> IoResult<()> {
         // TODO: ditto
-        self.stream.as_mut().unwrap().flush()
+        let limit = timeout::limit(None, Total, self.deadline);
+        self.stream.as_mut().unwrap().flush().map_err(|err| timeout::reached(err, limit))
+    }
+}
+
+/// The error of failing to connect (or to set up SSL) within `limit`: a timeout if the limit was
+/// reached, and otherwise the error wrapped by `wrap`.
+fn connect_error(err: IoError, limit: Option<(u64, Timeout)>, wrap: fn(IoError) -> Error)
+                 -> Error {
+    let err = timeout::reached(err, limit);
+    match Timeout::of(&err) {
+        Some(timeout) => Error::Timeout(timeout),
+        None => wrap(err),
     }
 }
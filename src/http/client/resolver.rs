@@ -0,0 +1,244 @@
+/*!
+
+Resolving host names to IP addresses for the client.
+
+A `RequestWriter` looks up the host of its URL with its `resolver` when it connects, then connects
+to whichever of the addresses found answers first (see `connecter::connect_tcp`). By default this
+is the `SystemResolver`, asking the operating system; any other `Resolver` can be put in its place,
+such as a `StaticResolver` pinning hosts to fixed addresses, for tests or to send requests for a
+production host to a local stand-in:
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use std::io::net::ip::Ipv4Addr;
+use http::client::RequestWriter;
+use http::client::resolver::StaticResolver;
+use http::method::Get;
+use url::Url;
+
+fn main() {
+    let mut resolver = StaticResolver::new();
+    resolver.insert("api.example.com", vec!(Ipv4Addr(127, 0, 0, 1)));
+    let url = Url::parse("http://api.example.com:8001/status").unwrap();
+    let mut request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+    request.resolver = Box::new(resolver);
+    let response = request.read_response().ok().unwrap();
+    println!("{:?}", response.status);
+}
+```
+
+The system resolver is asked afresh for every request. To look each host up only once in a while,
+wrap the resolver in a `CachingResolver`, which remembers the addresses it is given for `ttl`
+seconds; to share the cache between requests, put it in an `Arc` and give each request a clone:
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use std::sync::Arc;
+use http::client::RequestWriter;
+use http::client::resolver::{CachingResolver, SystemResolver};
+use http::method::Get;
+use url::Url;
+
+fn main() {
+    let resolver = Arc::new(CachingResolver::new(SystemResolver));
+    for _ in range(0us, 2) {
+        let url = Url::parse("http://example.com/").unwrap();
+        let mut request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+        // The second request uses the addresses found for the first.
+        request.resolver = Box::new(resolver.clone());
+        let response = request.read_response().ok().unwrap();
+        println!("{:?}", response.status);
+    }
+}
+```
+
+*/
+
+use std::ascii::AsciiExt;
+use std::collections::HashMap;
+use std::io::{IoResult, IoError, OtherIoError};
+use std::io::net::get_host_addresses;
+use std::io::net::ip::IpAddr;
+use std::sync::{Arc, Mutex};
+use time;
+
+/// Something which can look up the IP addresses of a host name.
+pub trait Resolver {
+    /// The addresses of `host`, a registered name such as `example.com`, in the order in which
+    /// they should be preferred. An empty list is taken to mean that the host has no addresses.
+    fn resolve(&self, host: &str) -> IoResult<Vec<IpAddr>>;
+}
+
+impl<R: Resolver> Resolver for Arc<R> {
+    fn resolve(&self, host: &str) -> IoResult<Vec<IpAddr>> {
+        (**self).resolve(host)
+    }
+}
+
+/// The resolver of the operating system (`getaddrinfo`), with its hosts file and DNS settings.
+#[derive(Clone, Copy)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str) -> IoResult<Vec<IpAddr>> {
+        let mut addrs = try!(get_host_addresses(host));
+        // getaddrinfo gives each address once for each socket type.
+        addrs.dedup();
+        Ok(addrs)
+    }
+}
+
+/// A fixed table of host names and their addresses, like a hosts file. Names which are not in the
+/// table are looked up with the `SystemResolver` if `fallback` is set, and are otherwise an error.
+#[derive(Clone)]
+pub struct StaticResolver {
+    hosts: Vec<(String, Vec<IpAddr>)>,
+    /// Whether to ask the operating system about names which are not in the table; off by default.
+    pub fallback: bool,
+}
+
+impl StaticResolver {
+    /// An empty table, without fallback.
+    pub fn new() -> StaticResolver {
+        StaticResolver {
+            hosts: Vec::new(),
+            fallback: false,
+        }
+    }
+
+    /// Give `host` the addresses `addrs`, in place of any it had.
+    pub fn insert(&mut self, host: &str, addrs: Vec<IpAddr>) {
+        let host = host.to_ascii_lowercase();
+        self.hosts.retain(|&(ref name, _)| *name != host);
+        self.hosts.push((host, addrs));
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str) -> IoResult<Vec<IpAddr>> {
+        match self.hosts.iter().find(|&&(ref name, _)| name[].eq_ignore_ascii_case(host)) {
+            Some(&(_, ref addrs)) => Ok(addrs.clone()),
+            None if self.fallback => SystemResolver.resolve(host),
+            None => Err(IoError {
+                kind: OtherIoError,
+                desc: "unknown host",
+                detail: Some(String::from_str(host)),
+            }),
+        }
+    }
+}
+
+/// A resolver remembering the addresses found by another for a while, so that a host is not looked
+/// up again for every request. Failures are not remembered.
+///
+/// The system resolver does not say how long its answers are good for, so every name is kept for
+/// the same time, `ttl`.
+pub struct CachingResolver<R = SystemResolver> {
+    resolver: R,
+    /// The number of seconds for which the addresses of a name are kept; a minute by default.
+    pub ttl: i64,
+    entries: Mutex<HashMap<String, (Vec<IpAddr>, i64)>>,
+}
+
+impl<R: Resolver> CachingResolver<R> {
+    /// An empty cache in front of `resolver`.
+    pub fn new(resolver: R) -> CachingResolver<R> {
+        CachingResolver {
+            resolver: resolver,
+            ttl: 60,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forget all the addresses held, so that every name is looked up again.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Remove all expired entries. Expired entries are never used, but they take up memory until
+    /// this is called or their names are looked up again.
+    pub fn remove_expired(&self) {
+        let now = time::get_time().sec;
+        let mut entries = self.entries.lock().unwrap();
+        let expired: Vec<String> = entries.iter().filter(|&(_, &(_, expires))| expires <= now)
+                                          .map(|(host, _)| host.clone()).collect();
+        for host in expired.iter() {
+            entries.remove(host);
+        }
+    }
+
+    fn resolve_at(&self, host: &str, now: i64) -> IoResult<Vec<IpAddr>> {
+        let host = host.to_ascii_lowercase();
+        match self.entries.lock().unwrap().get(&host) {
+            Some(&(ref addrs, expires)) if expires > now => return Ok(addrs.clone()),
+            _ => (),
+        }
+        // The lock is not held while resolving, which may take a while; two requests for the
+        // same name at once may both look it up.
+        let addrs = try!(self.resolver.resolve(&host[]));
+        self.entries.lock().unwrap().insert(host, (addrs.clone(), now + self.ttl));
+        Ok(addrs)
+    }
+}
+
+impl<R: Resolver> Resolver for CachingResolver<R> {
+    fn resolve(&self, host: &str) -> IoResult<Vec<IpAddr>> {
+        self.resolve_at(host, time::get_time().sec)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::io::{IoResult, IoError, OtherIoError};
+    use std::io::net::ip::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use super::{Resolver, StaticResolver, CachingResolver};
+
+    #[test]
+    fn test_static_resolver() {
+        let mut resolver = StaticResolver::new();
+        resolver.insert("Example.com", vec!(Ipv4Addr(192, 0, 2, 1)));
+        resolver.insert("example.com", vec!(Ipv6Addr(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+                                            Ipv4Addr(192, 0, 2, 1)));
+        assert_eq!(resolver.resolve("EXAMPLE.com").unwrap(),
+                   vec!(Ipv6Addr(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), Ipv4Addr(192, 0, 2, 1)));
+        assert!(resolver.resolve("example.org").is_err());
+    }
+
+    /// Resolves every name to one address, counting the lookups; fails for `fail.example`.
+    struct Counting(Cell<u32>);
+
+    impl Resolver for Counting {
+        fn resolve(&self, host: &str) -> IoResult<Vec<IpAddr>> {
+            self.0.set(self.0.get() + 1);
+            if host == "fail.example" {
+                return Err(IoError { kind: OtherIoError, desc: "failed", detail: None });
+            }
+            Ok(vec!(Ipv4Addr(192, 0, 2, 1)))
+        }
+    }
+
+    #[test]
+    fn test_caching_resolver() {
+        let cache = CachingResolver::new(Counting(Cell::new(0)));
+        cache.resolve_at("example.com", 1000).unwrap();
+        assert_eq!(cache.resolve_at("Example.COM", 1059).unwrap(), vec!(Ipv4Addr(192, 0, 2, 1)));
+        assert_eq!(cache.resolver.0.get(), 1);
+        cache.resolve_at("example.com", 1060).unwrap();
+        assert_eq!(cache.resolver.0.get(), 2);
+        cache.resolve_at("example.org", 1060).unwrap();
+        assert_eq!(cache.resolver.0.get(), 3);
+
+        assert!(cache.resolve_at("fail.example", 1060).is_err());
+        assert!(cache.resolve_at("fail.example", 1060).is_err());
+        assert_eq!(cache.resolver.0.get(), 5);
+
+        cache.clear();
+        cache.resolve_at("example.com", 1060).unwrap();
+        assert_eq!(cache.resolver.0.get(), 6);
+    }
+}
@@ -1,5 +1,9 @@
+use std::ascii::AsciiExt;
 use std::io::{Stream, IoResult, OtherIoError, IoError};
+use client::error::Error;
 use client::request::RequestWriter;
+use client::timeout::{self, Idle};
+use connecter::Connecter;
 use rfc2616::{CR, LF, SP};
 use common::read_http_version;
 use headers;
@@ -24,27 +28,36 @@ pub struct ResponseReader<S> {
 
     /// The headers received in the response.
     pub headers: headers::response::HeaderCollection,
+
+    /// The header lines exactly as received, in order, if `record_raw_headers` was set on the
+    /// request; this includes any headers which were dropped from `headers` because their values
+    /// were malformed.
+    pub raw_headers: Option<Vec<headers::RawHeader>>,
 }
 
-fn bad_response_err() -> IoError {
-    // TODO: IoError isn't right
-    IoError {
-        kind: OtherIoError,
-        desc: "Server returned malformed HTTP response",
-        detail: None,
+/// The error of reading the status line: `read_http_version` reports a malformed version as an
+/// `OtherIoError`.
+fn status_line_err(err: IoError) -> Error {
+    if err.kind == OtherIoError {
+        Error::MalformedStatusLine
+    } else {
+        Error::from_io(err)
     }
 }
 
+/// Whether a header is needed to find the end of the body, such that the response cannot be read
+/// if its value is malformed.
+fn frames_body(name: &str) -> bool {
+    name.eq_ignore_ascii_case("content-length") || name.eq_ignore_ascii_case("transfer-encoding")
+}
+
 impl<S: Stream> ResponseReader<S> {
     pub fn construct(mut stream: BufferedStream<S>, request: RequestWriter<S>)
-            -> Result<ResponseReader<S>, (RequestWriter<S>, IoError)> {
-        // TODO: raise condition at the points where Err is returned
-        //let mut b = [0u8, ..4096];
-        //let len = stream.read(b);
-        //println!("{}", ::std::str::from_bytes(b[..len.unwrap()]));
+            -> Result<ResponseReader<S>, (RequestWriter<S>, Error)> {
+        stream.read_error = None;
         let http_version = match read_http_version(&mut stream, &mut |b| b == SP) {
             Ok(nums) => nums,
-            Err(_) => return Err((request, bad_response_err())),
+            Err(err) => return Err((request, status_line_err(err))),
         };
 
         // Read the status code
@@ -53,14 +66,15 @@ impl<S: Stream> ResponseReader<S> {
         loop {
             if digits == 4u8 {
                 // Status code must be three digits long
-                return Err((request, bad_response_err()));
+                return Err((request, Error::MalformedStatusLine));
             }
             match stream.read_byte() {
                 Ok(b) if b >= b'0' && b <= b'9' => {
                     status_code = status_code * 10 + b as u16 - '0' as u16;
                 },
                 Ok(b) if b == SP => break,
-                _ => return Err((request, bad_response_err())),
+                Ok(_) => return Err((request, Error::MalformedStatusLine)),
+                Err(err) => return Err((request, Error::from_io(err))),
             }
             digits += 1;
         }
@@ -70,17 +84,17 @@ impl<S: Stream> ResponseReader<S> {
         loop {
             match stream.read_byte() {
                 Ok(b) if b == CR => {
-                    if stream.read_byte() == Ok(LF) {
-                        break;
-                    } else {
+                    match stream.read_byte() {
+                        Ok(b) if b == LF => break,
                         // Response-Line has CR without LF. Not yet resilient; TODO.
-                        return Err((request, bad_response_err()));
+                        Ok(_) => return Err((request, Error::MalformedStatusLine)),
+                        Err(err) => return Err((request, Error::from_io(err))),
                     }
                 }
                 Ok(b) => {
                     reason.push(b as char);
                 }
-                Err(_) => return Err((request, bad_response_err())),
+                Err(err) => return Err((request, Error::from_io(err))),
             }
         }
 
@@ -91,24 +105,51 @@ impl<S: Stream> ResponseReader<S> {
         // not be shared between them as they will have ultra-smart parsers (probably using Ragel)
         // to provide fast loading of standard headers, and the set of defined headers is distinct
         // between a request and response.
+        let mut raw_headers = if request.record_raw_headers { Some(Vec::new()) } else { None };
+        // The header lines are recorded even if the request does not want them, so that a
+        // malformed header can be reported.
+        let mut scratch = Vec::new();
         let headers = {
             let mut buffer = RequestBuffer::new(&mut stream);
             let mut headers = headers::response::HeaderCollection::new();
             loop {
-                let xxx = buffer.read_header::<headers::response::Header>();
-                match xxx {
-                //match buffer.read_header::<headers::response::Header>() {
+                let (header, malformed) = {
+                    let raw = match raw_headers {
+                        Some(ref mut raw) => raw,
+                        None => {
+                            scratch.clear();
+                            &mut scratch
+                        },
+                    };
+                    let header = buffer.read_header_recording::<headers::response::Header>(
+                        Some(&mut *raw));
+                    let malformed = match header {
+                        Err(MalformedHeaderSyntax) | Err(MalformedHeaderValue) => {
+                            raw.last().map(|line| line.clone())
+                        },
+                        _ => None,
+                    };
+                    (header, malformed)
+                };
+                match header {
+                    // The header parser gives up on any error reading; report the error itself.
                     Err(EndOfFile) => {
-                        //panic!("server disconnected, no more response to receive :-(");
-                        return Err((request, bad_response_err()));
+                        let err = match buffer.stream.read_error.take() {
+                            Some(err) => Error::from_io(err),
+                            None => Error::UnexpectedEof,
+                        };
+                        return Err((request, err));
                     },
                     Err(EndOfHeaders) => break,
                     Err(MalformedHeaderSyntax) => {
-                        return Err((request, bad_response_err()));
+                        return Err((request, Error::MalformedHeader(malformed.unwrap())));
                     },
-                    Err(MalformedHeaderValue) => {
-                        println!("Bad header encountered. TODO: handle this better.");
+                    Err(MalformedHeaderValue) => match malformed {
+                        Some(ref line) if frames_body(&line.name[]) => {
+                            return Err((request, Error::MalformedHeader(line.clone())));
+                        },
                         // Now just ignore the header
+                        _ => (),
                     },
                     Ok(header) => {
                         headers.insert(header);
@@ -117,6 +158,19 @@ impl<S: Stream> ResponseReader<S> {
             }
             headers
         };
+        // Repeated Content-Length lines which agree are kept as one; any left over disagree, and
+        // leave the end of the body in doubt (RFC 7230, section 3.3.2).
+        match headers.extensions.get("content-length") {
+            Some(value) => {
+                let line = headers::RawHeader {
+                    name: String::from_str("Content-Length"),
+                    value: value.as_bytes().to_vec(),
+                    malformed: false,
+                };
+                return Err((request, Error::MalformedHeader(line)));
+            },
+            None => (),
+        }
 
         Ok(ResponseReader {
             stream: stream,
@@ -124,12 +178,17 @@ impl<S: Stream> ResponseReader<S> {
             version: http_version,
             status: Status::from_code_and_reason(status_code, reason),
             headers: headers,
+            raw_headers: raw_headers,
         })
     }
 }
 
-impl<S: Stream> Reader for ResponseReader<S> {
+impl<S: Connecter + Stream> Reader for ResponseReader<S> {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
-        self.stream.read(buf)
+        let limit = timeout::limit(self.request.timeouts.idle, Idle, self.request.deadline());
+        if limit.is_some() {
+            self.stream.wrapped.set_timeout(limit.map(|(ms, _)| ms));
+        }
+        self.stream.read(buf).map_err(|err| timeout::reached(err, limit))
     }
 }
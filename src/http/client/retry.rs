@@ -0,0 +1,328 @@
+/*!
+
+Sending requests again when they fail for reasons which may soon pass.
+
+A `RetryPolicy` sends a request and, if no connection could be made, the connection was lost, or
+the server answered `502 Bad Gateway`, `503 Service Unavailable` or `504 Gateway Timeout`, sends
+it again after a pause, up to `max_attempts` times in all. The pause starts at `initial_delay` and
+doubles with each attempt, up to `max_delay`, and is shortened by a random amount (jitter) so that
+clients which failed together do not all come back together. If the response has a Retry-After
+header, that is waited for instead; if it asks for longer than `max_delay`, the response is
+returned as it is.
+
+Only requests with idempotent methods are sent again unless `methods` says otherwise (see
+`Method::is_idempotent`): sending a `POST` twice may do twice what was asked. A request which is to
+be sent again must be sent with its body not yet written, and the body given to `send_with_body`, so
+that it can be written anew for each attempt; a request whose headers have already been written is
+sent once. The `total` timeout of the request covers all the attempts together.
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::RequestWriter;
+use http::client::retry::RetryPolicy;
+use http::method::Put;
+use url::Url;
+
+fn main() {
+    let policy = RetryPolicy::new();
+    let url = Url::parse("http://example.com/lights/kitchen").unwrap();
+    let request: RequestWriter = RequestWriter::new(Put, url).unwrap();
+    let body = b"on".to_vec();
+    let response = policy.send_with_body(request, &body).unwrap();
+    println!("{:?}", response.status);
+}
+```
+
+*/
+
+use std::cmp::min;
+use std::io::{IoResult, ConnectionReset, ConnectionAborted, BrokenPipe};
+use std::io::timer::sleep;
+use std::mem;
+use std::num::Int;
+use std::rand::{thread_rng, Rng};
+use std::time::Duration;
+use time;
+
+use client::error::Error;
+use client::request::RequestWriter;
+use client::resolver::SystemResolver;
+use client::response::ResponseReader;
+use client::timeout::Timeout;
+use connecter::Connecter;
+use form::Form;
+use method::Method;
+use multipart::MultipartBody;
+use status::Status;
+use status::Status::{BadGateway, ServiceUnavailable, GatewayTimeout};
+
+/// A request body which can be written again for each attempt to send the request.
+pub trait Replayable {
+    /// Write the body to the request, setting the Content-Length header and any other headers
+    /// describing it.
+    fn write_body<S: Connecter + Reader + Writer>(&self, request: &mut RequestWriter<S>)
+                                                 -> IoResult<()>;
+}
+
+impl Replayable for Vec<u8> {
+    fn write_body<S: Connecter + Reader + Writer>(&self, request: &mut RequestWriter<S>)
+                                                 -> IoResult<()> {
+        request.headers.content_length = Some(self.len());
+        request.write(&self[])
+    }
+}
+
+impl Replayable for Form {
+    fn write_body<S: Connecter + Reader + Writer>(&self, request: &mut RequestWriter<S>)
+                                                 -> IoResult<()> {
+        request.write_form(self)
+    }
+}
+
+impl Replayable for MultipartBody {
+    fn write_body<S: Connecter + Reader + Writer>(&self, request: &mut RequestWriter<S>)
+                                                 -> IoResult<()> {
+        request.write_multipart(self)
+    }
+}
+
+/// When and how often to send a request again; see the module documentation.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// The most times a request is sent, counting the first; 3 by default.
+    pub max_attempts: usize,
+    /// The pause before the first retry, in milliseconds; 200 by default.
+    pub initial_delay: u64,
+    /// The longest pause between attempts, in milliseconds; 30 seconds by default.
+    pub max_delay: u64,
+    /// The methods of the requests which may be sent again, if not those which are idempotent
+    /// (the default, `None`).
+    pub methods: Option<Vec<Method>>,
+    /// The response statuses upon which a request is sent again; by default, `502 Bad Gateway`,
+    /// `503 Service Unavailable` and `504 Gateway Timeout`.
+    pub statuses: Vec<Status>,
+}
+
+impl RetryPolicy {
+    /// The default policy.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: 200,
+            max_delay: 30000,
+            methods: None,
+            statuses: vec!(BadGateway, ServiceUnavailable, GatewayTimeout),
+        }
+    }
+
+    /// Send a request with no body, sending it again as the policy allows.
+    pub fn send<S: Connecter + Reader + Writer>(&self, request: RequestWriter<S>)
+            -> Result<ResponseReader<S>, Error> {
+        self.send_body(request, None::<&Vec<u8>>)
+    }
+
+    /// Send a request with the body `body`, sending both again as the policy allows. The body of
+    /// `request` must not have been written.
+    pub fn send_with_body<S: Connecter + Reader + Writer, B: Replayable>(
+            &self, request: RequestWriter<S>, body: &B) -> Result<ResponseReader<S>, Error> {
+        self.send_body(request, Some(body))
+    }
+
+    fn send_body<S: Connecter + Reader + Writer, B: Replayable>(
+            &self, mut request: RequestWriter<S>, body: Option<&B>)
+            -> Result<ResponseReader<S>, Error> {
+        let retryable = match self.methods {
+            Some(ref methods) => methods.contains(&request.method),
+            None => request.method.is_idempotent(),
+        } && !request.headers_written();
+        let mut attempt = 1;
+        loop {
+            let may_retry = retryable && attempt < self.max_attempts;
+            let written = match body {
+                // Connecting first keeps the error of failing to connect from being taken for an
+                // error of writing.
+                Some(body) if !request.headers_written() => {
+                    match request.try_connect() {
+                        Ok(()) => body.write_body(&mut request).map_err(Error::from_io),
+                        Err(err) => Err(err),
+                    }
+                },
+                _ => Ok(()),
+            };
+            let (mut sent, delay) = match written {
+                Ok(()) => match request.read_response() {
+                    Ok(response) => {
+                        if !may_retry || !self.statuses.contains(&response.status) {
+                            return Ok(response);
+                        }
+                        let now = time::get_time().sec;
+                        // A wait too long to count in milliseconds is longer than `max_delay`.
+                        let wait = response.headers.retry_after.as_ref()
+                                           .map(|r| r.seconds_from(now).checked_mul(1000));
+                        let delay = match wait {
+                            Some(Some(wait)) if wait <= self.max_delay => wait,
+                            Some(_) => return Ok(response),
+                            None => self.jittered_backoff(attempt),
+                        };
+                        (response.request, delay)
+                    },
+                    Err((request, err)) => {
+                        if !may_retry || !is_transient(&err) {
+                            return Err(err);
+                        }
+                        (request, self.jittered_backoff(attempt))
+                    },
+                },
+                Err(err) => {
+                    if !may_retry || !is_transient(&err) {
+                        return Err(err);
+                    }
+                    (request, self.jittered_backoff(attempt))
+                },
+            };
+            request = try!(again(&mut sent));
+            sleep(Duration::milliseconds(delay as i64));
+            attempt += 1;
+        }
+    }
+
+    /// The pause before retry number `retry` (the first being 1), before jitter: `initial_delay`
+    /// doubled for each retry after the first, but no more than `max_delay`.
+    pub fn backoff(&self, retry: usize) -> u64 {
+        let mut delay = self.initial_delay;
+        for _ in range(1, retry) {
+            if delay >= self.max_delay {
+                break;
+            }
+            delay *= 2;
+        }
+        min(delay, self.max_delay)
+    }
+
+    /// The backoff with jitter: somewhere between half of it and all of it.
+    fn jittered_backoff(&self, retry: usize) -> u64 {
+        let delay = self.backoff(retry);
+        delay - thread_rng().gen_range(0, delay / 2 + 1)
+    }
+}
+
+/// Whether a failure may well not happen again: a connection which could not be made or was lost,
+/// or a connection attempt which took too long.
+pub fn is_transient(err: &Error) -> bool {
+    match *err {
+        Error::Connect(_) | Error::UnexpectedEof | Error::Timeout(Timeout::Connect) => true,
+        Error::Io(ref err) => match err.kind {
+            ConnectionReset | ConnectionAborted | BrokenPipe => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// A new request like one which has been sent, to send in its place.
+fn again<S: Connecter + Reader + Writer>(request: &mut RequestWriter<S>)
+                                        -> IoResult<RequestWriter<S>> {
+    let mut next = try!(RequestWriter::new(request.method.clone(), request.url.clone()));
+    next.headers = request.headers.clone();
+    next.record_raw_headers = request.record_raw_headers;
+    next.timeouts = request.timeouts;
+    next.remote_addr = request.remote_addr;
+    next.set_deadline(request.deadline());
+    next.resolver = mem::replace(&mut request.resolver, Box::new(SystemResolver));
+    Ok(next)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Acceptor, Listener, IoError, ConnectionReset, OtherIoError};
+    use std::io::net::tcp::TcpListener;
+    use std::thread::Thread;
+    use url::Url;
+    use client::error::Error;
+    use client::request::RequestWriter;
+    use client::timeout::Timeout;
+    use method::Method::{Get, Post};
+    use status::Status;
+    use super::{RetryPolicy, is_transient};
+
+    #[test]
+    fn test_backoff() {
+        let mut policy = RetryPolicy::new();
+        policy.initial_delay = 100;
+        policy.max_delay = 1000;
+        assert_eq!(policy.backoff(1), 100);
+        assert_eq!(policy.backoff(2), 200);
+        assert_eq!(policy.backoff(4), 800);
+        assert_eq!(policy.backoff(5), 1000);
+        assert_eq!(policy.backoff(100), 1000);
+        for _ in range(0us, 20) {
+            let delay = policy.jittered_backoff(3);
+            assert!(delay >= 200 && delay <= 400);
+        }
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let io = |kind| IoError { kind: kind, desc: "", detail: None };
+        assert!(is_transient(&Error::Connect(io(OtherIoError))));
+        assert!(is_transient(&Error::Io(io(ConnectionReset))));
+        assert!(is_transient(&Error::UnexpectedEof));
+        assert!(!is_transient(&Error::Io(io(OtherIoError))));
+        assert!(!is_transient(&Error::Timeout(Timeout::Total)));
+        assert!(!is_transient(&Error::MalformedStatusLine));
+    }
+
+    /// Serve the given responses to one connection each, returning the address of the server.
+    fn serve(responses: Vec<&'static str>) -> ::std::io::net::ip::SocketAddr {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        let mut acceptor = listener.listen().unwrap();
+        Thread::spawn(move || {
+            for response in responses.into_iter() {
+                let mut stream = acceptor.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                stream.write_str(response).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_retry() {
+        let unavailable = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\n\
+                           Content-Length: 0\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        let mut policy = RetryPolicy::new();
+        policy.initial_delay = 10;
+
+        let addr = serve(vec!(unavailable, unavailable, ok));
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let request: RequestWriter = RequestWriter::new(Get, url.clone()).unwrap();
+        assert_eq!(policy.send(request).unwrap().status, Status::Ok);
+
+        // Not idempotent, so not sent again.
+        let addr = serve(vec!(unavailable));
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let request: RequestWriter = RequestWriter::new(Post, url).unwrap();
+        let response = policy.send_with_body(request, &b"x".to_vec()).unwrap();
+        assert_eq!(response.status, Status::ServiceUnavailable);
+
+        // Unless the policy says it may be.
+        policy.methods = Some(vec!(Post));
+        let addr = serve(vec!(unavailable, ok));
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let request: RequestWriter = RequestWriter::new(Post, url).unwrap();
+        let response = policy.send_with_body(request, &b"x".to_vec()).unwrap();
+        assert_eq!(response.status, Status::Ok);
+
+        // A wait longer than `max_delay`, even one too long to count, is not waited for.
+        let addr = serve(vec!("HTTP/1.1 503 Service Unavailable\r\n\
+                               Retry-After: 18446744073709551615\r\nContent-Length: 0\r\n\r\n"));
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+        assert_eq!(policy.send(request).unwrap().status, Status::ServiceUnavailable);
+    }
+}
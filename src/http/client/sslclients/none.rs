@@ -3,13 +3,14 @@
 use std::io::net::ip::SocketAddr;
 use std::io::net::tcp::TcpStream;
 use std::io::{IoResult, IoError, InvalidInput};
-use connecter::Connecter;
+use client::error::Error;
+use connecter::{Connecter, connect_tcp};
 use self::NetworkStream::NormalStream;
 
 /// A TCP stream, plain text and with no SSL support.
 ///
 /// This build was made *without* SSL support; if you attempt to make an SSL
-/// connection you will receive an `IoError` of the `InvalidInput` kind.
+/// connection you will receive an `Error::Tls` holding an `IoError` of the `InvalidInput` kind.
 ///
 /// (To build with SSL support, use ``--cfg openssl`` or ``--cfg nss``.)
 pub enum NetworkStream {
@@ -17,20 +18,38 @@ pub enum NetworkStream {
 }
 
 impl Connecter for NetworkStream {
-    fn connect(addr: SocketAddr, _host: &str, use_ssl: bool) -> IoResult<NetworkStream> {
-        if use_ssl {
-            Err(IoError {
-                kind: InvalidInput,
-                desc: "http crate was compiled without SSL support",
-                detail: None,
-            })
-        } else {
-            let stream = try!(TcpStream::connect(addr));
-            Ok(NormalStream(stream))
+    fn connect(addr: SocketAddr, _host: &str, use_ssl: bool) -> Result<NetworkStream, Error> {
+        try!(check_ssl(use_ssl).map_err(Error::Tls));
+        let stream = try!(TcpStream::connect(addr).map_err(Error::Connect));
+        Ok(NormalStream(stream))
+    }
+
+    fn connect_any(addrs: &[SocketAddr], _host: &str, use_ssl: bool, timeout_ms: Option<u64>)
+                   -> Result<NetworkStream, Error> {
+        try!(check_ssl(use_ssl).map_err(Error::Tls));
+        let stream = try!(connect_tcp(addrs, timeout_ms).map_err(Error::Connect));
+        Ok(NormalStream(stream))
+    }
+
+    fn set_timeout(&mut self, timeout_ms: Option<u64>) {
+        match *self {
+            NormalStream(ref mut ns) => ns.set_timeout(timeout_ms),
         }
     }
 }
 
+fn check_ssl(use_ssl: bool) -> IoResult<()> {
+    if use_ssl {
+        Err(IoError {
+            kind: InvalidInput,
+            desc: "http crate was compiled without SSL support",
+            detail: None,
+        })
+    } else {
+        Ok(())
+    }
+}
+
 impl Reader for NetworkStream {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
         match *self {
@@ -1,15 +1,14 @@
 //! SSL support provided by OpenSSL.
 
-#[cfg(any(feature = "ssl", feature = "default"))]
-extern crate openssl;
-
 use std::io::net::ip::SocketAddr;
 use std::io::net::tcp::TcpStream;
 use std::io::{IoResult, IoError, ConnectionAborted, OtherIoError};
-use self::openssl::ssl::{SslStream, SslContext, SslMethod, Ssl};
-use self::openssl::ssl::error::{SslError, StreamError, SslSessionClosed, OpenSslErrors};
+use openssl::ssl::{SslStream, SslContext, SslMethod, Ssl};
+use openssl::ssl::error::{SslError, StreamError, SslSessionClosed, OpenSslErrors};
 use self::NetworkStream::{NormalStream, SslProtectedStream};
-use connecter::Connecter;
+use client::error::Error;
+use client::timeout::now_ms;
+use connecter::{Connecter, connect_tcp};
 
 /// A TCP stream, either plain text or SSL.
 ///
@@ -20,8 +19,37 @@ pub enum NetworkStream {
 }
 
 impl Connecter for NetworkStream {
-    fn connect(addr: SocketAddr, host: &str, use_ssl: bool) -> IoResult<NetworkStream> {
-        let stream = try!(TcpStream::connect(addr));
+    fn connect(addr: SocketAddr, host: &str, use_ssl: bool) -> Result<NetworkStream, Error> {
+        let stream = try!(TcpStream::connect(addr).map_err(Error::Connect));
+        NetworkStream::wrap(stream, host, use_ssl).map_err(Error::Tls)
+    }
+
+    fn connect_any(addrs: &[SocketAddr], host: &str, use_ssl: bool, timeout_ms: Option<u64>)
+                   -> Result<NetworkStream, Error> {
+        let started = now_ms();
+        let mut stream = try!(connect_tcp(addrs, timeout_ms).map_err(Error::Connect));
+        if use_ssl {
+            // The handshake has whatever is left of the time, lest a server which takes the
+            // connection and then says nothing hang the request.
+            stream.set_timeout(timeout_ms.map(|timeout| {
+                let spent = now_ms() - started;
+                if timeout > spent { timeout - spent } else { 0 }
+            }));
+        }
+        NetworkStream::wrap(stream, host, use_ssl).map_err(Error::Tls)
+    }
+
+    fn set_timeout(&mut self, timeout_ms: Option<u64>) {
+        match *self {
+            NormalStream(ref mut ns) => ns.set_timeout(timeout_ms),
+            SslProtectedStream(ref mut ns) => ns.get_mut().set_timeout(timeout_ms),
+        }
+    }
+}
+
+impl NetworkStream {
+    /// Set up SSL over a new connection, if it is wanted.
+    fn wrap(stream: TcpStream, host: &str, use_ssl: bool) -> IoResult<NetworkStream> {
         if use_ssl {
             let context = try!(SslContext::new(SslMethod::Sslv23).map_err(lift_ssl_error));
             let ssl = try!(Ssl::new(&context).map_err(lift_ssl_error));
@@ -0,0 +1,234 @@
+/*!
+
+Time limits for client requests.
+
+Each `RequestWriter` has a set of `Timeouts`, none of which are set by default:
+
+- `connect`, for a TCP connection to be made (to any of the addresses of the host) and SSL to be
+  set up over it;
+- `first_byte`, once the request has been sent, for the response to begin;
+- `idle`, once the response has begun, for the rest of its head and then for each read of its
+  body;
+- `total`, for the whole exchange, from connecting until the last of the body has been read.
+
+A request which runs out of time fails with `Error::Timeout`, saying which of the limits was
+reached, so that a server which is slow to answer can be told apart from one which could not be
+reached at all. Reading the body, which fails with an `IoError`, fails with one of the `TimedOut`
+kind; `Timeout::of` tells which limit it was.
+
+```rust,no_run
+extern crate http;
+extern crate url;
+
+use http::client::RequestWriter;
+use http::client::error::Error;
+use http::client::timeout::Timeout;
+use http::method::Get;
+use url::Url;
+
+fn main() {
+    let url = Url::parse("http://example.com/").unwrap();
+    let mut request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+    request.timeouts.connect = Some(2000);
+    request.timeouts.total = Some(10000);
+    match request.read_response() {
+        Ok(response) => println!("{:?}", response.status),
+        Err((_, Error::Timeout(Timeout::Connect))) => println!("could not connect in time"),
+        Err((_, err)) => println!("{}", err),
+    }
+}
+```
+
+*/
+
+use std::io::{IoError, TimedOut};
+use time::precise_time_ns;
+
+pub use self::Timeout::{Connect, FirstByte, Idle, Total};
+
+/// The time limits of a request, in milliseconds; `None` is no limit.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub struct Timeouts {
+    /// The longest to wait for a connection to be made, and SSL set up over it.
+    pub connect: Option<u64>,
+    /// The longest to wait, once the request has been sent, for the first byte of the response.
+    pub first_byte: Option<u64>,
+    /// The longest to wait, once the response has begun, for the rest of its head, and then for
+    /// each read of the body.
+    pub idle: Option<u64>,
+    /// The longest the whole request may take, from connecting to reading the end of the body.
+    pub total: Option<u64>,
+}
+
+impl Timeouts {
+    /// No time limits at all.
+    pub fn new() -> Timeouts {
+        Timeouts {
+            connect: None,
+            first_byte: None,
+            idle: None,
+            total: None,
+        }
+    }
+}
+
+/// Which of the time limits of a request was reached.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum Timeout {
+    /// No connection could be made, or SSL set up over it, in time.
+    Connect,
+    /// The server did not begin its response in time.
+    FirstByte,
+    /// The server stopped sending the response part of the way through.
+    Idle,
+    /// The request as a whole took too long.
+    Total,
+}
+
+impl Timeout {
+    /// The error with which a request fails on reaching this limit.
+    pub fn error(self) -> IoError {
+        IoError {
+            kind: TimedOut,
+            desc: self.desc(),
+            detail: None,
+        }
+    }
+
+    /// The limit which a request failing with `err` reached, if it failed for lack of time.
+    pub fn of(err: &IoError) -> Option<Timeout> {
+        if err.kind != TimedOut {
+            return None;
+        }
+        [Connect, FirstByte, Idle, Total].iter().map(|&t| t).find(|t| t.desc() == err.desc)
+    }
+
+    fn desc(self) -> &'static str {
+        match self {
+            Connect => "timed out connecting",
+            FirstByte => "timed out waiting for the response",
+            Idle => "timed out reading the response",
+            Total => "request took too long",
+        }
+    }
+}
+
+/// The current time, in milliseconds from an arbitrary point, for working out deadlines.
+pub fn now_ms() -> u64 {
+    precise_time_ns() / 1000000
+}
+
+/// The time limit of a step of a request which may take up to `timeout`, in a request which must
+/// be finished by `deadline` (as from `now_ms`): the number of milliseconds left, and the limit
+/// which will be reached first.
+pub fn limit(timeout: Option<u64>, kind: Timeout, deadline: Option<u64>)
+             -> Option<(u64, Timeout)> {
+    let left = deadline.map(|deadline| {
+        let now = now_ms();
+        if deadline > now { deadline - now } else { 0 }
+    });
+    match (timeout, left) {
+        (Some(timeout), Some(left)) if left < timeout => Some((left, Total)),
+        (Some(timeout), _) => Some((timeout, kind)),
+        (None, Some(left)) => Some((left, Total)),
+        (None, None) => None,
+    }
+}
+
+/// Report a `TimedOut` error from a step limited by `limit` (see `limit`) as the limit reached.
+pub fn reached(err: IoError, limit: Option<(u64, Timeout)>) -> IoError {
+    match limit {
+        Some((_, timeout)) if err.kind == TimedOut => timeout.error(),
+        _ => err,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{IoError, TimedOut, ConnectionRefused, Listener, Acceptor};
+    use std::io::net::tcp::TcpListener;
+    use std::sync::mpsc::channel;
+    use std::thread::Thread;
+    use url::Url;
+    use client::error::Error;
+    use client::request::RequestWriter;
+    use method::Method::Get;
+    use super::{Timeout, Connect, FirstByte, Idle, Total, limit, reached, now_ms};
+
+    #[test]
+    fn test_timeout_of() {
+        for &timeout in [Connect, FirstByte, Idle, Total].iter() {
+            assert_eq!(Timeout::of(&timeout.error()), Some(timeout));
+        }
+        let refused = IoError { kind: ConnectionRefused, desc: "refused", detail: None };
+        assert_eq!(Timeout::of(&refused), None);
+        let other = IoError { kind: TimedOut, desc: "timed out", detail: None };
+        assert_eq!(Timeout::of(&other), None);
+        assert_eq!(Timeout::of(&reached(other, Some((100, Idle)))), Some(Idle));
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(limit(None, FirstByte, None), None);
+        assert_eq!(limit(Some(100), FirstByte, None), Some((100, FirstByte)));
+        assert_eq!(limit(Some(100), FirstByte, Some(now_ms() + 60000)), Some((100, FirstByte)));
+        assert_eq!(limit(Some(100), FirstByte, Some(now_ms() - 1)), Some((0, Total)));
+        assert_eq!(limit(None, Idle, Some(now_ms() - 1)), Some((0, Total)));
+    }
+
+    #[test]
+    fn test_first_byte_timeout() {
+        // A server which takes connections (into its backlog) but never answers.
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        let _acceptor = listener.listen().unwrap();
+
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let mut request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+        request.timeouts.connect = Some(5000);
+        request.timeouts.first_byte = Some(100);
+        match request.read_response() {
+            Err((_, Error::Timeout(FirstByte))) => (),
+            Ok(_) => panic!("the server never answers"),
+            Err((_, err)) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_idle_timeout_in_head() {
+        // A server which begins its response, then either stalls or hangs up.
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        let mut acceptor = listener.listen().unwrap();
+        let (done_sender, done_receiver) = channel::<()>();
+        Thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            let mut stalled = acceptor.accept().unwrap();
+            stalled.read(&mut buf).unwrap();
+            stalled.write(b"HTTP/1.1 200 OK\r\nContent-").unwrap();
+            let mut closed = acceptor.accept().unwrap();
+            closed.read(&mut buf).unwrap();
+            closed.write(b"HTTP/1.1 200 OK\r\nContent-").unwrap();
+            drop(closed);
+            let _ = done_receiver.recv();
+        });
+
+        let url = Url::parse(&format!("http://{}/", addr)[]).unwrap();
+        let mut request: RequestWriter = RequestWriter::new(Get, url.clone()).unwrap();
+        request.timeouts.idle = Some(100);
+        match request.read_response() {
+            Err((_, Error::Timeout(Idle))) => (),
+            Ok(_) => panic!("the server never finishes"),
+            Err((_, err)) => panic!("{}", err),
+        }
+
+        let mut request: RequestWriter = RequestWriter::new(Get, url).unwrap();
+        request.timeouts.idle = Some(60000);
+        match request.read_response() {
+            Err((_, Error::UnexpectedEof)) => (),
+            Ok(_) => panic!("the server hung up"),
+            Err((_, err)) => panic!("{}", err),
+        }
+        done_sender.send(()).unwrap();
+    }
+}
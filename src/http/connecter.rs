@@ -1,13 +1,198 @@
 // The spelling "Connecter" is deliberate, by the way.
 
-use std::io::IoResult;
-use std::io::net::ip::SocketAddr;
+use std::io::{IoResult, IoError, OtherIoError, TimedOut};
+use std::io::net::ip::{SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr};
+use std::io::net::tcp::TcpStream;
+use std::io::timer::Timer;
+use std::sync::mpsc::channel;
+use std::thread::Thread;
+use std::time::Duration;
+use time::precise_time_ns;
+
+use client::error::Error;
+
+/// How long to wait for a connection attempt before starting the next one in parallel, in
+/// milliseconds (RFC 8305, section 5).
+pub const CONNECTION_ATTEMPT_DELAY: i64 = 250;
 
 /// A trait for the concept of opening a stream connected to a IP socket address.
 ///
 /// Why is this here? So that we can implement things which must make
 /// connections in terms of *anything* that can make such a connection rather
 /// than in terms of `TcpStream` only. This is handy for testing and for SSL.
+///
+/// Failures are reported as `Error::Connect` if no connection could be made, or `Error::Tls` if
+/// SSL could not be set up over it.
 pub trait Connecter {
-    fn connect(addr: SocketAddr, host: &str, use_ssl: bool) -> IoResult<Self>;
+    fn connect(addr: SocketAddr, host: &str, use_ssl: bool) -> Result<Self, Error>;
+
+    /// Connect to whichever of the addresses of a host can be reached, in order of preference,
+    /// giving up with a `TimedOut` error after `timeout_ms` milliseconds if it is set. The limit
+    /// covers setting up SSL over the connection as well as making it.
+    ///
+    /// The default tries each address in turn, with no time limit, returning the error of the last
+    /// if none can be reached. `NetworkStream` instead tries them in parallel with staggered
+    /// starts, as `connect_tcp` does.
+    fn connect_any(addrs: &[SocketAddr], host: &str, use_ssl: bool, _timeout_ms: Option<u64>)
+                   -> Result<Self, Error> {
+        let mut last_error = Error::Connect(no_addresses());
+        for addr in addrs.iter() {
+            match Connecter::connect(*addr, host, use_ssl) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Make reads and writes fail with a `TimedOut` error once `timeout_ms` milliseconds from now
+    /// have passed, or remove the limit if it is `None`. The default does nothing.
+    fn set_timeout(&mut self, _timeout_ms: Option<u64>) { }
+}
+
+fn no_addresses() -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: "no addresses to connect to",
+        detail: None,
+    }
+}
+
+fn is_ipv6(addr: &IpAddr) -> bool {
+    match *addr {
+        Ipv6Addr(..) => true,
+        Ipv4Addr(..) => false,
+    }
+}
+
+/// Order the addresses of a host for connection attempts (RFC 8305, section 4): IPv6 first, then
+/// alternating between the address families, so that a broken network of one family costs no
+/// more than one attempt delay. The order within each family is kept.
+pub fn sort_addresses(addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+    let (ipv6, ipv4): (Vec<IpAddr>, Vec<IpAddr>) = addrs.into_iter().partition(is_ipv6);
+    let mut sorted = Vec::with_capacity(ipv6.len() + ipv4.len());
+    let mut ipv6 = ipv6.into_iter();
+    let mut ipv4 = ipv4.into_iter();
+    loop {
+        match (ipv6.next(), ipv4.next()) {
+            (None, None) => return sorted,
+            (a, b) => {
+                sorted.extend(a.into_iter());
+                sorted.extend(b.into_iter());
+            },
+        }
+    }
+}
+
+/// Open a TCP connection to `addr`, failing with a `TimedOut` error if it is not made by
+/// `deadline`, in milliseconds as from `precise_time_ns`.
+fn connect_by(addr: SocketAddr, deadline: Option<u64>) -> IoResult<TcpStream> {
+    match deadline {
+        None => TcpStream::connect(addr),
+        Some(deadline) => {
+            let now = precise_time_ns() / 1000000;
+            if now >= deadline {
+                return Err(IoError {
+                    kind: TimedOut,
+                    desc: "connection timed out",
+                    detail: None,
+                });
+            }
+            TcpStream::connect_timeout(addr, Duration::milliseconds((deadline - now) as i64))
+        },
+    }
+}
+
+/// Open a TCP connection to whichever of `addrs` answers first ("Happy Eyeballs", RFC 8305),
+/// giving up with a `TimedOut` error after `timeout_ms` milliseconds if it is set.
+///
+/// The addresses are tried in order, starting a new attempt every `CONNECTION_ATTEMPT_DELAY`
+/// milliseconds, or as soon as the previous attempt fails, without abandoning those already
+/// started. The first connection made is returned, and any made later are closed; if none can be
+/// made, the error of the last attempt to fail is returned.
+pub fn connect_tcp(addrs: &[SocketAddr], timeout_ms: Option<u64>) -> IoResult<TcpStream> {
+    let deadline = timeout_ms.map(|timeout| precise_time_ns() / 1000000 + timeout);
+    match addrs.len() {
+        0 => return Err(no_addresses()),
+        1 => return connect_by(addrs[0], deadline),
+        _ => (),
+    }
+    let (sender, receiver) = channel();
+    let mut timer = try!(Timer::new());
+    let mut started = 0;
+    let mut failed = 0;
+    loop {
+        if started < addrs.len() {
+            let addr = addrs[started];
+            let sender = sender.clone();
+            Thread::spawn(move || {
+                // If another attempt has already won, the receiver is gone and this connection is
+                // simply dropped.
+                let _ = sender.send(connect_by(addr, deadline));
+            });
+            started += 1;
+        }
+
+        let result = if started < addrs.len() {
+            let timeout = timer.oneshot(Duration::milliseconds(CONNECTION_ATTEMPT_DELAY));
+            select! {
+                result = receiver.recv() => Some(result.unwrap()),
+                _ = timeout.recv() => None
+            }
+        } else {
+            Some(receiver.recv().unwrap())
+        };
+        match result {
+            Some(Ok(stream)) => return Ok(stream),
+            Some(Err(err)) => {
+                failed += 1;
+                if failed == addrs.len() {
+                    return Err(err);
+                }
+            },
+            // The attempt delay has passed: start the next attempt.
+            None => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Listener;
+    use std::io::net::ip::{Ipv4Addr, Ipv6Addr};
+    use std::io::net::tcp::TcpListener;
+    use super::{sort_addresses, connect_tcp};
+
+    #[test]
+    fn test_sort_addresses() {
+        let a4 = Ipv4Addr(192, 0, 2, 1);
+        let b4 = Ipv4Addr(192, 0, 2, 2);
+        let c4 = Ipv4Addr(192, 0, 2, 3);
+        let a6 = Ipv6Addr(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let b6 = Ipv6Addr(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+        assert_eq!(sort_addresses(vec!(a4, b4, a6, c4, b6)), vec!(a6, a4, b6, b4, c4));
+        assert_eq!(sort_addresses(vec!(a4, b4)), vec!(a4, b4));
+        assert_eq!(sort_addresses(vec!(a6)), vec!(a6));
+        assert_eq!(sort_addresses(Vec::new()), Vec::new());
+    }
+
+    #[test]
+    fn test_connect_tcp() {
+        // A port with nothing listening on it, which refuses connections.
+        let closed = {
+            let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.socket_name().unwrap()
+        };
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.socket_name().unwrap();
+        let _acceptor = listener.listen().unwrap();
+
+        assert!(connect_tcp(&[], None).is_err());
+        assert!(connect_tcp(&[closed], None).is_err());
+        assert!(connect_tcp(&[closed, closed], None).is_err());
+        let mut stream = connect_tcp(&[closed, open], None).unwrap();
+        assert_eq!(stream.peer_name().unwrap(), open);
+        let mut stream = connect_tcp(&[open, closed], Some(5000)).unwrap();
+        assert_eq!(stream.peer_name().unwrap(), open);
+    }
 }
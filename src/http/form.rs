@@ -0,0 +1,304 @@
+/*!
+
+HTML form data in the `application/x-www-form-urlencoded` format, as used by query strings and the
+bodies of form submissions.
+
+On the server, `Request::form` decodes the body of a request and `Request::query` its query
+string; on the client, `RequestWriter::write_form` sends a form as the body of a request.
+
+Decoding follows the URL standard: the input is split on `&`, each field on its first `=`, and in
+names and values `+` stands for a space and `%XX` for a byte. Fields which are empty are skipped
+and invalid percent-escapes are kept as they are. The bytes are then decoded as UTF-8, or as
+ISO-8859-1 if the Content-Type says so; invalid UTF-8 is replaced with U+FFFD rather than
+refused, as browsers do.
+
+*/
+
+use std::ascii::AsciiExt;
+use std::slice::Iter;
+
+use headers::content_type::MediaType;
+
+/// Form data: an ordered multimap of names to values. A name may appear several times, as from a
+/// set of check boxes or a `<select multiple>`.
+#[derive(Clone, PartialEq, Eq, Show)]
+pub struct Form {
+    fields: Vec<(String, String)>,
+}
+
+impl Form {
+    /// An empty form.
+    pub fn new() -> Form {
+        Form {
+            fields: Vec::new(),
+        }
+    }
+
+    /// The first value of the named field (matched exactly).
+    pub fn get<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.fields.iter().find(|&&(ref n, _)| &n[] == name).map(|&(_, ref v)| &v[])
+    }
+
+    /// All the values of the named field, in order.
+    pub fn get_all<'a>(&'a self, name: &str) -> Vec<&'a str> {
+        self.fields.iter().filter(|&&(ref n, _)| &n[] == name).map(|&(_, ref v)| &v[]).collect()
+    }
+
+    /// Add a field, after any others of the same name.
+    pub fn append(&mut self, name: String, value: String) {
+        self.fields.push((name, value));
+    }
+
+    /// Set a field, replacing any others of the same name.
+    pub fn insert(&mut self, name: String, value: String) {
+        self.remove(&name[]);
+        self.fields.push((name, value));
+    }
+
+    /// Remove all fields of the given name, returning their values.
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        let (removed, kept): (Vec<_>, Vec<_>) = self.fields.drain()
+                                                    .partition(|&(ref n, _)| &n[] == name);
+        self.fields = kept;
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The fields in order.
+    pub fn iter(&self) -> Iter<(String, String)> {
+        self.fields.iter()
+    }
+
+    /// Encode the form as `application/x-www-form-urlencoded`.
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        for (i, &(ref name, ref value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push('&');
+            }
+            out.push_str(&encode_component(&name[])[]);
+            out.push('=');
+            out.push_str(&encode_component(&value[])[]);
+        }
+        out
+    }
+
+    /// The Content-Type of an encoded form.
+    pub fn media_type() -> MediaType {
+        MediaType::new(String::from_str("application"), String::from_str("x-www-form-urlencoded"),
+                       Vec::new())
+    }
+}
+
+/// Limits upon the form data which will be decoded, to bound the memory a client can make the
+/// server use.
+#[derive(Clone, Copy)]
+pub struct FormLimits {
+    /// The maximum length of the encoded form, in bytes; 2 MiB by default.
+    pub max_length: usize,
+    /// The maximum number of fields; a thousand by default.
+    pub max_fields: usize,
+}
+
+impl FormLimits {
+    pub fn new() -> FormLimits {
+        FormLimits {
+            max_length: 2 * 1024 * 1024,
+            max_fields: 1000,
+        }
+    }
+}
+
+/// The reasons form data cannot be decoded.
+#[derive(Clone, PartialEq, Eq, Show)]
+pub enum FormError {
+    /// The request body is not `application/x-www-form-urlencoded` (or has no Content-Type). For
+    /// file uploads, `multipart/form-data` is used instead.
+    NotAForm,
+    /// The Content-Type gives a charset other than UTF-8, US-ASCII or ISO-8859-1.
+    UnsupportedCharset(String),
+    /// The encoded form is longer than `FormLimits::max_length`.
+    TooLong,
+    /// The form has more than `FormLimits::max_fields` fields.
+    TooManyFields,
+}
+
+/// How the decoded bytes of a form are turned into strings.
+#[derive(Clone, Copy, PartialEq)]
+enum Charset {
+    Utf8,
+    Latin1,
+}
+
+/// Decode a form body with the given Content-Type.
+pub fn decode_body(content_type: Option<&MediaType>, body: &[u8], limits: &FormLimits)
+                   -> Result<Form, FormError> {
+    let content_type = match content_type {
+        Some(content_type) if content_type.type_[].eq_ignore_ascii_case("application") &&
+                content_type.subtype[].eq_ignore_ascii_case("x-www-form-urlencoded") => {
+            content_type
+        },
+        _ => return Err(FormError::NotAForm),
+    };
+    let charset = content_type.parameters.iter()
+                              .find(|&&(ref name, _)| name[].eq_ignore_ascii_case("charset"))
+                              .map(|&(_, ref value)| value.to_ascii_lowercase());
+    let charset = match charset.as_ref().map(|charset| &charset[]) {
+        None | Some("utf-8") | Some("utf8") | Some("us-ascii") => Charset::Utf8,
+        Some("iso-8859-1") | Some("latin1") => Charset::Latin1,
+        Some(other) => return Err(FormError::UnsupportedCharset(String::from_str(other))),
+    };
+    decode_with(body, charset, limits)
+}
+
+/// Decode a query string (without the leading `?`), or any other UTF-8 form data.
+pub fn decode(input: &[u8], limits: &FormLimits) -> Result<Form, FormError> {
+    decode_with(input, Charset::Utf8, limits)
+}
+
+fn decode_with(input: &[u8], charset: Charset, limits: &FormLimits) -> Result<Form, FormError> {
+    if input.len() > limits.max_length {
+        return Err(FormError::TooLong);
+    }
+    let mut form = Form::new();
+    for field in input.split(|&b| b == b'&') {
+        if field.is_empty() {
+            continue;
+        }
+        if form.len() == limits.max_fields {
+            return Err(FormError::TooManyFields);
+        }
+        let mut parts = field.splitn(1, |&b| b == b'=');
+        let name = parts.next().unwrap();
+        let value = parts.next().unwrap_or(&field[field.len()..]);
+        form.append(decode_component(name, charset), decode_component(value, charset));
+    }
+    Ok(form)
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'...b'9' => Some(b - b'0'),
+        b'a'...b'f' => Some(b - b'a' + 10),
+        b'A'...b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Percent-decode a name or value, with `+` for space.
+fn decode_component(input: &[u8], charset: Charset) -> String {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'+' => bytes.push(b' '),
+            b'%' if i + 2 < input.len() => {
+                match (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                    (Some(high), Some(low)) => {
+                        bytes.push(high * 16 + low);
+                        i += 2;
+                    },
+                    _ => bytes.push(b'%'),
+                }
+            },
+            b => bytes.push(b),
+        }
+        i += 1;
+    }
+    match charset {
+        Charset::Utf8 => String::from_utf8_lossy(&bytes[]).into_owned(),
+        Charset::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Encode a name or value as `application/x-www-form-urlencoded`: alphanumerics and `*-._` are
+/// kept, spaces become `+`, and everything else (as UTF-8) is percent-encoded.
+pub fn encode_component(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for &b in input.as_bytes().iter() {
+        match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'*' | b'-' | b'.' | b'_' => {
+                out.push(b as char)
+            },
+            b' ' => out.push('+'),
+            b => out.push_str(&format!("%{:02X}", b)[]),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use headers::content_type::MediaType;
+    use super::{Form, FormLimits, FormError, decode, decode_body, encode_component};
+
+    fn pairs(form: &Form) -> Vec<(&str, &str)> {
+        form.iter().map(|&(ref n, ref v)| (&n[], &v[])).collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let limits = FormLimits::new();
+        let form = decode(b"a=1&b=x+y%20z&&a=%E2%9C%93&c&=d&e=%zz%4", &limits).unwrap();
+        assert_eq!(pairs(&form), vec!(("a", "1"), ("b", "x y z"), ("a", "\u{2713}"), ("c", ""),
+                                      ("", "d"), ("e", "%zz%4")));
+        assert_eq!(form.get("a"), Some("1"));
+        assert_eq!(form.get_all("a"), vec!("1", "\u{2713}"));
+        assert_eq!(form.get("z"), None);
+        assert_eq!(decode(b"a=%FF", &limits).unwrap().get("a"), Some("\u{FFFD}"));
+        assert!(decode(b"", &limits).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = FormLimits { max_length: 8, max_fields: 2 };
+        assert_eq!(decode(b"a=1&b=2", &limits).unwrap().len(), 2);
+        assert_eq!(decode(b"a=1&b=2&c", &limits), Err(FormError::TooLong));
+        assert_eq!(decode(b"a&b&&c", &limits), Err(FormError::TooManyFields));
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let limits = FormLimits::new();
+        let media_type = |parameters: Vec<(String, String)>| MediaType::new(
+            String::from_str("application"), String::from_str("x-www-form-urlencoded"),
+            parameters);
+        assert_eq!(decode_body(Some(&media_type(Vec::new())), b"a=%C3%A9", &limits)
+                       .unwrap().get("a"),
+                   Some("\u{e9}"));
+        let latin1 = media_type(vec!((String::from_str("charset"),
+                                      String::from_str("ISO-8859-1"))));
+        assert_eq!(decode_body(Some(&latin1), b"a=%E9", &limits).unwrap().get("a"),
+                   Some("\u{e9}"));
+        let koi8 = media_type(vec!((String::from_str("charset"), String::from_str("KOI8-R"))));
+        assert_eq!(decode_body(Some(&koi8), b"a=1", &limits),
+                   Err(FormError::UnsupportedCharset(String::from_str("koi8-r"))));
+        let json = MediaType::new(String::from_str("application"), String::from_str("json"),
+                                  Vec::new());
+        assert_eq!(decode_body(Some(&json), b"{}", &limits), Err(FormError::NotAForm));
+        assert_eq!(decode_body(None, b"a=1", &limits), Err(FormError::NotAForm));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode_component("my client:1/\u{e9}*"), "my+client%3A1%2F%C3%A9*");
+        let mut form = Form::new();
+        form.append(String::from_str("q"), String::from_str("a&b=c"));
+        form.append(String::from_str("tag"), String::from_str("x"));
+        form.append(String::from_str("tag"), String::from_str("y z"));
+        assert_eq!(form.encode(), "q=a%26b%3Dc&tag=x&tag=y+z");
+        assert_eq!(decode(form.encode().as_bytes(), &FormLimits::new()), Ok(form.clone()));
+
+        form.insert(String::from_str("tag"), String::from_str("w"));
+        assert_eq!(form.encode(), "q=a%26b%3Dc&tag=w");
+        assert_eq!(form.remove("q"), vec!(String::from_str("a&b=c")));
+        assert_eq!(form.len(), 1);
+    }
+}
@@ -0,0 +1,220 @@
+//! The Origin request header and the headers of Cross-Origin Resource Sharing (CORS), defined in
+//! the Fetch standard, sections 3.1 and 3.2.
+//!
+//! Origin                           = origin-or-null
+//! origin-or-null                   = origin / "null"
+//! Access-Control-Request-Method    = method
+//! Access-Control-Request-Headers   = 1#field-name
+//! Access-Control-Allow-Origin      = origin-or-null / "*"
+//! Access-Control-Allow-Credentials = %s"true"
+//! Access-Control-Allow-Methods     = #method
+//! Access-Control-Allow-Headers     = #field-name
+//! Access-Control-Expose-Headers    = #field-name
+//! Access-Control-Max-Age           = delta-seconds
+//!
+//! The method headers use `Method` and Access-Control-Max-Age uses `usize`; the types for the rest
+//! are here.
+
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::io::IoResult;
+
+pub use self::Origin::{NullOrigin, SerializedOrigin};
+pub use self::AllowOrigin::{AnyOrigin, AllowedOrigin};
+
+/// The origin a request was made from: a scheme, host and port such as `https://example.com` or
+/// `http://localhost:8080`, or `null` for an opaque origin (such as a sandboxed document).
+#[derive(Clone, PartialEq, Eq)]
+pub enum Origin {
+    NullOrigin,
+    /// A serialized origin. The scheme and host are in lowercase.
+    SerializedOrigin(String),
+}
+
+impl fmt::Show for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NullOrigin => f.write_str("null"),
+            SerializedOrigin(ref origin) => f.write_str(&origin[]),
+        }
+    }
+}
+
+/// Parse an origin-or-null, lowercasing the scheme and host of an origin.
+fn parse_origin(value: &str) -> Option<Origin> {
+    if value == "null" {
+        return Some(NullOrigin);
+    }
+    let separator = match value.find_str("://") {
+        Some(separator) if separator > 0 => separator,
+        _ => return None,
+    };
+    let (scheme, host_port) = (&value[..separator], &value[separator + 3..]);
+    if !scheme.bytes().all(|b| (b as char).is_alphanumeric() || b == b'+' || b == b'-' ||
+                              b == b'.') ||
+            host_port.is_empty() ||
+            host_port.bytes().any(|b| b == b'/' || b == b'?' || b == b'#' || b <= b' ') {
+        return None;
+    }
+    Some(SerializedOrigin(format!("{}://{}", scheme.to_ascii_lowercase(),
+                                  host_port.to_ascii_lowercase())))
+}
+
+impl super::HeaderConvertible for Origin {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<Origin> {
+        parse_origin(reader.collect_to_string().trim())
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// The value of Access-Control-Allow-Origin.
+#[derive(Clone, PartialEq, Eq)]
+pub enum AllowOrigin {
+    /// `*`: any origin may read the response, provided that the request was made without
+    /// credentials.
+    AnyOrigin,
+    /// Only the given origin may read the response.
+    AllowedOrigin(Origin),
+}
+
+impl fmt::Show for AllowOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AnyOrigin => f.write_str("*"),
+            AllowedOrigin(ref origin) => origin.fmt(f),
+        }
+    }
+}
+
+impl super::HeaderConvertible for AllowOrigin {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<AllowOrigin> {
+        match reader.collect_to_string().trim() {
+            "*" => Some(AnyOrigin),
+            value => parse_origin(value).map(AllowedOrigin),
+        }
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// The only value of Access-Control-Allow-Credentials: the response may be read by the requesting
+/// origin even though the request was made with credentials (cookies or HTTP authentication).
+#[derive(Clone, PartialEq, Eq, Copy)]
+pub struct AllowCredentials;
+
+impl fmt::Show for AllowCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("true")
+    }
+}
+
+impl super::HeaderConvertible for AllowCredentials {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<AllowCredentials> {
+        // The value is case-sensitive.
+        match &reader.collect_to_string().trim()[] {
+            "true" => Some(AllowCredentials),
+            _ => None,
+        }
+    }
+
+    fn http_value(&self) -> String {
+        String::from_str("true")
+    }
+}
+
+/// A header name, as listed in Access-Control-Request-Headers, Access-Control-Allow-Headers and
+/// Access-Control-Expose-Headers. In the latter two, `*` is a wildcard (except for requests with
+/// credentials). Header names are compared case-insensitively; see `matches`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FieldName(pub String);
+
+impl FieldName {
+    /// Whether this is the same header name as `name`, ignoring case.
+    pub fn matches(&self, name: &str) -> bool {
+        self.0[].eq_ignore_ascii_case(name)
+    }
+}
+
+impl fmt::Show for FieldName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0[])
+    }
+}
+
+impl super::CommaListHeaderConvertible for FieldName {}
+
+impl super::HeaderConvertible for FieldName {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<FieldName> {
+        reader.read_token().map(FieldName)
+    }
+
+    fn to_stream<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        writer.write(self.0.as_bytes())
+    }
+
+    fn http_value(&self) -> String {
+        self.0.clone()
+    }
+}
+
+#[test]
+fn test_origin() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("null", NullOrigin);
+    assert_conversion_correct("https://example.com",
+                              SerializedOrigin(String::from_str("https://example.com")));
+    assert_interpretation_correct("HTTP://LocalHost:8080",
+                                  SerializedOrigin(String::from_str("http://localhost:8080")));
+
+    assert_invalid::<Origin>("");
+    assert_invalid::<Origin>("example.com");
+    assert_invalid::<Origin>("https://");
+    assert_invalid::<Origin>("https://example.com/");
+    assert_invalid::<Origin>("https://example.com https://example.net");
+}
+
+#[test]
+fn test_allow_origin() {
+    use headers::test_utils::{assert_conversion_correct, assert_invalid};
+    assert_conversion_correct("*", AnyOrigin);
+    assert_conversion_correct("null", AllowedOrigin(NullOrigin));
+    assert_conversion_correct("https://example.com",
+        AllowedOrigin(SerializedOrigin(String::from_str("https://example.com"))));
+
+    assert_invalid::<AllowOrigin>("");
+    assert_invalid::<AllowOrigin>("*, https://example.com");
+}
+
+#[test]
+fn test_allow_credentials() {
+    use headers::test_utils::{assert_conversion_correct, assert_invalid};
+    assert_conversion_correct("true", AllowCredentials);
+
+    assert_invalid::<AllowCredentials>("");
+    assert_invalid::<AllowCredentials>("True");
+    assert_invalid::<AllowCredentials>("false");
+}
+
+#[test]
+fn test_field_names() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("X-Requested-With, Content-Type",
+                              vec!(FieldName(String::from_str("X-Requested-With")),
+                                   FieldName(String::from_str("Content-Type"))));
+    assert_interpretation_correct("*,x-foo", vec!(FieldName(String::from_str("*")),
+                                                  FieldName(String::from_str("x-foo"))));
+    assert!(FieldName(String::from_str("Content-Type")).matches("content-type"));
+
+    assert_invalid::<Vec<FieldName>>("");
+    assert_invalid::<Vec<FieldName>>("X-Foo X-Bar");
+}
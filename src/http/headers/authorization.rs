@@ -0,0 +1,585 @@
+//! The Authorization and Proxy-Authorization request headers and the WWW-Authenticate and
+//! Proxy-Authenticate response headers, defined in RFC 7235, sections 2.1 and 4, along with the
+//! Basic (RFC 7617) and Digest (RFC 7616) authentication schemes.
+//!
+//! Authorization    = credentials
+//! WWW-Authenticate = 1#challenge
+//! credentials      = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+//! challenge        = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+//! auth-param       = token BWS "=" BWS ( token / quoted-string )
+//! token68          = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+
+use std::ascii::AsciiExt;
+use std::fmt;
+use rustc_serialize::base64::{ToBase64, FromBase64, STANDARD};
+use rfc2616::{is_token, is_token_item};
+use headers::serialization_utils::quoted_string;
+
+pub use self::AuthData::{NoData, Token68, AuthParams};
+pub use self::DigestAlgorithm::{Md5, Md5Sess, Sha256, Sha256Sess};
+
+/// What follows the scheme in credentials or a challenge.
+#[derive(Clone, PartialEq, Eq)]
+pub enum AuthData {
+    NoData,
+    /// A single token68, as used by Basic and Bearer credentials.
+    Token68(String),
+    /// A list of parameters, with their names in lowercase.
+    AuthParams(Vec<(String, String)>),
+}
+
+impl AuthData {
+    /// The value of a parameter, if there is one by that name (matched case-insensitively).
+    pub fn param<'a>(&'a self, name: &str) -> Option<&'a str> {
+        match *self {
+            AuthParams(ref params) => {
+                params.iter().find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+                             .map(|&(_, ref value)| &value[])
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Show for AuthData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NoData => Ok(()),
+            Token68(ref token) => write!(f, " {}", token),
+            AuthParams(ref params) => {
+                for (i, &(ref name, ref value)) in params.iter().enumerate() {
+                    try!(f.write_str(if i == 0 { " " } else { ", " }));
+                    // Digest requires most of its parameters to be quoted, and quoting is always
+                    // permitted, so only these are sent as tokens.
+                    let unquoted = match &name[] {
+                        "algorithm" | "charset" | "nc" | "qop" | "stale" | "userhash" => true,
+                        _ => false,
+                    };
+                    if unquoted && is_token(value) {
+                        try!(write!(f, "{}={}", name, value));
+                    } else {
+                        try!(write!(f, "{}={}", name, quoted_string(value)));
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// The credentials of an Authorization or Proxy-Authorization header.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    /// The authentication scheme, such as `Basic` or `Digest`. It is compared case-insensitively.
+    pub scheme: String,
+    pub data: AuthData,
+}
+
+impl Credentials {
+    /// Basic credentials (RFC 7617) for a user name and password. Neither may contain control
+    /// characters, and the user name may not contain a colon.
+    pub fn basic(username: &str, password: &str) -> Credentials {
+        Credentials {
+            scheme: String::from_str("Basic"),
+            data: Token68(format!("{}:{}", username, password).as_bytes().to_base64(STANDARD)),
+        }
+    }
+
+    /// Bearer credentials (RFC 6750) for an access token, which must be a valid token68.
+    pub fn bearer(token: &str) -> Credentials {
+        Credentials {
+            scheme: String::from_str("Bearer"),
+            data: Token68(String::from_str(token)),
+        }
+    }
+
+    /// Whether these credentials are of the given scheme (compared case-insensitively).
+    pub fn is_scheme(&self, scheme: &str) -> bool {
+        self.scheme[].eq_ignore_ascii_case(scheme)
+    }
+
+    /// The access token of Bearer credentials.
+    pub fn bearer_token<'a>(&'a self) -> Option<&'a str> {
+        match self.data {
+            Token68(ref token) if self.is_scheme("Bearer") => Some(&token[]),
+            _ => None,
+        }
+    }
+
+    /// The user name and password of Basic credentials, or `None` if these are not well-formed
+    /// Basic credentials.
+    pub fn basic_user_pass(&self) -> Option<(String, String)> {
+        let token = match self.data {
+            Token68(ref token) if self.is_scheme("Basic") => token,
+            _ => return None,
+        };
+        let decoded = match token.from_base64().ok().and_then(|d| String::from_utf8(d).ok()) {
+            Some(decoded) => decoded,
+            None => return None,
+        };
+        let mut parts = decoded.splitn(1, ':');
+        match (parts.next(), parts.next()) {
+            (Some(username), Some(password)) => {
+                Some((String::from_str(username), String::from_str(password)))
+            },
+            _ => None,
+        }
+    }
+
+    /// The value of a parameter (see `AuthData::param`).
+    pub fn param<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.data.param(name)
+    }
+}
+
+impl fmt::Show for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str(&self.scheme[]));
+        self.data.fmt(f)
+    }
+}
+
+impl super::HeaderConvertible for Credentials {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<Credentials> {
+        let value = reader.collect_to_string();
+        let mut parser = Parser { s: value.as_bytes(), pos: 0 };
+        parser.skip_ows();
+        let (scheme, data) = match parser.auth() {
+            Some(auth) => auth,
+            None => return None,
+        };
+        parser.skip_ows();
+        if parser.peek().is_some() {
+            return None;
+        }
+        Some(Credentials { scheme: scheme, data: data })
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// A challenge of a WWW-Authenticate or Proxy-Authenticate header.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// The authentication scheme, such as `Basic` or `Digest`. It is compared case-insensitively.
+    pub scheme: String,
+    pub data: AuthData,
+}
+
+impl Challenge {
+    /// A Basic challenge for the given realm, declaring the UTF-8 charset (RFC 7617, section
+    /// 2.1).
+    pub fn basic(realm: &str) -> Challenge {
+        Challenge {
+            scheme: String::from_str("Basic"),
+            data: AuthParams(vec!((String::from_str("realm"), String::from_str(realm)),
+                                  (String::from_str("charset"), String::from_str("UTF-8")))),
+        }
+    }
+
+    /// Whether this challenge is of the given scheme (compared case-insensitively).
+    pub fn is_scheme(&self, scheme: &str) -> bool {
+        self.scheme[].eq_ignore_ascii_case(scheme)
+    }
+
+    /// The value of a parameter (see `AuthData::param`).
+    pub fn param<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.data.param(name)
+    }
+}
+
+impl fmt::Show for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str(&self.scheme[]));
+        self.data.fmt(f)
+    }
+}
+
+/// The challenges of a WWW-Authenticate or Proxy-Authenticate header, in order.
+///
+/// This cannot simply be a comma-separated list of `Challenge`, because the parameters of a
+/// challenge are separated by commas too.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Challenges(pub Vec<Challenge>);
+
+impl Challenges {
+    /// The first challenge of the given scheme (compared case-insensitively).
+    pub fn find<'a>(&'a self, scheme: &str) -> Option<&'a Challenge> {
+        self.0.iter().find(|challenge| challenge.is_scheme(scheme))
+    }
+}
+
+impl fmt::Show for Challenges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, challenge) in self.0.iter().enumerate() {
+            if i > 0 {
+                try!(f.write_str(", "));
+            }
+            try!(challenge.fmt(f));
+        }
+        Ok(())
+    }
+}
+
+impl super::HeaderConvertible for Challenges {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<Challenges> {
+        let value = reader.collect_to_string();
+        let mut parser = Parser { s: value.as_bytes(), pos: 0 };
+        let mut challenges = Vec::new();
+        loop {
+            parser.skip_empty_elements();
+            if parser.peek().is_none() {
+                break;
+            }
+            match parser.auth() {
+                Some((scheme, data)) => challenges.push(Challenge { scheme: scheme, data: data }),
+                None => return None,
+            }
+            parser.skip_ows();
+            match parser.peek() {
+                None => break,
+                Some(b',') => parser.pos += 1,
+                Some(_) => return None,
+            }
+        }
+        if challenges.is_empty() {
+            None
+        } else {
+            Some(Challenges(challenges))
+        }
+    }
+
+    /// Each line of WWW-Authenticate holds one or more challenges; they are combined.
+    fn merge(&mut self, other: Challenges) -> Result<(), Challenges> {
+        self.0.extend(other.0.into_iter());
+        Ok(())
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+fn is_token68_char(b: u8) -> bool {
+    match b {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' | b'+' | b'/' => true,
+        _ => false,
+    }
+}
+
+/// A parser for credentials and challenges, over a whole header value.
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        if self.pos < self.s.len() { Some(self.s[self.pos]) } else { None }
+    }
+
+    fn skip_ows(&mut self) {
+        while self.peek() == Some(b' ') || self.peek() == Some(b'\t') {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace and the commas of empty list elements, but not the comma before a
+    /// non-empty element: that is left to the caller.
+    fn skip_empty_elements(&mut self) {
+        loop {
+            self.skip_ows();
+            if self.peek() != Some(b',') {
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self.peek().map_or(false, is_token_item) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            None
+        } else {
+            String::from_utf8(self.s[start..self.pos].to_vec()).ok()
+        }
+    }
+
+    fn quoted_string(&mut self) -> Option<String> {
+        let mut output = Vec::new();
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None => return None,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(output).ok();
+                },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b) => output.push(b),
+                        None => return None,
+                    }
+                },
+                Some(b) => output.push(b),
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Whether an auth-param begins here: a token, then `=` and something other than `=`.
+    fn at_param(&self) -> bool {
+        let mut lookahead = Parser { s: self.s, pos: self.pos };
+        if lookahead.token().is_none() {
+            return false;
+        }
+        lookahead.skip_ows();
+        if lookahead.peek() != Some(b'=') {
+            return false;
+        }
+        lookahead.pos += 1;
+        lookahead.skip_ows();
+        match lookahead.peek() {
+            None | Some(b'=') | Some(b',') => false,
+            Some(_) => true,
+        }
+    }
+
+    fn param(&mut self) -> Option<(String, String)> {
+        let name = match self.token() {
+            Some(name) => name.to_ascii_lowercase(),
+            None => return None,
+        };
+        self.skip_ows();
+        self.pos += 1;  // The "=", checked by at_param
+        self.skip_ows();
+        let value = if self.peek() == Some(b'"') { self.quoted_string() } else { self.token() };
+        value.map(|value| (name, value))
+    }
+
+    /// Read the scheme and data of credentials or a challenge, leaving the comma before whatever
+    /// follows unconsumed.
+    fn auth(&mut self) -> Option<(String, AuthData)> {
+        let scheme = match self.token() {
+            Some(scheme) => scheme,
+            None => return None,
+        };
+        let after_scheme = self.pos;
+        self.skip_ows();
+        if self.pos == after_scheme {
+            return Some((scheme, NoData));
+        }
+
+        if self.at_param() {
+            let mut params = Vec::new();
+            loop {
+                match self.param() {
+                    Some(param) => params.push(param),
+                    None => return None,
+                }
+                let before_comma = self.pos;
+                self.skip_ows();
+                if self.peek() != Some(b',') {
+                    self.pos = before_comma;
+                    break;
+                }
+                self.skip_empty_elements();
+                if !self.at_param() {
+                    // What follows is the next challenge.
+                    self.pos = before_comma;
+                    break;
+                }
+            }
+            return Some((scheme, AuthParams(params)));
+        }
+
+        let start = self.pos;
+        while self.peek().map_or(false, is_token68_char) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            self.pos = after_scheme;
+            return Some((scheme, NoData));
+        }
+        while self.peek() == Some(b'=') {
+            self.pos += 1;
+        }
+        String::from_utf8(self.s[start..self.pos].to_vec()).ok()
+                                                           .map(|token| (scheme, Token68(token)))
+    }
+}
+
+/// A hash algorithm of the Digest scheme (RFC 7616, section 3.3).
+#[derive(Clone, PartialEq, Eq, Copy, Show)]
+pub enum DigestAlgorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    /// The algorithm of an `algorithm` parameter (compared case-insensitively); MD5 if there is
+    /// none.
+    pub fn from_param(param: Option<&str>) -> Option<DigestAlgorithm> {
+        match param.map(|name| name.to_ascii_uppercase()) {
+            None => Some(Md5),
+            Some(name) => match &name[] {
+                "MD5" => Some(Md5),
+                "MD5-SESS" => Some(Md5Sess),
+                "SHA-256" => Some(Sha256),
+                "SHA-256-SESS" => Some(Sha256Sess),
+                _ => None,
+            },
+        }
+    }
+
+    /// The name of the algorithm, as used in the `algorithm` parameter.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Md5 => "MD5",
+            Md5Sess => "MD5-sess",
+            Sha256 => "SHA-256",
+            Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    /// Hash a string, giving the lowercase hexadecimal digest.
+    #[cfg(feature = "ssl")]
+    pub fn hash(&self, data: &str) -> String {
+        use openssl::crypto::hash::{hash, HashType};
+        use rustc_serialize::hex::ToHex;
+        let hash_type = match *self {
+            Md5 | Md5Sess => HashType::MD5,
+            Sha256 | Sha256Sess => HashType::SHA256,
+        };
+        hash(hash_type, data.as_bytes()).to_hex()
+    }
+
+    /// The A1 hash of Digest (RFC 7616, section 3.4.2). `cnonce` is only used by the session
+    /// variants.
+    #[cfg(feature = "ssl")]
+    pub fn ha1(&self, username: &str, realm: &str, password: &str, nonce: &str, cnonce: &str)
+               -> String {
+        let ha1 = self.hash(&format!("{}:{}:{}", username, realm, password)[]);
+        match *self {
+            Md5Sess | Sha256Sess => self.hash(&format!("{}:{}:{}", ha1, nonce, cnonce)[]),
+            Md5 | Sha256 => ha1,
+        }
+    }
+
+    /// The `response` parameter of Digest credentials (RFC 7616, section 3.4.1), given the A1
+    /// hash. With `qop` of `Some("auth")`, `nc` and `cnonce` are used; with `None`, the response
+    /// is that of RFC 2069, for servers which offer no qop.
+    #[cfg(feature = "ssl")]
+    pub fn response(&self, ha1: &str, nonce: &str, nc: &str, cnonce: &str, qop: Option<&str>,
+                    method: &str, uri: &str) -> String {
+        let ha2 = self.hash(&format!("{}:{}", method, uri)[]);
+        match qop {
+            Some(qop) => self.hash(&format!("{}:{}:{}:{}:{}:{}",
+                                            ha1, nonce, nc, cnonce, qop, ha2)[]),
+            None => self.hash(&format!("{}:{}:{}", ha1, nonce, ha2)[]),
+        }
+    }
+}
+
+#[test]
+fn test_credentials() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    let basic = Credentials::basic("Aladdin", "open sesame");
+    assert_conversion_correct("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==", basic.clone());
+    assert_eq!(basic.basic_user_pass(),
+               Some((String::from_str("Aladdin"), String::from_str("open sesame"))));
+    assert_interpretation_correct("basic   QWxhZGRpbjpvcGVuIHNlc2FtZQ==", Credentials {
+        scheme: String::from_str("basic"),
+        data: Token68(String::from_str("QWxhZGRpbjpvcGVuIHNlc2FtZQ==")),
+    });
+
+    let digest = Credentials {
+        scheme: String::from_str("Digest"),
+        data: AuthParams(vec!((String::from_str("username"), String::from_str("Mufasa")),
+                              (String::from_str("uri"), String::from_str("/dir/index.html")),
+                              (String::from_str("qop"), String::from_str("auth")),
+                              (String::from_str("nc"), String::from_str("00000001")))),
+    };
+    assert_conversion_correct("Digest username=\"Mufasa\", uri=\"/dir/index.html\", qop=auth, \
+                               nc=00000001", digest.clone());
+    assert_interpretation_correct("Digest Username = Mufasa ,uri=\"/dir/index.html\",, \
+                                   qop=\"auth\", nc=00000001", digest.clone());
+    assert_eq!(digest.param("USERNAME"), Some("Mufasa"));
+    assert_eq!(digest.basic_user_pass(), None);
+    assert_conversion_correct("Bearer mF_9.B5f-4.1JqM", Credentials::bearer("mF_9.B5f-4.1JqM"));
+    assert_eq!(Credentials::bearer("mF_9.B5f-4.1JqM").bearer_token(), Some("mF_9.B5f-4.1JqM"));
+    assert_eq!(basic.bearer_token(), None);
+    assert_conversion_correct("Negotiate", Credentials {
+        scheme: String::from_str("Negotiate"),
+        data: NoData,
+    });
+
+    assert_invalid::<Credentials>("");
+    assert_invalid::<Credentials>("Basic QWxh ZGRp");
+    assert_invalid::<Credentials>("Digest username=\"Mufasa");
+    assert_invalid::<Credentials>("Basic abc, Digest");
+    // U+00AA is encoded as 0xC2 0xAA, both of which are alphabetic if taken as Latin-1.
+    assert_invalid::<Credentials>("Bearer \u{aa}");
+}
+
+#[test]
+fn test_challenges() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    let digest = Challenge {
+        scheme: String::from_str("Digest"),
+        data: AuthParams(vec!((String::from_str("realm"), String::from_str("a, b")),
+                              (String::from_str("qop"), String::from_str("auth, auth-int")),
+                              (String::from_str("algorithm"), String::from_str("SHA-256")))),
+    };
+    let challenges = Challenges(vec!(digest.clone(), Challenge::basic("simple")));
+    assert_conversion_correct("Digest realm=\"a, b\", qop=\"auth, auth-int\", \
+                               algorithm=SHA-256, Basic realm=\"simple\", charset=UTF-8",
+                              challenges.clone());
+    assert_interpretation_correct("Newauth, Digest realm=\"a, b\",qop=\"auth, auth-int\" , \
+                                   algorithm=SHA-256, Bearer abc=, Basic",
+                                  Challenges(vec!(
+        Challenge { scheme: String::from_str("Newauth"), data: NoData },
+        digest.clone(),
+        Challenge { scheme: String::from_str("Bearer"), data: Token68(String::from_str("abc=")) },
+        Challenge { scheme: String::from_str("Basic"), data: NoData })));
+    assert_eq!(challenges.find("basic").and_then(|c| c.param("Realm")), Some("simple"));
+    assert!(challenges.find("Bearer").is_none());
+
+    assert_invalid::<Challenges>("");
+    assert_invalid::<Challenges>(" , ");
+    assert_invalid::<Challenges>("Basic realm=\"x");
+    assert_invalid::<Challenges>("Basic realm=\"x\" Digest");
+}
+
+#[cfg(feature = "ssl")]
+#[test]
+fn test_digest_response() {
+    // The example of RFC 7616, section 3.9.1.
+    let nonce = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+    let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+    let response = |algorithm: DigestAlgorithm| {
+        let ha1 = algorithm.ha1("Mufasa", "http-auth@example.org", "Circle of Life", nonce,
+                                cnonce);
+        algorithm.response(&ha1[], nonce, "00000001", cnonce, Some("auth"), "GET",
+                           "/dir/index.html")
+    };
+    assert_eq!(&response(Md5)[], "8ca523f5e9506fed4657c9700eebdbec");
+    assert_eq!(&response(Sha256)[],
+               "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1");
+
+    assert_eq!(DigestAlgorithm::from_param(None), Some(Md5));
+    assert_eq!(DigestAlgorithm::from_param(Some("sha-256-SESS")), Some(Sha256Sess));
+    assert_eq!(DigestAlgorithm::from_param(Some("SHA-512-256")), None);
+}
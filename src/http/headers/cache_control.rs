@@ -0,0 +1,189 @@
+//! The Cache-Control general header, defined in RFC 7234, Section 5.2.
+//!
+//! Cache-Control   = 1#cache-directive
+//! cache-directive = token [ "=" ( token / quoted-string ) ]
+
+use std::ascii::AsciiExt;
+use std::io::IoResult;
+use headers::DeltaSeconds;
+use headers::serialization_utils::{comma_split_iter, comma_join, push_maybe_quoted_string,
+                                   push_quoted_string, WriterUtil};
+
+pub use self::CacheDirective::{MaxAge, MaxStale, MinFresh, NoCache, NoStore, NoTransform,
+                               OnlyIfCached, MustRevalidate, Public, Private, ProxyRevalidate,
+                               SMaxAge, CacheExtension};
+
+/// A single cache directive. Directives which may only appear in requests and those which may only
+/// appear in responses share this type; it is up to the cache to ignore those which do not apply.
+#[derive(Clone, PartialEq, Eq)]
+pub enum CacheDirective {
+    /// max-age=delta-seconds (request and response)
+    MaxAge(DeltaSeconds),
+    /// max-stale[=delta-seconds] (request); with no value, any staleness is acceptable
+    MaxStale(Option<DeltaSeconds>),
+    /// min-fresh=delta-seconds (request)
+    MinFresh(DeltaSeconds),
+    /// no-cache[="field-names"]; the field names are only permitted in responses
+    NoCache(Vec<String>),
+    /// no-store (request and response)
+    NoStore,
+    /// no-transform (request and response)
+    NoTransform,
+    /// only-if-cached (request)
+    OnlyIfCached,
+    /// must-revalidate (response)
+    MustRevalidate,
+    /// public (response)
+    Public,
+    /// private[="field-names"] (response)
+    Private(Vec<String>),
+    /// proxy-revalidate (response)
+    ProxyRevalidate,
+    /// s-maxage=delta-seconds (response)
+    SMaxAge(DeltaSeconds),
+    /// Any other directive, with its optional argument
+    CacheExtension(String, Option<String>),
+}
+
+impl super::CommaListHeaderConvertible for CacheDirective {}
+
+impl super::HeaderConvertible for CacheDirective {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<CacheDirective> {
+        let name = match reader.read_token() {
+            Some(token) => token.to_ascii_lowercase(),
+            None => return None,
+        };
+        let argument = match reader.next() {
+            Some(b'=') => match reader.read_token_or_quoted_string() {
+                Some(argument) => Some(argument),
+                None => return None,
+            },
+            Some(b) => {
+                reader.next_byte = Some(b);
+                None
+            },
+            None => None,
+        };
+
+        fn seconds(argument: Option<String>) -> Option<DeltaSeconds> {
+            match argument {
+                Some(s) => s.parse(),
+                None => None,
+            }
+        }
+
+        fn field_names(argument: Option<String>) -> Vec<String> {
+            match argument {
+                Some(s) => comma_split_iter(&s[]).map(|name| String::from_str(name.trim()))
+                                                 .filter(|name| name.len() > 0)
+                                                 .collect(),
+                None => Vec::new(),
+            }
+        }
+
+        match &name[] {
+            "max-age" => seconds(argument).map(MaxAge),
+            "max-stale" => match argument {
+                None => Some(MaxStale(None)),
+                argument => seconds(argument).map(|s| MaxStale(Some(s))),
+            },
+            "min-fresh" => seconds(argument).map(MinFresh),
+            "no-cache" => Some(NoCache(field_names(argument))),
+            "no-store" => Some(NoStore),
+            "no-transform" => Some(NoTransform),
+            "only-if-cached" => Some(OnlyIfCached),
+            "must-revalidate" => Some(MustRevalidate),
+            "public" => Some(Public),
+            "private" => Some(Private(field_names(argument))),
+            "proxy-revalidate" => Some(ProxyRevalidate),
+            "s-maxage" => seconds(argument).map(SMaxAge),
+            _ => Some(CacheExtension(name, argument)),
+        }
+    }
+
+    fn to_stream<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        match *self {
+            MaxAge(s) => write!(writer, "max-age={}", s),
+            MaxStale(None) => writer.write(b"max-stale"),
+            MaxStale(Some(s)) => write!(writer, "max-stale={}", s),
+            MinFresh(s) => write!(writer, "min-fresh={}", s),
+            NoCache(ref names) if names.len() == 0 => writer.write(b"no-cache"),
+            NoCache(ref names) => {
+                try!(writer.write(b"no-cache="));
+                writer.write_quoted_string(&comma_join(&names[]))
+            },
+            NoStore => writer.write(b"no-store"),
+            NoTransform => writer.write(b"no-transform"),
+            OnlyIfCached => writer.write(b"only-if-cached"),
+            MustRevalidate => writer.write(b"must-revalidate"),
+            Public => writer.write(b"public"),
+            Private(ref names) if names.len() == 0 => writer.write(b"private"),
+            Private(ref names) => {
+                try!(writer.write(b"private="));
+                writer.write_quoted_string(&comma_join(&names[]))
+            },
+            ProxyRevalidate => writer.write(b"proxy-revalidate"),
+            SMaxAge(s) => write!(writer, "s-maxage={}", s),
+            CacheExtension(ref name, None) => writer.write_token(name),
+            CacheExtension(ref name, Some(ref argument)) => {
+                try!(writer.write_token(name));
+                try!(writer.write(b"="));
+                writer.write_maybe_quoted_string(argument)
+            },
+        }
+    }
+
+    fn http_value(&self) -> String {
+        match *self {
+            MaxAge(s) => format!("max-age={}", s),
+            MaxStale(None) => String::from_str("max-stale"),
+            MaxStale(Some(s)) => format!("max-stale={}", s),
+            MinFresh(s) => format!("min-fresh={}", s),
+            NoCache(ref names) if names.len() == 0 => String::from_str("no-cache"),
+            NoCache(ref names) => push_quoted_string(String::from_str("no-cache="),
+                                                     &comma_join(&names[])),
+            NoStore => String::from_str("no-store"),
+            NoTransform => String::from_str("no-transform"),
+            OnlyIfCached => String::from_str("only-if-cached"),
+            MustRevalidate => String::from_str("must-revalidate"),
+            Public => String::from_str("public"),
+            Private(ref names) if names.len() == 0 => String::from_str("private"),
+            Private(ref names) => push_quoted_string(String::from_str("private="),
+                                                     &comma_join(&names[])),
+            ProxyRevalidate => String::from_str("proxy-revalidate"),
+            SMaxAge(s) => format!("s-maxage={}", s),
+            CacheExtension(ref name, None) => name.clone(),
+            CacheExtension(ref name, Some(ref argument)) => {
+                let mut s = name.clone();
+                s.push('=');
+                push_maybe_quoted_string(s, argument)
+            },
+        }
+    }
+}
+
+#[test]
+fn test_cache_control() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("no-cache", vec!(NoCache(vec!())));
+    assert_conversion_correct("max-age=3600, must-revalidate", vec!(MaxAge(3600), MustRevalidate));
+    assert_conversion_correct("private, s-maxage=0", vec!(Private(vec!()), SMaxAge(0)));
+    assert_conversion_correct("no-cache=\"Set-Cookie, Foo\"",
+                              vec!(NoCache(vec!(String::from_str("Set-Cookie"),
+                                                String::from_str("Foo")))));
+    assert_conversion_correct("max-stale, min-fresh=10", vec!(MaxStale(None), MinFresh(10)));
+    assert_conversion_correct("foo=bar, baz=\"a b\"",
+                              vec!(CacheExtension(String::from_str("foo"),
+                                                  Some(String::from_str("bar"))),
+                                   CacheExtension(String::from_str("baz"),
+                                                  Some(String::from_str("a b")))));
+
+    assert_interpretation_correct("Max-Age=\"60\" ,public", vec!(MaxAge(60), Public));
+    assert_interpretation_correct("NO-STORE", vec!(NoStore));
+
+    assert_invalid::<Vec<CacheDirective>>("max-age");
+    assert_invalid::<Vec<CacheDirective>>("max-age=soon");
+    assert_invalid::<Vec<CacheDirective>>("no-cache no-store");
+}
@@ -0,0 +1,170 @@
+//! The Content-Disposition header, defined in RFC 6266 for responses and in RFC 7578, section 4.2,
+//! for the parts of a `multipart/form-data` body.
+//!
+//! content-disposition = disposition-type *( ";" disposition-parm )
+
+use std::ascii::AsciiExt;
+use std::fmt;
+use url::percent_encoding::percent_decode;
+
+use headers::serialization_utils::push_parameters;
+
+pub use self::DispositionType::{Inline, Attachment, FormData, ExtensionDisposition};
+
+/// How the content is to be presented.
+#[derive(Clone, PartialEq, Eq)]
+pub enum DispositionType {
+    /// Displayed as part of the page (the default, in the absence of the header).
+    Inline,
+    /// Downloaded rather than displayed, usually as a file of the name given.
+    Attachment,
+    /// A field of a `multipart/form-data` body.
+    FormData,
+    ExtensionDisposition(String),
+}
+
+impl fmt::Show for DispositionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Inline => "inline",
+            Attachment => "attachment",
+            FormData => "form-data",
+            ExtensionDisposition(ref s) => &s[],
+        })
+    }
+}
+
+/// A disposition type with its parameters, such as the `filename` of an attachment or the `name`
+/// of a form field.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ContentDisposition {
+    pub disposition: DispositionType,
+    pub parameters: Vec<(String, String)>,
+}
+
+impl ContentDisposition {
+    pub fn new(disposition: DispositionType, parameters: Vec<(String, String)>)
+               -> ContentDisposition {
+        ContentDisposition {
+            disposition: disposition,
+            parameters: parameters,
+        }
+    }
+
+    /// `form-data; name="..."`, with a `filename` if the field is a file.
+    pub fn form_data(name: &str, filename: Option<&str>) -> ContentDisposition {
+        let mut parameters = vec!((String::from_str("name"), String::from_str(name)));
+        match filename {
+            Some(filename) => parameters.push((String::from_str("filename"),
+                                               String::from_str(filename))),
+            None => (),
+        }
+        ContentDisposition::new(FormData, parameters)
+    }
+
+    /// The value of a parameter (matched case-insensitively).
+    pub fn param<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.parameters.iter().find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+                              .map(|&(_, ref v)| &v[])
+    }
+
+    /// The name of a form field.
+    pub fn name<'a>(&'a self) -> Option<&'a str> {
+        self.param("name")
+    }
+
+    /// The file name, preferring the UTF-8 `filename*` parameter of RFC 5987 to `filename`.
+    /// This is only a suggestion from the sender: it may contain path separators or `..`, and
+    /// must not be used as a path without being checked.
+    pub fn filename(&self) -> Option<String> {
+        match self.param("filename*").and_then(decode_ext_value) {
+            Some(filename) => Some(filename),
+            None => self.param("filename").map(|filename| String::from_str(filename)),
+        }
+    }
+}
+
+/// Decode an RFC 5987 ext-value in UTF-8, such as `UTF-8''%e2%82%ac%20rates.txt`. Other
+/// charsets are not supported.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(2, '\'');
+    let charset = parts.next().unwrap();
+    match (parts.next(), parts.next()) {
+        (Some(_language), Some(encoded)) if charset.eq_ignore_ascii_case("utf-8") => {
+            String::from_utf8(percent_decode(encoded.as_bytes())).ok()
+        },
+        _ => None,
+    }
+}
+
+/// The header value iterator reads each byte as a char; quoted file names are commonly sent as raw
+/// UTF-8, so turn them back into bytes and decode them as such.
+fn decode_utf8(value: String) -> String {
+    if value.chars().all(|c| (c as u32) < 0x80) {
+        return value;
+    }
+    let bytes: Vec<u8> = value.chars().map(|c| c as u32 as u8).collect();
+    String::from_utf8_lossy(&bytes[]).into_owned()
+}
+
+impl fmt::Show for ContentDisposition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = format!("{:?}", self.disposition);
+        f.write_str(&push_parameters(s, &self.parameters[])[])
+    }
+}
+
+impl super::HeaderConvertible for ContentDisposition {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<ContentDisposition> {
+        let disposition = match reader.read_token() {
+            Some(token) => match &token.to_ascii_lowercase()[] {
+                "inline" => Inline,
+                "attachment" => Attachment,
+                "form-data" => FormData,
+                _ => ExtensionDisposition(token),
+            },
+            None => return None,
+        };
+        match reader.read_parameters() {
+            Some(parameters) => {
+                let parameters = parameters.into_iter()
+                                           .map(|(k, v)| (k, decode_utf8(v)))
+                                           .collect();
+                reader.some_if_consumed(ContentDisposition::new(disposition, parameters))
+            },
+            None => None,
+        }
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_content_disposition() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid, from_stream_with_str};
+    assert_conversion_correct("attachment", ContentDisposition::new(Attachment, Vec::new()));
+    assert_conversion_correct("form-data;name=upload;filename=\"my photo.jpg\"",
+                              ContentDisposition::form_data("upload", Some("my photo.jpg")));
+    assert_interpretation_correct("Form-Data; name=\"title\"",
+                                  ContentDisposition::form_data("title", None));
+    assert_interpretation_correct("x-custom; a=b",
+        ContentDisposition::new(ExtensionDisposition(String::from_str("x-custom")),
+                                vec!((String::from_str("a"), String::from_str("b")))));
+
+    assert_invalid::<ContentDisposition>("");
+    assert_invalid::<ContentDisposition>("attachment; filename");
+    assert_invalid::<ContentDisposition>("attachment; filename=\"a.txt");
+    assert_invalid::<ContentDisposition>("attachment filename=a.txt");
+
+    let cd: ContentDisposition = from_stream_with_str(
+        "attachment; filename=\"\u{20ac} rates.txt\"").unwrap();
+    assert_eq!(cd.filename(), Some(String::from_str("\u{20ac} rates.txt")));
+    let cd: ContentDisposition = from_stream_with_str(
+        "attachment; filename=\"EUR rates.txt\"; filename*=UTF-8''%e2%82%ac%20rates.txt").unwrap();
+    assert_eq!(cd.filename(), Some(String::from_str("\u{20ac} rates.txt")));
+    assert_eq!(cd.name(), None);
+}
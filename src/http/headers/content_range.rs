@@ -0,0 +1,105 @@
+//! The Content-Range entity header, defined in RFC 7233, section 4.2.
+//!
+//! Content-Range      = byte-content-range / other-content-range
+//! byte-content-range = bytes-unit SP ( byte-range-resp / unsatisfied-range )
+//! byte-range-resp    = byte-range "/" ( complete-length / "*" )
+//! unsatisfied-range  = "*/" complete-length
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+pub use self::ContentRange::{ByteRange, UnsatisfiedRange, OtherContentRange};
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum ContentRange {
+    /// The inclusive positions of the first and last bytes enclosed, and the complete length of
+    /// the representation if it is known
+    ByteRange(u64, u64, Option<u64>),
+    /// Sent with 416 (Range Not Satisfiable): the complete length of the representation
+    UnsatisfiedRange(u64),
+    /// A range in some other unit: the unit, and the unparsed range
+    OtherContentRange(String, String),
+}
+
+impl fmt::Show for ContentRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ByteRange(first, last, Some(length)) => write!(f, "bytes {}-{}/{}", first, last, length),
+            ByteRange(first, last, None) => write!(f, "bytes {}-{}/*", first, last),
+            UnsatisfiedRange(length) => write!(f, "bytes */{}", length),
+            OtherContentRange(ref unit, ref range) => write!(f, "{} {}", unit, range),
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    if s.len() > 0 && s.bytes().all(|b| b >= b'0' && b <= b'9') {
+        s.parse()
+    } else {
+        None
+    }
+}
+
+impl super::HeaderConvertible for ContentRange {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<ContentRange> {
+        let unit = match reader.read_token() {
+            Some(unit) => unit.to_ascii_lowercase(),
+            None => return None,
+        };
+        if !reader.consume_lws() {
+            return None;
+        }
+        let value = reader.collect_to_string();
+        let value = value.trim_right();
+        if &unit[] != "bytes" {
+            return Some(OtherContentRange(unit, String::from_str(value)));
+        }
+
+        let mut parts = value.splitn(1, '/');
+        let range = parts.next().unwrap();
+        let length = match parts.next() {
+            Some("*") => None,
+            Some(length) => match parse_number(length) {
+                Some(length) => Some(length),
+                None => return None,
+            },
+            None => return None,
+        };
+        if range == "*" {
+            return length.map(UnsatisfiedRange);
+        }
+        let mut positions = range.splitn(1, '-');
+        match (positions.next().and_then(parse_number), positions.next().and_then(parse_number)) {
+            (Some(first), Some(last)) if first <= last => match length {
+                Some(length) if last >= length => None,
+                _ => Some(ByteRange(first, last, length)),
+            },
+            _ => None,
+        }
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_content_range() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("bytes 42-1233/1234", ByteRange(42, 1233, Some(1234)));
+    assert_conversion_correct("bytes 42-1233/*", ByteRange(42, 1233, None));
+    assert_conversion_correct("bytes */1234", UnsatisfiedRange(1234));
+    assert_conversion_correct("items 1-5/10", OtherContentRange(String::from_str("items"),
+                                                                String::from_str("1-5/10")));
+    assert_interpretation_correct("BYTES  0-0/1 ", ByteRange(0, 0, Some(1)));
+
+    assert_invalid::<ContentRange>("");
+    assert_invalid::<ContentRange>("bytes");
+    assert_invalid::<ContentRange>("bytes 42-1233");
+    assert_invalid::<ContentRange>("bytes 1233-42/1234");
+    assert_invalid::<ContentRange>("bytes 0-1234/1234");
+    assert_invalid::<ContentRange>("bytes */*");
+    assert_invalid::<ContentRange>("bytes=0-1/2");
+}
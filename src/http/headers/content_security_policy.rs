@@ -0,0 +1,123 @@
+//! The Content-Security-Policy and Content-Security-Policy-Report-Only response headers, defined
+//! in Content Security Policy Level 3, section 3.
+//!
+//! serialized-policy    = serialized-directive *( OWS ";" [ OWS serialized-directive ] )
+//! serialized-directive = directive-name [ RWS directive-value ]
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+/// A content security policy: a list of directives, each with its list of sources (or other
+/// values, such as the URLs of `report-uri`).
+///
+/// The same type serves for both headers; a policy sent as Content-Security-Policy-Report-Only is
+/// not enforced, but violations of it are reported.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ContentSecurityPolicy {
+    /// The directives in order, with their names in lowercase.
+    pub directives: Vec<(String, Vec<String>)>,
+}
+
+impl ContentSecurityPolicy {
+    /// An empty policy, which restricts nothing.
+    pub fn new() -> ContentSecurityPolicy {
+        ContentSecurityPolicy {
+            directives: Vec::new(),
+        }
+    }
+
+    /// The sources of the named directive (matched case-insensitively), if it is present.
+    pub fn get<'a>(&'a self, name: &str) -> Option<&'a [String]> {
+        self.directives.iter().find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+                              .map(|&(_, ref sources)| &sources[])
+    }
+
+    /// Set the sources of a directive, replacing it if it is already present.
+    pub fn set(&mut self, name: &str, sources: Vec<String>) {
+        let name = name.to_ascii_lowercase();
+        match self.directives.iter_mut().find(|&&mut (ref n, _)| *n == name) {
+            Some(&mut (_, ref mut existing)) => {
+                *existing = sources;
+                return;
+            },
+            None => (),
+        }
+        self.directives.push((name, sources));
+    }
+
+    /// Remove a directive (matched case-insensitively).
+    pub fn remove(&mut self, name: &str) {
+        self.directives.retain(|&(ref n, _)| !n[].eq_ignore_ascii_case(name));
+    }
+}
+
+impl fmt::Show for ContentSecurityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(ref name, ref sources)) in self.directives.iter().enumerate() {
+            if i > 0 {
+                try!(f.write_str("; "));
+            }
+            try!(f.write_str(&name[]));
+            for source in sources.iter() {
+                try!(write!(f, " {}", source));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl super::HeaderConvertible for ContentSecurityPolicy {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<ContentSecurityPolicy> {
+        let value = reader.collect_to_string();
+        let mut policy = ContentSecurityPolicy::new();
+        for directive in value.split(';') {
+            let mut tokens = directive.split(|c: char| c == ' ' || c == '\t')
+                                      .filter(|token| !token.is_empty());
+            let name = match tokens.next() {
+                Some(name) => name.to_ascii_lowercase(),
+                None => continue,
+            };
+            if !name.bytes().all(|b| (b as char).is_alphanumeric() || b == b'-') {
+                return None;
+            }
+            // Only the first occurrence of a directive counts (CSP 3, section 2.2.1).
+            if policy.get(&name[]).is_some() {
+                continue;
+            }
+            policy.directives.push((name, tokens.map(|token| String::from_str(token)).collect()));
+        }
+        if policy.directives.is_empty() {
+            None
+        } else {
+            Some(policy)
+        }
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_content_security_policy() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    let mut policy = ContentSecurityPolicy::new();
+    policy.set("default-src", vec!(String::from_str("'self'")));
+    policy.set("img-src", vec!(String::from_str("'self'"), String::from_str("https:")));
+    policy.set("upgrade-insecure-requests", Vec::new());
+    assert_conversion_correct("default-src 'self'; img-src 'self' https:; \
+                               upgrade-insecure-requests", policy.clone());
+    assert_interpretation_correct("Default-Src  'self' ;; img-src 'self'\thttps: ;\
+                                   upgrade-insecure-requests; img-src *", policy.clone());
+    assert_eq!(policy.get("IMG-SRC").map(|sources| sources.len()), Some(2));
+
+    policy.set("default-src", vec!(String::from_str("'none'")));
+    policy.remove("img-src");
+    assert_conversion_correct("default-src 'none'; upgrade-insecure-requests", policy);
+
+    assert_invalid::<ContentSecurityPolicy>("");
+    assert_invalid::<ContentSecurityPolicy>(" ; ");
+    assert_invalid::<ContentSecurityPolicy>("default_src 'self'");
+}
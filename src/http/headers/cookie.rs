@@ -0,0 +1,426 @@
+//! The Cookie request header and Set-Cookie response header, defined in RFC 6265.
+//!
+//! cookie-header     = "Cookie:" OWS cookie-string OWS
+//! cookie-string     = cookie-pair *( ";" SP cookie-pair )
+//! set-cookie-header = "Set-Cookie:" SP set-cookie-string
+//! set-cookie-string = cookie-pair *( ";" SP cookie-av )
+//!
+//! Unlike most headers, Set-Cookie cannot be folded into a single comma-separated line (the
+//! Expires attribute contains a comma), so each cookie set is written on a line of its own. A
+//! cookie whose name, value, Domain or Path could not be sent as they are (see
+//! `SetCookie::is_valid`) is refused with an error rather than written.
+
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::io::{IoError, IoResult, InvalidInput};
+use time::{Tm, strptime};
+use headers::{HeaderConvertible, parse_header_value};
+use rfc2616::is_token;
+
+pub use self::SameSite::{Strict, Lax, NoRestriction};
+
+/// The value of the Cookie header: the name/value pairs of the cookies sent with a request, in the
+/// order they were sent.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub pairs: Vec<(String, String)>,
+}
+
+impl Cookie {
+    /// The value of the first cookie with the given name, if any.
+    pub fn get<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.pairs.iter().find(|&&(ref n, _)| &n[] == name).map(|&(_, ref v)| &v[])
+    }
+}
+
+impl fmt::Show for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(ref name, ref value)) in self.pairs.iter().enumerate() {
+            if i != 0 {
+                try!(f.write_str("; "));
+            }
+            try!(write!(f, "{}={}", name, value));
+        }
+        Ok(())
+    }
+}
+
+/// Split a `name=value` pair, trimming white space around both parts. The name must not be empty.
+fn split_pair(pair: &str) -> Option<(String, String)> {
+    let mut parts = pair.splitn(1, '=');
+    let name = parts.next().unwrap().trim();
+    match parts.next() {
+        Some(value) if !name.is_empty() => Some((String::from_str(name),
+                                                 String::from_str(value.trim()))),
+        _ => None,
+    }
+}
+
+impl HeaderConvertible for Cookie {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<Cookie> {
+        // Be lenient, as browsers are: skip pairs that cannot be understood.
+        let value = reader.collect_to_string();
+        let pairs: Vec<(String, String)> = value.split(';').filter_map(split_pair).collect();
+        if pairs.is_empty() {
+            None
+        } else {
+            Some(Cookie { pairs: pairs })
+        }
+    }
+
+    /// Several Cookie lines (as sent by HTTP/2 intermediaries, for example) are combined.
+    fn merge(&mut self, other: Cookie) -> Result<(), Cookie> {
+        self.pairs.extend(other.pairs.into_iter());
+        Ok(())
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// The value of the SameSite attribute of a cookie.
+#[derive(Clone, PartialEq, Eq, Copy)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// `SameSite=None`
+    NoRestriction,
+}
+
+impl fmt::Show for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Strict => "Strict",
+            Lax => "Lax",
+            NoRestriction => "None",
+        })
+    }
+}
+
+/// A single cookie set by a response.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub expires: Option<Tm>,
+    /// The number of seconds until the cookie expires; zero or less expires it immediately.
+    pub max_age: Option<i64>,
+    /// The domain the cookie is to be sent to, without any leading dot.
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+impl SetCookie {
+    /// A cookie with the given name and value and no attributes, i.e. a session cookie for the
+    /// current host and path.
+    pub fn new(name: String, value: String) -> SetCookie {
+        SetCookie {
+            name: name,
+            value: value,
+            expires: None,
+            max_age: None,
+            domain: None,
+            path: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// Parse a set-cookie-string following the algorithm of RFC 6265, section 5.2: attributes
+    /// that are not understood, or whose values are invalid, are ignored.
+    pub fn parse(value: &str) -> Option<SetCookie> {
+        let mut parts = value.split(';');
+        let (name, value) = match split_pair(parts.next().unwrap()) {
+            Some(pair) => pair,
+            None => return None,
+        };
+        let mut cookie = SetCookie::new(name, value);
+
+        for attribute in parts {
+            let mut attribute = attribute.splitn(1, '=');
+            let name = attribute.next().unwrap().trim().to_ascii_lowercase();
+            let value = attribute.next().map(|value| value.trim()).unwrap_or("");
+            match &name[] {
+                "expires" => match parse_cookie_date(value) {
+                    Some(expires) => cookie.expires = Some(expires),
+                    None => (),
+                },
+                "max-age" => match parse_max_age(value) {
+                    Some(max_age) => cookie.max_age = Some(max_age),
+                    None => (),
+                },
+                "domain" => {
+                    let domain = value.trim_left_matches('.').to_ascii_lowercase();
+                    if !domain.is_empty() {
+                        cookie.domain = Some(domain);
+                    }
+                },
+                "path" => if value.starts_with("/") {
+                    cookie.path = Some(String::from_str(value));
+                },
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match &value.to_ascii_lowercase()[] {
+                        "strict" => Some(Strict),
+                        "lax" => Some(Lax),
+                        "none" => Some(NoRestriction),
+                        _ => cookie.same_site,
+                    };
+                },
+                _ => (),
+            }
+        }
+        Some(cookie)
+    }
+
+    /// Whether the cookie can be sent as it is (RFC 6265, section 4.1.1): the name must be a
+    /// token, the value made of cookie-octets (optionally within double quotes), and the Domain
+    /// and Path free of control characters and semicolons.
+    pub fn is_valid(&self) -> bool {
+        !self.name.is_empty() && is_token(&self.name) && is_cookie_value(&self.value[]) &&
+            self.domain.as_ref().map_or(true, |domain| is_av_value(&domain[])) &&
+            self.path.as_ref().map_or(true, |path| is_av_value(&path[]))
+    }
+}
+
+/// cookie-value = *cookie-octet / ( DQUOTE *cookie-octet DQUOTE )
+fn is_cookie_value(value: &str) -> bool {
+    let value = if value.len() >= 2 && value.starts_with("\"") && value.ends_with("\"") {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    };
+    value.bytes().all(is_cookie_octet)
+}
+
+/// cookie-octet = %x21 / %x23-2B / %x2D-3A / %x3C-5B / %x5D-7E
+fn is_cookie_octet(b: u8) -> bool {
+    match b {
+        0x21 | 0x23...0x2B | 0x2D...0x3A | 0x3C...0x5B | 0x5D...0x7E => true,
+        _ => false,
+    }
+}
+
+/// av-octet = %x20-3A / %x3C-7E, i.e. any CHAR except CTLs or ";"
+fn is_av_value(value: &str) -> bool {
+    value.bytes().all(|b| match b {
+        0x20...0x3A | 0x3C...0x7E => true,
+        _ => false,
+    })
+}
+
+fn parse_cookie_date(value: &str) -> Option<Tm> {
+    match parse_header_value(value) {
+        Some(tm) => Some(tm),
+        // The variant with a four-digit year and dashes that many servers send.
+        None => strptime(value, "%a, %d-%b-%Y %T %Z").ok(),
+    }
+}
+
+fn parse_max_age(value: &str) -> Option<i64> {
+    let digits = if value.starts_with("-") { &value[1..] } else { value };
+    if digits.len() > 0 && digits.bytes().all(|b| b >= b'0' && b <= b'9') {
+        value.parse()
+    } else {
+        None
+    }
+}
+
+impl fmt::Show for SetCookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}={}", self.name, self.value));
+        match self.expires {
+            Some(ref expires) => try!(write!(f, "; Expires={}", expires.http_value())),
+            None => (),
+        }
+        match self.max_age {
+            Some(max_age) => try!(write!(f, "; Max-Age={}", max_age)),
+            None => (),
+        }
+        match self.domain {
+            Some(ref domain) => try!(write!(f, "; Domain={}", domain)),
+            None => (),
+        }
+        match self.path {
+            Some(ref path) => try!(write!(f, "; Path={}", path)),
+            None => (),
+        }
+        if self.secure {
+            try!(f.write_str("; Secure"));
+        }
+        if self.http_only {
+            try!(f.write_str("; HttpOnly"));
+        }
+        match self.same_site {
+            Some(same_site) => write!(f, "; SameSite={:?}", same_site),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The value of the Set-Cookie header: all of the cookies set by a response, one per line.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SetCookies(pub Vec<SetCookie>);
+
+impl fmt::Show for SetCookies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SetCookies(ref cookies) = *self;
+        for (i, cookie) in cookies.iter().enumerate() {
+            if i != 0 {
+                try!(f.write_str(", "));
+            }
+            try!(write!(f, "{:?}", cookie));
+        }
+        Ok(())
+    }
+}
+
+impl HeaderConvertible for SetCookies {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<SetCookies> {
+        SetCookie::parse(&reader.collect_to_string()[]).map(|cookie| SetCookies(vec!(cookie)))
+    }
+
+    fn merge(&mut self, other: SetCookies) -> Result<(), SetCookies> {
+        let SetCookies(other) = other;
+        self.0.extend(other.into_iter());
+        Ok(())
+    }
+
+    /// Fails with `InvalidInput`, writing nothing, if any of the cookies is not valid.
+    fn write_header_lines<W: Writer>(&self, name: &str, writer: &mut W) -> IoResult<()> {
+        match self.0.iter().find(|cookie| !cookie.is_valid()) {
+            Some(cookie) => return Err(IoError {
+                kind: InvalidInput,
+                desc: "invalid Set-Cookie name, value, Domain or Path",
+                detail: Some(format!("cookie {:?}", cookie.name)),
+            }),
+            None => (),
+        }
+        for cookie in self.0.iter() {
+            try!(write!(&mut *writer as &mut Writer, "{}: {:?}\r\n", name, cookie));
+        }
+        Ok(())
+    }
+
+    /// All of the cookies joined with commas. This is for display only: as Expires dates contain
+    /// commas, the value cannot be parsed again; on the wire each cookie has its own line.
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_cookie() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    fn pair(name: &str, value: &str) -> (String, String) {
+        (String::from_str(name), String::from_str(value))
+    }
+    assert_conversion_correct("SID=31d4d96e407aad42",
+                              Cookie { pairs: vec!(pair("SID", "31d4d96e407aad42")) });
+    assert_conversion_correct("SID=31d4d96e407aad42; lang=en-US",
+                              Cookie { pairs: vec!(pair("SID", "31d4d96e407aad42"),
+                                                   pair("lang", "en-US")) });
+    assert_interpretation_correct("a=1;b = 2 ; junk; c=", Cookie { pairs: vec!(pair("a", "1"),
+                                                                                pair("b", "2"),
+                                                                                pair("c", "")) });
+    assert_invalid::<Cookie>("");
+    assert_invalid::<Cookie>("junk; =1");
+
+    let cookie = Cookie { pairs: vec!(pair("a", "1"), pair("b", "2"), pair("a", "3")) };
+    assert_eq!(cookie.get("a"), Some("1"));
+    assert_eq!(cookie.get("c"), None);
+}
+
+#[test]
+fn test_set_cookie() {
+    use time;
+    use headers::test_utils::{assert_interpretation_correct, assert_invalid};
+
+    let mut cookie = SetCookie::new(String::from_str("SID"), String::from_str("31d4d96e407aad42"));
+    assert_interpretation_correct("SID=31d4d96e407aad42", SetCookies(vec!(cookie.clone())));
+
+    cookie.path = Some(String::from_str("/"));
+    cookie.domain = Some(String::from_str("example.com"));
+    cookie.secure = true;
+    cookie.http_only = true;
+    assert_interpretation_correct("SID=31d4d96e407aad42; Path=/; Domain=.EXAMPLE.com; Secure; \
+                                   HttpOnly", SetCookies(vec!(cookie.clone())));
+    assert_eq!(format!("{:?}", cookie),
+               String::from_str("SID=31d4d96e407aad42; Domain=example.com; Path=/; Secure; \
+                                 HttpOnly"));
+
+    let mut cookie = SetCookie::new(String::from_str("lang"), String::from_str("en-US"));
+    cookie.expires = Some(time::at_utc(time::Timespec::new(1445412480, 0)));
+    cookie.max_age = Some(-1);
+    cookie.same_site = Some(Lax);
+    let value = "lang=en-US; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=-1; SameSite=Lax";
+    assert_eq!(SetCookie::parse(value).map(|c| c.expires.unwrap().to_timespec()),
+               Some(cookie.expires.unwrap().to_timespec()));
+    assert_eq!(format!("{:?}", cookie), String::from_str(value));
+
+    // Invalid attributes are ignored.
+    let cookie = SetCookie::parse("a=b; Max-Age=soon; Path=relative; SameSite=Sometimes; Foo")
+                           .unwrap();
+    assert_eq!(cookie, SetCookie::new(String::from_str("a"), String::from_str("b")));
+
+    assert_invalid::<SetCookies>("");
+    assert_invalid::<SetCookies>("novalue; Path=/");
+    assert_invalid::<SetCookies>("=b");
+}
+
+#[test]
+fn test_set_cookie_lines() {
+    use std::io::MemWriter;
+    let cookies = SetCookies(vec!(SetCookie::new(String::from_str("a"), String::from_str("1")),
+                                  SetCookie::new(String::from_str("b"), String::from_str("2"))));
+    let mut writer = MemWriter::new();
+    cookies.write_header_lines("Set-Cookie", &mut writer).unwrap();
+    assert_eq!(&writer.get_ref()[], b"Set-Cookie: a=1\r\nSet-Cookie: b=2\r\n");
+
+    let mut merged = SetCookies(vec!(SetCookie::new(String::from_str("a"), String::from_str("1"))));
+    assert!(merged.merge(SetCookies(vec!(SetCookie::new(String::from_str("b"),
+                                                        String::from_str("2"))))).is_ok());
+    assert_eq!(merged, cookies);
+}
+
+#[test]
+fn test_set_cookie_validity() {
+    use std::io::{MemWriter, InvalidInput};
+    let cookie = |name: &str, value: &str| SetCookie::new(String::from_str(name),
+                                                          String::from_str(value));
+    assert!(cookie("SID", "31d4d96e407aad42").is_valid());
+    assert!(cookie("a", "").is_valid());
+    assert!(cookie("a", "\"quoted\"").is_valid());
+    assert!(!cookie("", "1").is_valid());
+    assert!(!cookie("a b", "1").is_valid());
+    assert!(!cookie("a=b", "1").is_valid());
+    assert!(!cookie("a", "1; Domain=evil.com").is_valid());
+    assert!(!cookie("a", "x y").is_valid());
+    assert!(!cookie("a", "x,y").is_valid());
+    assert!(!cookie("a", "\"").is_valid());
+    assert!(!cookie("a", "x\r\nLocation: /").is_valid());
+    assert!(!cookie("a", "caf\u{e9}").is_valid());
+
+    let mut with_path = cookie("a", "1");
+    with_path.path = Some(String::from_str("/a b"));
+    assert!(with_path.is_valid());
+    with_path.path = Some(String::from_str("/; Secure"));
+    assert!(!with_path.is_valid());
+    let mut with_domain = cookie("a", "1");
+    with_domain.domain = Some(String::from_str("example.com\r\nX: y"));
+    assert!(!with_domain.is_valid());
+
+    let mut writer = MemWriter::new();
+    let cookies = SetCookies(vec!(cookie("a", "1"), cookie("b", "x;y")));
+    assert_eq!(cookies.write_header_lines("Set-Cookie", &mut writer).unwrap_err().kind,
+               InvalidInput);
+    assert!(writer.get_ref().is_empty());
+}
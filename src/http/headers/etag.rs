@@ -1,13 +1,34 @@
+//! The ETag response header and the entity-tag lists of the If-Match and If-None-Match request
+//! headers, defined in RFC 7232, sections 2.3, 3.1 and 3.2.
+
+use headers::HeaderConvertible;
 use headers::serialization_utils::{push_quoted_string, quoted_string, WriterUtil};
+use headers::ConsumeCommaLWSResult::{CommaConsumed, EndOfValue, ErrCommaNotFound};
 use std::io::IoResult;
 use std::fmt;
 
+pub use self::EntityTagMatch::{AnyEntityTag, EntityTags};
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct EntityTag {
     pub weak: bool,
     pub opaque_tag: String,
 }
 
+impl EntityTag {
+    /// Strong comparison, as defined in RFC 7232, section 2.3.2: both entity-tags must be strong
+    /// and their opaque tags identical. This is the comparison used for If-Match and If-Range.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.opaque_tag == other.opaque_tag
+    }
+
+    /// Weak comparison, as defined in RFC 7232, section 2.3.2: the opaque tags must be identical,
+    /// whether or not either entity-tag is weak. This is the comparison used for If-None-Match.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.opaque_tag == other.opaque_tag
+    }
+}
+
 pub fn weak_etag(opaque_tag: String) -> EntityTag {
     EntityTag {
         weak: true,
@@ -32,30 +53,38 @@ impl fmt::Show for EntityTag {
     }
 }
 
-impl super::HeaderConvertible for EntityTag {
-    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<EntityTag> {
-        let weak = match reader.next() {
-            Some(b) if b == b'W' || b == b'w' => {
-                if reader.next() != Some(b'/') || reader.next() != Some(b'"') {
-                    return None;
-                }
-                true
-            },
-            Some(b) if b == b'"' => {
-                false
-            },
-            _ => {
+/// Read an entity-tag, leaving anything following it (such as a comma) unconsumed.
+fn read_entity_tag<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<EntityTag> {
+    let weak = match reader.next() {
+        Some(b) if b == b'W' || b == b'w' => {
+            if reader.next() != Some(b'/') || reader.next() != Some(b'"') {
                 return None;
             }
-        };
-        let opaque_tag = match reader.read_quoted_string(true) {
-            Some(tag) => tag,
-            None => return None,
-        };
-        reader.some_if_consumed(EntityTag {
-            weak: weak,
-            opaque_tag: opaque_tag,
-        })
+            true
+        },
+        Some(b) if b == b'"' => {
+            false
+        },
+        _ => {
+            return None;
+        }
+    };
+    let opaque_tag = match reader.read_quoted_string(true) {
+        Some(tag) => tag,
+        None => return None,
+    };
+    Some(EntityTag {
+        weak: weak,
+        opaque_tag: opaque_tag,
+    })
+}
+
+impl super::HeaderConvertible for EntityTag {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<EntityTag> {
+        match read_entity_tag(reader) {
+            Some(etag) => reader.some_if_consumed(etag),
+            None => None,
+        }
     }
 
     fn to_stream<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
@@ -70,6 +99,100 @@ impl super::HeaderConvertible for EntityTag {
     }
 }
 
+/// The value of the If-Match and If-None-Match request headers (RFC 7232, sections 3.1 and 3.2):
+///
+/// If-Match = "*" / 1#entity-tag
+#[derive(Clone, PartialEq, Eq)]
+pub enum EntityTagMatch {
+    /// "*", matching any current representation of the resource
+    AnyEntityTag,
+    /// A list of entity-tags
+    EntityTags(Vec<EntityTag>),
+}
+
+impl EntityTagMatch {
+    /// Whether the current entity-tag of an existing resource matches, using strong comparison
+    /// as If-Match requires.
+    pub fn matches_strongly(&self, current: Option<&EntityTag>) -> bool {
+        match (self, current) {
+            (&AnyEntityTag, _) => true,
+            (&EntityTags(ref tags), Some(current)) => tags.iter().any(|t| t.strong_eq(current)),
+            (&EntityTags(_), None) => false,
+        }
+    }
+
+    /// Whether the current entity-tag of an existing resource matches, using weak comparison
+    /// as If-None-Match requires.
+    pub fn matches_weakly(&self, current: Option<&EntityTag>) -> bool {
+        match (self, current) {
+            (&AnyEntityTag, _) => true,
+            (&EntityTags(ref tags), Some(current)) => tags.iter().any(|t| t.weak_eq(current)),
+            (&EntityTags(_), None) => false,
+        }
+    }
+}
+
+impl fmt::Show for EntityTagMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.http_value()[])
+    }
+}
+
+impl super::HeaderConvertible for EntityTagMatch {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<EntityTagMatch> {
+        match reader.next() {
+            Some(b'*') => return reader.some_if_consumed(AnyEntityTag),
+            Some(b) => reader.next_byte = Some(b),
+            None => return None,
+        }
+        let mut tags = Vec::new();
+        loop {
+            match read_entity_tag(reader) {
+                Some(tag) => tags.push(tag),
+                None => return None,
+            }
+            match reader.consume_comma_lws() {
+                CommaConsumed => continue,
+                EndOfValue => break,
+                ErrCommaNotFound => return None,
+            }
+        }
+        Some(EntityTags(tags))
+    }
+
+    fn to_stream<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        match *self {
+            AnyEntityTag => writer.write(b"*"),
+            EntityTags(ref tags) => {
+                for (i, tag) in tags.iter().enumerate() {
+                    if i != 0 {
+                        try!(writer.write(b", "));
+                    }
+                    try!(tag.to_stream(writer));
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn http_value(&self) -> String {
+        match *self {
+            AnyEntityTag => String::from_str("*"),
+            EntityTags(ref tags) => {
+                let mut out = String::new();
+                for (i, tag) in tags.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
+                    }
+                    out.push_str(&tag.http_value()[]);
+                }
+                out
+            },
+        }
+    }
+}
+
 #[test]
 fn test_etag() {
     use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
@@ -92,3 +215,45 @@ fn test_etag() {
     assert_invalid::<EntityTag>("\"\\\"");
     assert_invalid::<EntityTag>("\"\"\"\"");
 }
+
+#[test]
+fn test_etag_comparison() {
+    // The examples from RFC 7232, section 2.3.2
+    let cases = [(weak_etag(String::from_str("1")), weak_etag(String::from_str("1")), false, true),
+                 (weak_etag(String::from_str("1")), weak_etag(String::from_str("2")), false, false),
+                 (weak_etag(String::from_str("1")), strong_etag(String::from_str("1")), false, true),
+                 (strong_etag(String::from_str("1")), strong_etag(String::from_str("1")), true, true)];
+    for &(ref a, ref b, strong, weak) in cases.iter() {
+        assert_eq!(a.strong_eq(b), strong);
+        assert_eq!(b.strong_eq(a), strong);
+        assert_eq!(a.weak_eq(b), weak);
+        assert_eq!(b.weak_eq(a), weak);
+    }
+}
+
+#[test]
+fn test_entity_tag_match() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("*", AnyEntityTag);
+    assert_conversion_correct("\"xyzzy\"", EntityTags(vec!(strong_etag(String::from_str("xyzzy")))));
+    assert_conversion_correct("\"xyzzy\", W/\"r2d2xxxx\", \"c3piozzzz\"",
+                              EntityTags(vec!(strong_etag(String::from_str("xyzzy")),
+                                              weak_etag(String::from_str("r2d2xxxx")),
+                                              strong_etag(String::from_str("c3piozzzz")))));
+    assert_interpretation_correct("\"a\",W/\"b\"",
+                                  EntityTags(vec!(strong_etag(String::from_str("a")),
+                                                  weak_etag(String::from_str("b")))));
+
+    assert_invalid::<EntityTagMatch>("");
+    assert_invalid::<EntityTagMatch>("* \"a\"");
+    assert_invalid::<EntityTagMatch>("\"a\" \"b\"");
+    assert_invalid::<EntityTagMatch>("xyzzy");
+
+    let tags = EntityTags(vec!(weak_etag(String::from_str("a")), strong_etag(String::from_str("b"))));
+    assert!(tags.matches_strongly(Some(&strong_etag(String::from_str("b")))));
+    assert!(!tags.matches_strongly(Some(&strong_etag(String::from_str("a")))));
+    assert!(tags.matches_weakly(Some(&strong_etag(String::from_str("a")))));
+    assert!(!tags.matches_weakly(None));
+    assert!(AnyEntityTag.matches_strongly(None));
+}
@@ -1,14 +1,134 @@
-//! The Host request header, defined in RFC 2616, Section 14.23.
+//! The Host request header, defined in RFC 7230, Section 5.4.
+//!
+//! Host = uri-host [ ":" port ]
+//!
+//! where uri-host is a registered name, an IPv4 address or an IPv6 address in brackets (RFC 3986,
+//! section 3.2.2).
 
+use std::ascii::AsciiExt;
 use std::io::Reader;
+use std::io::net::ip::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::fmt;
 
+pub use self::HostName::{Domain, Ip};
+
+/// The host part of an authority.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum HostName {
+    /// A registered name, such as `example.com`, in lower case; host names are case-insensitive,
+    /// so two names may be compared directly. It may be empty, as in the Host header of a request
+    /// for a URI which has no authority.
+    Domain(String),
+    /// An IPv4 address, such as `192.0.2.1`, or an IPv6 address, written in brackets, such as
+    /// `[2001:db8::1]`.
+    Ip(IpAddr),
+}
+
+impl HostName {
+    /// Parse a uri-host, returning `None` if it contains characters not permitted in a registered
+    /// name or is not a valid IP literal. IP literals other than IPv6 addresses (IPvFuture) are
+    /// not supported.
+    pub fn parse(s: &str) -> Option<HostName> {
+        if s.starts_with("[") {
+            if !s.ends_with("]") {
+                return None;
+            }
+            return match s[1..s.len() - 1].parse() {
+                Some(ip @ Ipv6Addr(..)) => Some(Ip(ip)),
+                _ => None,
+            };
+        }
+        let mut bytes = s.bytes();
+        while let Some(b) = bytes.next() {
+            match b {
+                // unreserved / sub-delims
+                b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' |
+                b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => (),
+                // pct-encoded
+                b'%' => match (bytes.next(), bytes.next()) {
+                    (Some(a), Some(b)) if is_hex_digit(a) && is_hex_digit(b) => (),
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        match s.parse() {
+            Some(ip @ Ipv4Addr(..)) => Some(Ip(ip)),
+            _ => Some(Domain(s.to_ascii_lowercase())),
+        }
+    }
+
+    /// Whether this is an IP address rather than a registered name.
+    pub fn is_ip(&self) -> bool {
+        match *self {
+            Ip(_) => true,
+            Domain(_) => false,
+        }
+    }
+}
+
+fn is_hex_digit(b: u8) -> bool {
+    match b {
+        b'0'...b'9' | b'a'...b'f' | b'A'...b'F' => true,
+        _ => false,
+    }
+}
+
+/// Write an IPv6 address in the canonical form of RFC 5952: lower-case hexadecimal, with the
+/// longest run of two or more zero groups (the first, if there is a tie) replaced by `::`.
+fn write_ipv6(f: &mut fmt::Formatter, groups: &[u16; 8]) -> fmt::Result {
+    let (mut best_start, mut best_len) = (0, 0);
+    let mut i = 0;
+    while i < 8 {
+        let start = i;
+        while i < 8 && groups[i] == 0 {
+            i += 1;
+        }
+        if i - start > best_len {
+            best_start = start;
+            best_len = i - start;
+        }
+        i += 1;
+    }
+    if best_len < 2 {
+        best_len = 0;
+    }
+    let mut i = 0;
+    while i < 8 {
+        if best_len > 0 && i == best_start {
+            try!(f.write_str(if i == 0 { "::" } else { ":" }));
+            i += best_len;
+            continue;
+        }
+        try!(write!(f, "{:x}", groups[i]));
+        if i < 7 {
+            try!(f.write_str(":"));
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+impl fmt::Show for HostName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Domain(ref name) => f.write_str(&name[]),
+            Ip(Ipv4Addr(a, b, c, d)) => write!(f, "{}.{}.{}.{}", a, b, c, d),
+            Ip(Ipv6Addr(a, b, c, d, e, g, h, i)) => {
+                try!(f.write_str("["));
+                try!(write_ipv6(f, &[a, b, c, d, e, g, h, i]));
+                f.write_str("]")
+            },
+        }
+    }
+}
+
 /// A simple little thing for the host of a request
 #[derive(Clone, PartialEq, Eq)]
 pub struct Host {
 
     /// The name of the host that was requested
-    pub name: String,
+    pub name: HostName,
 
     /// If unspecified, assume the default port was used (80 for HTTP, 443 for HTTPS).
     /// In that case, you shouldn't need to worry about it in URLs that you build, provided you
@@ -16,11 +136,46 @@ pub struct Host {
     pub port: Option<u16>,
 }
 
+impl Host {
+    /// Parse a uri-host with an optional port, as in the Host header, returning `None` if either
+    /// is invalid. An empty port (`example.com:`) is the same as none.
+    pub fn parse(s: &str) -> Option<Host> {
+        // The port follows the last colon, unless that colon is inside an IPv6 literal.
+        let (name, port) = match s.rfind(':') {
+            Some(colon) if !s[colon..].contains("]") => (&s[..colon], &s[colon + 1..]),
+            _ => (s, ""),
+        };
+        let port = if port.is_empty() {
+            None
+        } else if port.bytes().all(|b| b >= b'0' && b <= b'9') {
+            match port.parse() {
+                Some(port) => Some(port),
+                None => return None,
+            }
+        } else {
+            return None;
+        };
+        HostName::parse(name).map(|name| Host {
+            name: name,
+            port: port,
+        })
+    }
+
+    /// Parse an authority of the form `host[:port]`, as in the request target of CONNECT, where
+    /// unlike in the Host header the host may not be empty.
+    pub fn from_authority(authority: &str) -> Option<Host> {
+        match Host::parse(authority) {
+            Some(Host { name: Domain(ref name), .. }) if name.is_empty() => None,
+            host => host,
+        }
+    }
+}
+
 impl fmt::Show for Host {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.port {
-            Some(port) => write!(f, "{}:{}", self.name, port),
-            None => f.write_str(&self.name[]),
+            Some(port) => write!(f, "{:?}:{}", self.name, port),
+            None => write!(f, "{:?}", self.name),
         }
     }
 }
@@ -28,19 +183,60 @@ impl fmt::Show for Host {
 impl super::HeaderConvertible for Host {
     fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<Host> {
         let s = reader.collect_to_string();
-        // TODO: this doesn't support IPv6 address access (e.g. "[::1]")
-        // Do this properly with correct authority parsing.
-        let mut hi = s.splitn(1, ':');
-        Some(Host {
-            name: String::from_str(hi.next().unwrap()),
-            port: match hi.next() {
-                Some(name) => name.parse(),
-                None => None,
-            },
-        })
+        Host::parse(s.trim())
     }
 
     fn http_value(&self) -> String {
         format!("{:?}", self)
     }
 }
+
+#[test]
+fn test_host() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    let host = |name: HostName, port: Option<u16>| Host { name: name, port: port };
+    assert_conversion_correct("example.com", host(Domain(String::from_str("example.com")), None));
+    assert_conversion_correct("example.com:8080",
+                              host(Domain(String::from_str("example.com")), Some(8080)));
+    assert_conversion_correct("192.0.2.1:80", host(Ip(Ipv4Addr(192, 0, 2, 1)), Some(80)));
+    assert_conversion_correct("[2001:db8::1]",
+                              host(Ip(Ipv6Addr(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)), None));
+    assert_conversion_correct("[::1]:8080", host(Ip(Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 1)), Some(8080)));
+    assert_conversion_correct("", host(Domain(String::new()), None));
+    assert_interpretation_correct("Example.COM", host(Domain(String::from_str("example.com")),
+                                                      None));
+    assert_interpretation_correct("example.com:", host(Domain(String::from_str("example.com")),
+                                                       None));
+    assert_interpretation_correct("caf%C3%A9.example",
+                                  host(Domain(String::from_str("caf%c3%a9.example")), None));
+
+    assert_invalid::<Host>("example.com:http");
+    assert_invalid::<Host>("example.com:65536");
+    assert_invalid::<Host>("example.com:-1");
+    assert_invalid::<Host>("exa mple.com");
+    assert_invalid::<Host>("example.com/");
+    assert_invalid::<Host>("user@example.com");
+    assert_invalid::<Host>("%zz.example");
+    assert_interpretation_correct("[2001:DB8:0:0:1:0:0:1]",
+                                  host(Ip(Ipv6Addr(0x2001, 0xdb8, 0, 0, 1, 0, 0, 1)), None));
+    assert_conversion_correct("[2001:db8::1:0:0:1]",
+                              host(Ip(Ipv6Addr(0x2001, 0xdb8, 0, 0, 1, 0, 0, 1)), None));
+    assert_conversion_correct("[2001:db8:0:1:1:1:1:1]",
+                              host(Ip(Ipv6Addr(0x2001, 0xdb8, 0, 1, 1, 1, 1, 1)), None));
+    assert_conversion_correct("[::]", host(Ip(Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 0)), None));
+    assert_conversion_correct("[1::]", host(Ip(Ipv6Addr(1, 0, 0, 0, 0, 0, 0, 0)), None));
+
+    assert_invalid::<Host>("::1");
+    assert_invalid::<Host>("[::1");
+    assert_invalid::<Host>("[192.0.2.1]");
+    assert_invalid::<Host>("[v1.fe]");
+}
+
+#[test]
+fn test_from_authority() {
+    assert_eq!(Host::from_authority("example.com:443"),
+               Some(Host { name: Domain(String::from_str("example.com")), port: Some(443) }));
+    assert_eq!(Host::from_authority(""), None);
+    assert_eq!(Host::from_authority(":443"), None);
+}
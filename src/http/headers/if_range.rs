@@ -0,0 +1,55 @@
+//! The If-Range request header, defined in RFC 7233, section 3.2.
+//!
+//! If-Range = entity-tag / HTTP-date
+
+use std::fmt;
+use time::Tm;
+use headers::etag::EntityTag;
+use headers::{HeaderConvertible, parse_header_value};
+
+pub use self::IfRange::{EntityTagRange, DateRange};
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum IfRange {
+    EntityTagRange(EntityTag),
+    DateRange(Tm),
+}
+
+impl fmt::Show for IfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.http_value()[])
+    }
+}
+
+impl super::HeaderConvertible for IfRange {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<IfRange> {
+        // A weekday can begin with a "W" as well, so we must look further than the first byte.
+        let value = reader.collect_to_string();
+        if value.starts_with("\"") || value.starts_with("W/") || value.starts_with("w/") {
+            parse_header_value(&value[]).map(EntityTagRange)
+        } else {
+            parse_header_value(&value[]).map(DateRange)
+        }
+    }
+
+    fn http_value(&self) -> String {
+        match *self {
+            EntityTagRange(ref etag) => etag.http_value(),
+            DateRange(ref tm) => tm.http_value(),
+        }
+    }
+}
+
+#[test]
+fn test_if_range() {
+    use headers::etag::{strong_etag, weak_etag};
+    use headers::test_utils::{assert_conversion_correct, assert_invalid};
+    assert_conversion_correct("\"xyzzy\"", EntityTagRange(strong_etag(String::from_str("xyzzy"))));
+    assert_conversion_correct("W/\"xyzzy\"", EntityTagRange(weak_etag(String::from_str("xyzzy"))));
+    assert_conversion_correct("Wed, 21 Oct 2015 07:28:00 GMT",
+                              DateRange(parse_header_value("Wed, 21 Oct 2015 07:28:00 GMT").unwrap()));
+
+    assert_invalid::<IfRange>("");
+    assert_invalid::<IfRange>("xyzzy");
+    assert_invalid::<IfRange>("\"xyzzy\" \"plugh\"");
+}
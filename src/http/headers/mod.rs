@@ -2,10 +2,15 @@
 //!
 //! This HTTP system is Special in that it uses lots of strong typing for its header system. All
 //! known HTTP headers are type checked, rather than being dealt with as strings all the time. Only
-//! unknown headers are stored in a map in the traditional way.
+//! unknown headers are stored in a map in the traditional way; applications may still read and
+//! write those as their own types through `TypedHeader`.
 
 use url::Url;
-use std::io::IoResult;
+use std::any::Any;
+use std::ascii::AsciiExt;
+use std::cell::RefCell;
+use std::io::{BufReader, IoResult};
+use std::slice::Iter;
 use time::{Tm, strptime};
 use rfc2616::{is_token_item, is_separator, CR, LF, SP, HT, COLON};
 use method::Method;
@@ -34,20 +39,15 @@ From https://en.wikipedia.org/wiki/List_of_HTTP_headers:
 
 - Known missing headers:
 
-  - Content-Disposition
   - Link
   - P3P
   - Refresh
-  - Set-Cookie
   - Status
-  - Strict-Transport-Security
 
 - Common non-standard headers:
 
-  - X-Frame-Options
   - X-XSS-Protection
-  - Content-Security-Policy, X-Content-Security-Policy, X-WebKit-CSP
-  - X-Content-Type-Options
+  - X-Content-Security-Policy, X-WebKit-CSP
   - X-Powered-By
   - X-UA-Compatible
 
@@ -61,14 +61,26 @@ be the more canonical source.
 //pub mod accept_encoding;
 //pub mod accept_language;
 pub mod accept_ranges;
-//pub mod cache_control;
+pub mod access_control;
+pub mod authorization;
+pub mod cache_control;
 pub mod connection;
 //pub mod content_encoding;
-//pub mod content_range;
+pub mod content_disposition;
+pub mod content_range;
+pub mod content_security_policy;
 pub mod content_type;
+pub mod cookie;
 pub mod etag;
 pub mod host;
+pub mod if_range;
+pub mod range;
+pub mod referrer_policy;
+pub mod retry_after;
+pub mod strict_transport_security;
 pub mod transfer_encoding;
+pub mod x_content_type_options;
+pub mod x_frame_options;
 
 pub type DeltaSeconds = u64;
 
@@ -101,9 +113,61 @@ pub trait HeaderEnum {
         -> Option<Self>;
 }
 
+/// A header line exactly as it was received, for when the parsed headers are not enough: proxies
+/// which must forward headers faithfully, or logging of headers which could not be parsed.
+#[derive(Clone, PartialEq, Eq, Show)]
+pub struct RawHeader {
+    /// The name of the header, in its original case.
+    pub name: String,
+    /// The bytes of the value: everything after the colon up to the end of the line, including
+    /// any leading white space and obsolete line folding (`CR LF SP`), but excluding the final
+    /// `CR LF`.
+    pub value: Vec<u8>,
+    /// Whether the value could not be parsed as a value of the header, such that the header was
+    /// left out of the collection.
+    pub malformed: bool,
+}
+
+impl RawHeader {
+    /// Write the header line, byte for byte as it was received (bar the line ending, which is
+    /// always `CR LF`).
+    pub fn write_line<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        try!(writer.write(self.name.as_bytes()));
+        try!(writer.write(b":"));
+        try!(writer.write(&self.value[]));
+        writer.write(b"\r\n")
+    }
+}
+
+/// A reader that keeps a copy of everything read through it.
+struct RecordingReader<'a, R: 'a> {
+    reader: &'a mut R,
+    recording: bool,
+    bytes: Vec<u8>,
+}
+
+impl<'a, R: Reader> Reader for RecordingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let read = try!(self.reader.read(buf));
+        if self.recording {
+            self.bytes.push_all(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
 /// Shifted out of being a default method to fix an ICE (not yet reported, TODO)
 pub fn header_enum_from_stream<R: Reader, E: HeaderEnum>(reader: &mut R)
         -> (Result<E, HeaderLineErr>, Option<u8>) {
+    header_enum_from_stream_recording(reader, None)
+}
+
+/// As `header_enum_from_stream`, but if `raw` is given, the header line is also appended to it as
+/// a `RawHeader`, whether or not its value is valid. A line with malformed syntax is then read to
+/// its end and recorded as well, split at the first colon if it has one.
+pub fn header_enum_from_stream_recording<R: Reader, E: HeaderEnum>(
+        reader: &mut R, raw: Option<&mut Vec<RawHeader>>)
+        -> (Result<E, HeaderLineErr>, Option<u8>) {
     enum State { Start, ReadingName, NameFinished, GotCR }
 
     let mut state = State::Start;
@@ -121,24 +185,90 @@ pub fn header_enum_from_stream<R: Reader, E: HeaderEnum>(reader: &mut R)
             },
             (_, Ok(b)) if b == SP => State::NameFinished,
             (_, Ok(b)) if b == COLON => break,
-            (_, Ok(_)) => return (Err(MalformedHeaderSyntax), None),
+            (_, Ok(b)) => {
+                match raw {
+                    Some(raw) => raw.push(malformed_line(reader, header_name, b)),
+                    None => (),
+                }
+                return (Err(MalformedHeaderSyntax), None);
+            },
             (_, Err(_)) => return (Err(EndOfFile), None),
         }
     }
-    let mut iter = HeaderValueByteIterator::new(reader);
-    let header = HeaderEnum::value_from_stream(normalise_header_name(&header_name[]), &mut iter);
-    // Ensure that the entire header line is consumed (don't want to mess up next header!)
-    for _ in iter { }
+    let mut recorder = RecordingReader {
+        reader: reader,
+        recording: raw.is_some(),
+        bytes: Vec::new(),
+    };
+    let (header, next_byte) = {
+        let mut iter = HeaderValueByteIterator::new(&mut recorder);
+        let header: Option<E> = HeaderEnum::value_from_stream(
+            normalise_header_name(&header_name[]), &mut iter);
+        // Ensure that the entire header line is consumed (don't want to mess up next header!)
+        for _ in iter { }
+        (header, iter.next_byte)
+    };
+
+    match raw {
+        Some(raw) => {
+            let mut value = recorder.bytes;
+            // The byte after the line was read to check for folding; it is not part of the value.
+            if next_byte.is_some() {
+                value.pop();
+            }
+            if value.last() == Some(&LF) {
+                value.pop();
+            }
+            if value.last() == Some(&CR) {
+                value.pop();
+            }
+            raw.push(RawHeader {
+                name: header_name.clone(),
+                value: value,
+                malformed: header.is_none(),
+            });
+        },
+        None => (),
+    }
+
     match header {
-        Some(h) => (Ok(h), iter.next_byte),
+        Some(h) => (Ok(h), next_byte),
         None => {
             debug!("malformed header value for {}", &header_name[]);
-            // Alas, I can't tell you what the value actually was... TODO: improve that situation
-            (Err(MalformedHeaderValue), iter.next_byte)
+            (Err(MalformedHeaderValue), next_byte)
         },
     }
 }
 
+/// Read the rest of a header line which is not valid syntax, as a `RawHeader`; `start` is what was
+/// taken to be the name of the header (without any spaces after it), and `b` is the byte which was
+/// found to be invalid.
+fn malformed_line<R: Reader>(reader: &mut R, start: String, b: u8) -> RawHeader {
+    let mut line = start.into_bytes();
+    line.push(b);
+    if b != LF {
+        loop {
+            match reader.read_byte() {
+                Ok(b) if b == LF => break,
+                Ok(b) => line.push(b),
+                Err(_) => break,
+            }
+        }
+    }
+    while line.last() == Some(&LF) || line.last() == Some(&CR) {
+        line.pop();
+    }
+    let (name, value) = match line.iter().position(|&b| b == COLON) {
+        Some(colon) => (line[..colon].to_vec(), line[colon + 1..].to_vec()),
+        None => (line, Vec::new()),
+    };
+    RawHeader {
+        name: String::from_utf8_lossy(&name[]).into_owned(),
+        value: value,
+        malformed: true,
+    }
+}
+
 #[derive(PartialEq, Eq)]
 enum HeaderValueByteIteratorState {
     Normal,  // Anything other than the rest.
@@ -573,6 +703,182 @@ pub trait HeaderConvertible: PartialEq + Clone {
      * For types which implement ``Str``, a body of ``String::from_str(self)`` will often be sufficient.
      */
     fn http_value(&self) -> String;
+
+    /**
+     * Combine another value of this header, received on a separate header line, into this one.
+     *
+     * If the two cannot be combined, the other value is handed back; a header collection then keeps
+     * this value and adds the other to its extensions (see `HeaderCollection::insert`). This is the
+     * default, which suits headers which may only appear once.
+     */
+    fn merge(&mut self, other: Self) -> Result<(), Self> {
+        Err(other)
+    }
+
+    /**
+     * Write the complete header line (or lines) for this value, including the trailing CRLF.
+     *
+     * The default implementation writes a single ``name: value`` line; headers such as Set-Cookie
+     * which cannot be combined into one line override this to write one line per value.
+     */
+    fn write_header_lines<W: Writer>(&self, name: &str, writer: &mut W) -> IoResult<()> {
+        try!(write!(&mut *writer as &mut Writer, "{}: ", name));
+        try!(self.to_stream(writer));
+        write!(&mut *writer as &mut Writer, "\r\n")
+    }
+}
+
+/// A strongly-typed header defined outside this crate, which can be kept in a `HeaderCollection`
+/// alongside the built-in headers with its `get_typed` and `set_typed` methods.
+///
+/// Such headers are stored in the collection's extensions as text, and parsed when first asked
+/// for; the parsed value is cached until the text changes.
+pub trait TypedHeader: HeaderConvertible + Clone + Send + 'static {
+    /// The name of the header, such as ``"X-Request-Id"``.
+    fn header_name() -> &'static str;
+}
+
+/// The parsed values of the `TypedHeader`s of a collection, each kept with the text it was parsed
+/// from so that it is only used while the text is unchanged. Cloning a cache gives an empty one.
+pub struct TypedHeaderCache {
+    entries: RefCell<Vec<(String, String, Box<Any + Send>)>>,
+}
+
+impl TypedHeaderCache {
+    pub fn new() -> TypedHeaderCache {
+        TypedHeaderCache {
+            entries: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The value of header `H` parsed from `raw`, from the cache if it was parsed before.
+    pub fn parse<H: TypedHeader>(&self, raw: &str) -> Option<H> {
+        let name = <H as TypedHeader>::header_name();
+        let mut entries = self.entries.borrow_mut();
+        for &(ref n, ref r, ref value) in entries.iter() {
+            if n[].eq_ignore_ascii_case(name) && &r[] == raw {
+                match (&**value as &Any).downcast_ref::<H>() {
+                    Some(value) => return Some(value.clone()),
+                    None => (),
+                }
+            }
+        }
+
+        let parsed = parse_header_value::<H>(raw);
+        entries.retain(|&(ref n, _, _)| !n[].eq_ignore_ascii_case(name));
+        match parsed {
+            Some(ref value) => entries.push((String::from_str(name), String::from_str(raw),
+                                             Box::new(value.clone()) as Box<Any + Send>)),
+            None => (),
+        }
+        parsed
+    }
+
+    /// Remember that `value` is the value of header `H` given by `raw`.
+    pub fn put<H: TypedHeader>(&self, raw: String, value: H) {
+        let name = <H as TypedHeader>::header_name();
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|&(ref n, _, _)| !n[].eq_ignore_ascii_case(name));
+        entries.push((String::from_str(name), raw, Box::new(value) as Box<Any + Send>));
+    }
+}
+
+impl Clone for TypedHeaderCache {
+    fn clone(&self) -> TypedHeaderCache {
+        TypedHeaderCache::new()
+    }
+}
+
+/// Whether the header with the given (case-insensitive) name is defined as a comma-separated list,
+/// such that several lines of it may be combined into one by joining their values with commas
+/// (RFC 7230, section 3.2.2).
+pub fn is_list_header(name: &str) -> bool {
+    match &name.to_ascii_lowercase()[] {
+        "accept" | "accept-charset" | "accept-encoding" | "accept-language" | "accept-patch" |
+        "accept-ranges" | "access-control-allow-headers" | "access-control-allow-methods" |
+        "access-control-expose-headers" | "access-control-request-headers" | "allow" |
+        "cache-control" | "connection" | "content-encoding" | "content-language" | "expect" |
+        "if-match" | "if-none-match" | "pragma" | "proxy-authenticate" | "referrer-policy" |
+        "te" | "trailer" | "transfer-encoding" | "upgrade" | "vary" | "via" | "warning" |
+        "www-authenticate" => true,
+        _ => false,
+    }
+}
+
+/// The headers of a collection which are not strongly typed, along with any extra occurrences of
+/// strongly-typed headers which could not be combined with the first.
+///
+/// This is a multimap: a name may appear several times, and the order in which the headers were
+/// inserted is preserved. Names are compared case-insensitively.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Extensions {
+    entries: Vec<(String, String)>,
+}
+
+impl Extensions {
+    pub fn new() -> Extensions {
+        Extensions {
+            entries: Vec::new(),
+        }
+    }
+
+    /// The value of the first header with the given name.
+    pub fn get<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.entries.iter().find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+                           .map(|&(_, ref v)| &v[])
+    }
+
+    /// The values of all the headers with the given name, in order.
+    pub fn get_all<'a>(&'a self, name: &str) -> Vec<&'a str> {
+        self.entries.iter().filter(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+                           .map(|&(_, ref v)| &v[]).collect()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Add a header, keeping any others of the same name.
+    pub fn append(&mut self, name: String, value: String) {
+        self.entries.push((name, value));
+    }
+
+    /// Set a header, replacing any others of the same name.
+    pub fn insert(&mut self, name: String, value: String) {
+        self.remove(&name[]);
+        self.entries.push((name, value));
+    }
+
+    /// Remove all the headers with the given name, returning their values.
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        let (removed, kept) = self.entries.drain().partition(|&(ref n, _)| {
+            n[].eq_ignore_ascii_case(name)
+        });
+        self.entries = kept;
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    /// The number of headers, counting each occurrence of a name.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the headers as `(name, value)` pairs, in the order they were inserted.
+    pub fn iter(&self) -> Iter<(String, String)> {
+        self.entries.iter()
+    }
+}
+
+/// Parse a header value held as a string (for example, one taken from a header whose value is kept
+/// as a ``String``) as the given type.
+pub fn parse_header_value<T: HeaderConvertible>(value: &str) -> Option<T> {
+    let mut reader = BufReader::new(value.as_bytes());
+    let mut iter = HeaderValueByteIterator::new(&mut reader);
+    HeaderConvertible::from_stream(&mut iter)
 }
 
 /// A header with multiple comma-separated values. Implement this and a HeaderConvertible
@@ -597,6 +903,13 @@ impl<T: CommaListHeaderConvertible> HeaderConvertible for Vec<T> {
         Some(result)
     }
 
+    /// Lines of a comma-separated list header are combined into a single list, as RFC 7230,
+    /// section 3.2.2 permits.
+    fn merge(&mut self, other: Vec<T>) -> Result<(), Vec<T>> {
+        self.extend(other.into_iter());
+        Ok(())
+    }
+
     fn to_stream<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
         for (i, item) in self.iter().enumerate() {
             if i != 0 {
@@ -758,7 +1071,8 @@ impl HeaderConvertible for Tm {
 mod test {
     use time::Tm;
     use headers::test_utils::{from_stream_with_str, to_stream_into_str};
-    use super::HeaderConvertible;
+    use headers::response;
+    use super::{HeaderConvertible, HeaderValueByteIterator, Extensions, TypedHeader};
 
     #[test]
     fn test_from_stream_str() {
@@ -871,6 +1185,181 @@ mod test {
     fn test_to_stream() {
         assert_eq!(to_stream_into_str(&sample_tm()), String::from_str("Sun, 06 Nov 1994 08:49:37 GMT"));
     }
+
+    #[test]
+    fn test_extensions() {
+        let mut extensions = Extensions::new();
+        extensions.append(String::from_str("X-Foo"), String::from_str("1"));
+        extensions.append(String::from_str("X-Bar"), String::from_str("2"));
+        extensions.append(String::from_str("x-foo"), String::from_str("3"));
+        assert_eq!(extensions.get("X-FOO"), Some("1"));
+        assert_eq!(extensions.get_all("x-foo"), vec!("1", "3"));
+        assert_eq!(extensions.len(), 3);
+
+        extensions.insert(String::from_str("X-Foo"), String::from_str("4"));
+        assert_eq!(extensions.get_all("x-foo"), vec!("4"));
+        assert_eq!(extensions.remove("X-Bar"), vec!(String::from_str("2")));
+        assert!(!extensions.contains_key("X-Bar"));
+    }
+
+    #[test]
+    fn test_repeated_headers() {
+        use std::io::MemWriter;
+        use headers::etag::strong_etag;
+        use headers::response::HeaderCollection;
+        let mut headers = HeaderCollection::new();
+        // Typed lists are merged, as are list headers held as strings.
+        headers.insert_raw(String::from_str("Cache-Control"), b"no-cache").unwrap();
+        headers.insert_raw(String::from_str("Cache-Control"), b"max-age=60").unwrap();
+        headers.insert_raw(String::from_str("Via"), b"1.1 a").unwrap();
+        headers.insert_raw(String::from_str("Via"), b"1.1 b").unwrap();
+        // Other headers keep every different instance.
+        headers.insert_raw(String::from_str("Content-Length"), b"10").unwrap();
+        headers.insert_raw(String::from_str("Content-Length"), b"10").unwrap();
+        headers.insert_raw(String::from_str("ETag"), b"\"a\"").unwrap();
+        headers.insert_raw(String::from_str("ETag"), b"\"b\"").unwrap();
+        headers.insert_raw(String::from_str("Set-Cookie"), b"a=1").unwrap();
+        headers.insert_raw(String::from_str("Set-Cookie"), b"b=2").unwrap();
+        headers.insert_raw(String::from_str("X-Custom"), b"x").unwrap();
+        headers.insert_raw(String::from_str("X-Custom"), b"y").unwrap();
+
+        assert_eq!(headers.cache_control.as_ref().map(|v| v.len()), Some(2));
+        assert_eq!(headers.via, Some(String::from_str("1.1 a, 1.1 b")));
+        assert_eq!(headers.content_length, Some(10));
+        assert!(!headers.extensions.contains_key("Content-Length"));
+        assert_eq!(headers.extensions.get_all("ETag"), vec!("\"b\""));
+
+        let mut writer = MemWriter::new();
+        headers.write_all(&mut writer).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(),
+                   String::from_str("Cache-Control: no-cache, max-age=60\r\n\
+                                     Via: 1.1 a, 1.1 b\r\n\
+                                     ETag: \"a\"\r\n\
+                                     Content-Length: 10\r\n\
+                                     Set-Cookie: a=1\r\n\
+                                     Set-Cookie: b=2\r\n\
+                                     ETag: \"b\"\r\n\
+                                     X-Custom: x\r\n\
+                                     X-Custom: y\r\n\
+                                     \r\n"));
+
+        // `set` replaces every occurrence.
+        headers.set(response::Header::ETag(strong_etag(String::from_str("c"))));
+        assert!(!headers.extensions.contains_key("ETag"));
+    }
+
+    #[test]
+    fn test_lookup_by_name() {
+        use headers::HeaderEnum;
+        use headers::request::HeaderCollection;
+        let mut headers = HeaderCollection::new();
+        headers.insert_raw(String::from_str("Max-Forwards"), b"3").unwrap();
+        headers.insert_raw(String::from_str("X-Custom"), b"x").unwrap();
+        headers.insert_raw(String::from_str("x-custom"), b"y").unwrap();
+
+        assert_eq!(headers.get_raw("max-forwards"), Some(String::from_str("3")));
+        assert_eq!(headers.get("MAX-FORWARDS").map(|h| h.header_name()),
+                   Some(String::from_str("Max-Forwards")));
+        assert_eq!(headers.get_raw("X-CUSTOM"), Some(String::from_str("x")));
+        assert_eq!(headers.get_raw("Referer"), None);
+        assert_eq!(headers.get_raw("X-Other"), None);
+
+        assert_eq!(headers.set_raw(String::from_str("max-forwards"), b"five"), Err(()));
+        assert_eq!(headers.max_forwards, Some(3));
+        headers.set_raw(String::from_str("max-forwards"), b"5").unwrap();
+        assert_eq!(headers.max_forwards, Some(5));
+        headers.set_raw(String::from_str("X-CUSTOM"), b"z").unwrap();
+        assert_eq!(headers.extensions.get_all("x-custom"), vec!("z"));
+
+        let removed: Vec<String> = headers.remove("x-custom").iter()
+                                          .map(|h| h.header_value()).collect();
+        assert_eq!(removed, vec!(String::from_str("z")));
+        assert_eq!(headers.remove("Max-Forwards").len(), 1);
+        assert_eq!(headers.max_forwards, None);
+        assert!(headers.remove("Max-Forwards").is_empty());
+        assert!(headers.extensions.is_empty());
+    }
+
+    #[derive(Clone, PartialEq, Show)]
+    struct Retries(usize);
+
+    impl HeaderConvertible for Retries {
+        fn from_stream<R: Reader>(reader: &mut HeaderValueByteIterator<R>) -> Option<Retries> {
+            HeaderConvertible::from_stream(reader).map(Retries)
+        }
+
+        fn http_value(&self) -> String {
+            let Retries(count) = *self;
+            count.to_string()
+        }
+    }
+
+    impl TypedHeader for Retries {
+        fn header_name() -> &'static str {
+            "X-Retries"
+        }
+    }
+
+    #[test]
+    fn test_typed_headers() {
+        use headers::request::HeaderCollection;
+        let mut headers = HeaderCollection::new();
+        assert_eq!(headers.get_typed::<Retries>(), None);
+
+        headers.insert_raw(String::from_str("x-retries"), b"2").unwrap();
+        assert_eq!(headers.get_typed::<Retries>(), Some(Retries(2)));
+        assert_eq!(headers.get_typed::<Retries>(), Some(Retries(2)));
+
+        headers.set_typed(Retries(3));
+        assert_eq!(headers.extensions.get_all("X-Retries"), vec!("3"));
+        assert_eq!(headers.get_typed::<Retries>(), Some(Retries(3)));
+        assert_eq!(headers.clone().get_typed::<Retries>(), Some(Retries(3)));
+
+        // The cached value is not used once the text has changed.
+        headers.extensions.insert(String::from_str("X-Retries"), String::from_str("4"));
+        assert_eq!(headers.get_typed::<Retries>(), Some(Retries(4)));
+        headers.extensions.insert(String::from_str("X-Retries"), String::from_str("many"));
+        assert_eq!(headers.get_typed::<Retries>(), None);
+    }
+
+    #[test]
+    fn test_raw_headers() {
+        use std::io::{BufReader, MemWriter};
+        use headers::{header_enum_from_stream_recording, RawHeader};
+
+        fn record(input: &[u8]) -> (bool, RawHeader) {
+            let mut reader = BufReader::new(input);
+            let mut raw = Vec::new();
+            let (result, next_byte) = header_enum_from_stream_recording::<_, response::Header>(
+                &mut reader, Some(&mut raw));
+            assert_eq!(next_byte, Some(b'\r'));
+            assert_eq!(raw.len(), 1);
+            (result.is_ok(), raw.pop().unwrap())
+        }
+
+        assert_eq!(record(b"content-LENGTH:  12\r\n\r\n"),
+                   (true, RawHeader { name: String::from_str("content-LENGTH"),
+                                      value: b"  12".to_vec(), malformed: false }));
+        let (valid, folded) = record(b"X-Folded: a\r\n b\r\n\r\n");
+        assert!(valid);
+        assert_eq!(folded.value, b" a\r\n b".to_vec());
+        assert_eq!(record(b"Content-Length: x\r\n\r\n"),
+                   (false, RawHeader { name: String::from_str("Content-Length"),
+                                       value: b" x".to_vec(), malformed: true }));
+
+        let mut reader = BufReader::new(b"Bad\"Name: x\r\nNext: y\r\n\r\n");
+        let mut raw = Vec::new();
+        let (result, next_byte) = header_enum_from_stream_recording::<_, response::Header>(
+            &mut reader, Some(&mut raw));
+        assert!(result.is_err() && next_byte.is_none());
+        assert_eq!(raw, vec!(RawHeader { name: String::from_str("Bad\"Name"),
+                                         value: b" x".to_vec(), malformed: true }));
+        assert_eq!(reader.read_byte(), Ok(b'N'));
+
+        let mut writer = MemWriter::new();
+        folded.write_line(&mut writer).unwrap();
+        assert_eq!(&writer.get_ref()[], b"X-Folded: a\r\n b\r\n");
+    }
 }
 
 macro_rules! headers_mod {
@@ -895,11 +1384,12 @@ macro_rules! headers_mod {
 
             #[allow(unused_imports)]
             use std::io::{BufReader, IoResult};
-            use std::ascii::OwnedAsciiExt;
+            use std::ascii::{AsciiExt, OwnedAsciiExt};
             use time;
-            use std::collections::hash_map::{HashMap, Iter};
+            use std::slice::Iter;
             use headers;
-            use headers::{HeaderEnum, HeaderConvertible, HeaderValueByteIterator};
+            use headers::{HeaderEnum, HeaderConvertible, HeaderValueByteIterator, Extensions};
+            use headers::{TypedHeader, TypedHeaderCache};
 
             use self::Header::{$($caps_ident),+};
             use self::Header::ExtensionHeader;
@@ -912,22 +1402,70 @@ macro_rules! headers_mod {
             #[derive(Clone)]
             pub struct HeaderCollection {
                 $(pub $lower_ident: Option<$htype>,)*
-                pub extensions: HashMap<String, String>,
+                pub extensions: Extensions,
+                typed_cache: TypedHeaderCache,
             }
 
             impl HeaderCollection {
                 pub fn new() -> HeaderCollection {
                     HeaderCollection {
                         $($lower_ident: None,)*
-                        extensions: HashMap::new(),
+                        extensions: Extensions::new(),
+                        typed_cache: TypedHeaderCache::new(),
                     }
                 }
 
-                /// Consume a header, putting it into this structure.
+                /// Consume a header, putting it into this structure, alongside any occurrences of
+                /// the same header already present.
+                ///
+                /// A strongly-typed header that is already present is combined with the new value
+                /// if its type permits (as with comma-separated lists and Set-Cookie), or if it is
+                /// a list header (see `is_list_header`) whose joined values can be parsed. Failing
+                /// that, the first value is kept and the new one, unless it is the same, is added
+                /// to the extensions under the header's name, so that it is not lost. Extension
+                /// headers are appended.
                 pub fn insert(&mut self, header: Header) {
                     match header {
-                        $($caps_ident(value) => self.$lower_ident = Some(value),)*
-                        ExtensionHeader(key, value) => { self.extensions.insert(key, value); },
+                        $($caps_ident(value) => match self.$lower_ident.take() {
+                            None => self.$lower_ident = Some(value),
+                            Some(mut existing) => match existing.merge(value) {
+                                Ok(()) => self.$lower_ident = Some(existing),
+                                Err(value) => {
+                                    let joined = if headers::is_list_header($output_name) {
+                                        headers::parse_header_value(&format!("{}, {}",
+                                            existing.http_value(), value.http_value())[])
+                                    } else {
+                                        None
+                                    };
+                                    let value = value.http_value();
+                                    match joined {
+                                        Some(joined) => self.$lower_ident = Some(joined),
+                                        // A repeat of the same value is only the one header.
+                                        None if value == existing.http_value() => {
+                                            self.$lower_ident = Some(existing);
+                                        },
+                                        None => {
+                                            self.$lower_ident = Some(existing);
+                                            self.extensions.append(
+                                                String::from_str($output_name), value);
+                                        },
+                                    }
+                                },
+                            },
+                        },)*
+                        ExtensionHeader(key, value) => self.extensions.append(key, value),
+                    }
+                }
+
+                /// Put a header into this structure, replacing any occurrences of the same header
+                /// already present.
+                pub fn set(&mut self, header: Header) {
+                    match header {
+                        $($caps_ident(value) => {
+                            self.extensions.remove($output_name);
+                            self.$lower_ident = Some(value);
+                        },)*
+                        ExtensionHeader(key, value) => self.extensions.insert(key, value),
                     }
                 }
 
@@ -946,6 +1484,84 @@ macro_rules! headers_mod {
                     }
                 }
 
+                /// Set a raw header, replacing any occurrences of the same header already present.
+                /// This will return an error (leaving the collection unchanged) in the same cases
+                /// as `insert_raw`.
+                pub fn set_raw(&mut self, name: String, value: &[u8]) -> Result<(), ()> {
+                    let mut reader = BufReader::new(value);
+                    let mut value_iter = HeaderValueByteIterator::new(&mut reader);
+                    match HeaderEnum::value_from_stream(name, &mut value_iter) {
+                        Some(h) => {
+                            self.set(h);
+                            Ok(())
+                        },
+                        None => Err(())
+                    }
+                }
+
+                /// Get the header with the given name, which is matched case-insensitively.
+                ///
+                /// For a strongly-typed header this is the typed value; any extra occurrences
+                /// which could not be combined with it are only available from `extensions`. For
+                /// any other header it is the first occurrence.
+                pub fn get(&self, name: &str) -> Option<Header> {
+                    match &name.to_ascii_lowercase()[] {
+                        $($input_name => match self.$lower_ident {
+                            Some(ref v) => return Some($caps_ident(v.clone())),
+                            None => (),
+                        },)*
+                        _ => (),
+                    }
+                    self.extensions.iter().find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+                                          .map(|&(ref n, ref v)| ExtensionHeader(n.clone(),
+                                                                                 v.clone()))
+                }
+
+                /// Get the value of the header with the given name as it would be written, per
+                /// `get`.
+                pub fn get_raw(&self, name: &str) -> Option<String> {
+                    self.get(name).map(|header| header.header_value())
+                }
+
+                /// Remove all occurrences of the header with the given name, which is matched
+                /// case-insensitively, returning them in order.
+                pub fn remove(&mut self, name: &str) -> Vec<Header> {
+                    let mut removed = Vec::new();
+                    match &name.to_ascii_lowercase()[] {
+                        $($input_name => match self.$lower_ident.take() {
+                            Some(v) => removed.push($caps_ident(v)),
+                            None => (),
+                        },)*
+                        _ => (),
+                    }
+                    for &(ref n, ref v) in self.extensions.iter() {
+                        if n[].eq_ignore_ascii_case(name) {
+                            removed.push(ExtensionHeader(n.clone(), v.clone()));
+                        }
+                    }
+                    self.extensions.remove(name);
+                    removed
+                }
+
+                /// Get a header of a type defined outside this crate (see `TypedHeader`), or `None`
+                /// if it is absent or its value is not valid for the type.
+                pub fn get_typed<H: TypedHeader>(&self) -> Option<H> {
+                    match self.get_raw(<H as TypedHeader>::header_name()) {
+                        Some(raw) => self.typed_cache.parse::<H>(&raw[]),
+                        None => None,
+                    }
+                }
+
+                /// Set a header of a type defined outside this crate (see `TypedHeader`),
+                /// replacing any occurrences of the same header already present.
+                pub fn set_typed<H: TypedHeader>(&mut self, value: H) {
+                    let name = <H as TypedHeader>::header_name();
+                    let raw = value.http_value();
+                    self.remove(name);
+                    self.extensions.append(String::from_str(name), raw.clone());
+                    self.typed_cache.put(raw, value);
+                }
+
                 pub fn iter<'a>(&'a self) -> HeaderCollectionIterator<'a> {
                     HeaderCollectionIterator {
                         pos: 0,
@@ -967,7 +1583,7 @@ macro_rules! headers_mod {
             pub struct HeaderCollectionIterator<'a> {
                 pos: usize,
                 coll: &'a HeaderCollection,
-                ext_iter: Option<Iter<'a, String, String>>
+                ext_iter: Option<Iter<'a, (String, String)>>
             }
 
             impl<'a> Iterator for HeaderCollectionIterator<'a> {
@@ -986,7 +1602,7 @@ macro_rules! headers_mod {
                                 continue
                             },
                             _ => match self.ext_iter.as_mut().unwrap().next() {
-                                Some((k, v)) =>
+                                Some(&(ref k, ref v)) =>
                                     return Some(ExtensionHeader(k.clone(), v.clone())),
                                 None => return None,
                             },
@@ -1019,17 +1635,11 @@ macro_rules! headers_mod {
                         _ => (),
                     }
 
-                    try!(write!(&mut *writer as &mut Writer, "{}: ", match *self {
-                        $($caps_ident(_) => $output_name,)*
-                        ExtensionHeader(..) => unreachable!(),  // Already returned
-                    }));
-
                     // FIXME: all the `h` cases satisfy HeaderConvertible, can it be simplified?
-                    try!(match *self {
-                        $($caps_ident(ref h) => h.to_stream(writer),)*
+                    match *self {
+                        $($caps_ident(ref h) => h.write_header_lines($output_name, writer),)*
                         ExtensionHeader(..) => unreachable!(),  // Already returned
-                    });
-                    write!(&mut *writer as &mut Writer, "\r\n")
+                    }
                 }
 
                 fn value_from_stream<R: Reader>(name: String, value: &mut HeaderValueByteIterator<R>)
@@ -1051,10 +1661,10 @@ headers_mod! {
     #[doc = "Request whatnottery."]
     pub mod request;
 
-    num_headers: 38;
+    num_headers: 42;
 
     // RFC 2616, Section 4.5: General Header Fields
-     0, "Cache-Control",     "cache-control",     CacheControl,     cache_control,     String,
+     0, "Cache-Control",     "cache-control",     CacheControl,     cache_control,     Vec<headers::cache_control::CacheDirective>,
      1, "Connection",        "connection",        Connection,       connection,        Vec<headers::connection::Connection>,
      2, "Date",              "date",              Date,             date,              time::Tm,
      3, "Pragma",            "pragma",            Pragma,           pragma,            String,
@@ -1069,18 +1679,18 @@ headers_mod! {
     10, "Accept-Charset",      "accept-charset",      AcceptCharset,      accept_charset,      String,
     11, "Accept-Encoding",     "accept-encoding",     AcceptEncoding,     accept_encoding,     String,
     12, "Accept-Language",     "accept-language",     AcceptLanguage,     accept_language,     String,
-    13, "Authorization",       "authorization",       Authorization,      authorization,       String,
+    13, "Authorization",       "authorization",       Authorization,      authorization,       headers::authorization::Credentials,
     14, "Expect",              "expect",              Expect,             expect,              String,
     15, "From",                "from",                From,               from,                String,
     16, "Host",                "host",                Host,               host,                headers::host::Host,
-    17, "If-Match",            "if-match",            IfMatch,            if_match,            String,
+    17, "If-Match",            "if-match",            IfMatch,            if_match,            headers::etag::EntityTagMatch,
     18, "If-Modified-Since",   "if-modified-since",   IfModifiedSince,    if_modified_since,   time::Tm,
-    19, "If-None-Match",       "if-none-match",       IfNoneMatch,        if_none_match,       String,
-    20, "If-Range",            "if-range",            IfRange,            if_range,            String,
+    19, "If-None-Match",       "if-none-match",       IfNoneMatch,        if_none_match,       headers::etag::EntityTagMatch,
+    20, "If-Range",            "if-range",            IfRange,            if_range,            headers::if_range::IfRange,
     21, "If-Unmodified-Since", "if-unmodified-since", IfUnmodifiedSince,  if_unmodified_since, time::Tm,
     22, "Max-Forwards",        "max-forwards",        MaxForwards,        max_forwards,        usize,
-    23, "Proxy-Authorization", "proxy-authorization", ProxyAuthorization, proxy_authorization, String,
-    24, "Range",               "range",               Range,              range,               String,
+    23, "Proxy-Authorization", "proxy-authorization", ProxyAuthorization, proxy_authorization, headers::authorization::Credentials,
+    24, "Range",               "range",               Range,              range,               headers::range::Range,
     25, "Referer",             "referer",             Referer,            referer,             String,
     26, "TE",                  "te",                  Te,                 te,                  String,
     27, "User-Agent",          "user-agent",          UserAgent,          user_agent,          String,
@@ -1092,20 +1702,28 @@ headers_mod! {
     31, "Content-Length",   "content-length",   ContentLength,   content_length,   usize,
     32, "Content-Location", "content-location", ContentLocation, content_location, String,
     33, "Content-MD5",      "content-md5",      ContentMd5,      content_md5,      String,
-    34, "Content-Range",    "content-range",    ContentRange,    content_range,    String,
+    34, "Content-Range",    "content-range",    ContentRange,    content_range,    headers::content_range::ContentRange,
     35, "Content-Type",     "content-type",     ContentType,     content_type,     headers::content_type::MediaType,
     36, "Expires",          "expires",          Expires,         expires,          time::Tm,
     37, "Last-Modified",    "last-modified",    LastModified,    last_modified,    time::Tm,
+
+    // RFC 6265: HTTP State Management Mechanism
+    38, "Cookie", "cookie", Cookie, cookie, headers::cookie::Cookie,
+
+    // Fetch, section 3.2: CORS protocol
+    39, "Origin",                         "origin",                         Origin,                      origin,                         headers::access_control::Origin,
+    40, "Access-Control-Request-Method",  "access-control-request-method",  AccessControlRequestMethod,  access_control_request_method,  ::method::Method,
+    41, "Access-Control-Request-Headers", "access-control-request-headers", AccessControlRequestHeaders, access_control_request_headers, Vec<headers::access_control::FieldName>,
 }
 
 headers_mod! {
     #[doc = "Response whatnottery."]
     pub mod response;
 
-    num_headers: 30;
+    num_headers: 43;
 
     // RFC 2616, Section 4.5: General Header Fields
-     0, "Cache-Control",     "cache-control",     CacheControl,     cache_control,     String,
+     0, "Cache-Control",     "cache-control",     CacheControl,     cache_control,     Vec<headers::cache_control::CacheDirective>,
      1, "Connection",        "connection",        Connection,       connection,        Vec<headers::connection::Connection>,
      2, "Date",              "date",              Date,             date,              time::Tm,
      3, "Pragma",            "pragma",            Pragma,           pragma,            String,
@@ -1121,11 +1739,11 @@ headers_mod! {
     11, "Age",                "age",                Age,               age,                String,
     12, "ETag",               "etag",               ETag,              etag,               headers::etag::EntityTag,
     13, "Location",           "location",           Location,          location,           ::url::Url,
-    14, "Proxy-Authenticate", "proxy-authenticate", ProxyAuthenticate, proxy_authenticate, String,
-    15, "Retry-After",        "retry-after",        RetryAfter,        retry_after,        String,
+    14, "Proxy-Authenticate", "proxy-authenticate", ProxyAuthenticate, proxy_authenticate, headers::authorization::Challenges,
+    15, "Retry-After",        "retry-after",        RetryAfter,        retry_after,        headers::retry_after::RetryAfter,
     16, "Server",             "server",             Server,            server,             String,
     17, "Vary",               "vary",               Vary,              vary,               String,
-    18, "WWW-Authenticate",   "www-authenticate",   WwwAuthenticate,   www_authenticate,   String,
+    18, "WWW-Authenticate",   "www-authenticate",   WwwAuthenticate,   www_authenticate,   headers::authorization::Challenges,
 
     // RFC 2616, Section 7.1: Entity Header Fields
     19, "Allow",            "allow",            Allow,           allow,            Vec<::method::Method>,
@@ -1134,10 +1752,32 @@ headers_mod! {
     22, "Content-Length",   "content-length",   ContentLength,   content_length,   usize,
     23, "Content-Location", "content-location", ContentLocation, content_location, String,
     24, "Content-MD5",      "content-md5",      ContentMd5,      content_md5,      String,
-    25, "Content-Range",    "content-range",    ContentRange,    content_range,    String,
+    25, "Content-Range",    "content-range",    ContentRange,    content_range,    headers::content_range::ContentRange,
     26, "Content-Type",     "content-type",     ContentType,     content_type,     headers::content_type::MediaType,
     27, "Expires",          "expires",          Expires,         expires,          String, // TODO: Should be Tm
     28, "Last-Modified",    "last-modified",    LastModified,    last_modified,    time::Tm,
 
-    29, "Access-Control-Allow-Origin", "access-control-allow-origin", AccessControlAllowOrigin, access_control_allow_origin, String,
+    // Fetch, section 3.2: CORS protocol
+    29, "Access-Control-Allow-Origin", "access-control-allow-origin", AccessControlAllowOrigin, access_control_allow_origin, headers::access_control::AllowOrigin,
+
+    // RFC 6265: HTTP State Management Mechanism
+    30, "Set-Cookie", "set-cookie", SetCookie, set_cookie, headers::cookie::SetCookies,
+
+    // Security headers
+    31, "Strict-Transport-Security",           "strict-transport-security",           StrictTransportSecurity,          strict_transport_security,           headers::strict_transport_security::StrictTransportSecurity,
+    32, "Content-Security-Policy",             "content-security-policy",             ContentSecurityPolicy,            content_security_policy,             headers::content_security_policy::ContentSecurityPolicy,
+    33, "Content-Security-Policy-Report-Only", "content-security-policy-report-only", ContentSecurityPolicyReportOnly,  content_security_policy_report_only, headers::content_security_policy::ContentSecurityPolicy,
+    34, "X-Frame-Options",                     "x-frame-options",                     XFrameOptions,                    x_frame_options,                     headers::x_frame_options::XFrameOptions,
+    35, "X-Content-Type-Options",              "x-content-type-options",              XContentTypeOptions,              x_content_type_options,              headers::x_content_type_options::NoSniff,
+    36, "Referrer-Policy",                     "referrer-policy",                     ReferrerPolicy,                   referrer_policy,                     Vec<headers::referrer_policy::ReferrerPolicy>,
+
+    // Fetch, section 3.2: CORS protocol
+    37, "Access-Control-Allow-Credentials", "access-control-allow-credentials", AccessControlAllowCredentials, access_control_allow_credentials, headers::access_control::AllowCredentials,
+    38, "Access-Control-Allow-Methods",     "access-control-allow-methods",     AccessControlAllowMethods,     access_control_allow_methods,     Vec<::method::Method>,
+    39, "Access-Control-Allow-Headers",     "access-control-allow-headers",     AccessControlAllowHeaders,     access_control_allow_headers,     Vec<headers::access_control::FieldName>,
+    40, "Access-Control-Expose-Headers",    "access-control-expose-headers",    AccessControlExposeHeaders,    access_control_expose_headers,    Vec<headers::access_control::FieldName>,
+    41, "Access-Control-Max-Age",           "access-control-max-age",           AccessControlMaxAge,           access_control_max_age,           usize,
+
+    // RFC 6266: Use of the Content-Disposition Header Field
+    42, "Content-Disposition", "content-disposition", ContentDisposition, content_disposition, headers::content_disposition::ContentDisposition,
 }
@@ -0,0 +1,171 @@
+//! The Range request header, defined in RFC 7233, section 3.1.
+//!
+//! Range                  = byte-ranges-specifier / other-ranges-specifier
+//! byte-ranges-specifier  = bytes-unit "=" byte-range-set
+//! byte-range-set         = 1#( byte-range-spec / suffix-byte-range-spec )
+//! other-ranges-specifier = other-range-unit "=" other-range-set
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+pub use self::ByteRangeSpec::{FromTo, AllFrom, Last};
+pub use self::Range::{Bytes, OtherRange};
+
+/// A single range of bytes requested.
+#[derive(Clone, PartialEq, Eq, Copy)]
+pub enum ByteRangeSpec {
+    /// first-byte-pos "-" last-byte-pos; both positions are inclusive
+    FromTo(u64, u64),
+    /// first-byte-pos "-": everything from the given position onwards
+    AllFrom(u64),
+    /// "-" suffix-length: the final bytes of the representation
+    Last(u64),
+}
+
+impl ByteRangeSpec {
+    /// Resolve this range against a representation of `length` bytes, producing the inclusive
+    /// positions of the first and last byte to send, or `None` if the range is not satisfiable.
+    pub fn to_satisfiable_range(&self, length: u64) -> Option<(u64, u64)> {
+        match *self {
+            FromTo(first, _) | AllFrom(first) if first >= length => None,
+            FromTo(first, last) => Some((first, if last < length { last } else { length - 1 })),
+            AllFrom(first) => Some((first, length - 1)),
+            Last(0) => None,
+            Last(_) if length == 0 => None,
+            Last(suffix) => Some((if suffix < length { length - suffix } else { 0 }, length - 1)),
+        }
+    }
+}
+
+impl fmt::Show for ByteRangeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FromTo(first, last) => write!(f, "{}-{}", first, last),
+            AllFrom(first) => write!(f, "{}-", first),
+            Last(suffix) => write!(f, "-{}", suffix),
+        }
+    }
+}
+
+/// The value of the Range header.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Range {
+    /// A set of byte ranges, in the order requested
+    Bytes(Vec<ByteRangeSpec>),
+    /// Ranges in some other unit: the unit, and the unparsed set of ranges
+    OtherRange(String, String),
+}
+
+impl fmt::Show for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Bytes(ref ranges) => {
+                try!(f.write_str("bytes="));
+                for (i, range) in ranges.iter().enumerate() {
+                    if i != 0 {
+                        try!(f.write_str(","));
+                    }
+                    try!(write!(f, "{:?}", range));
+                }
+                Ok(())
+            },
+            OtherRange(ref unit, ref ranges) => write!(f, "{}={}", unit, ranges),
+        }
+    }
+}
+
+fn parse_byte_range_spec(spec: &str) -> Option<ByteRangeSpec> {
+    let mut parts = spec.splitn(1, '-');
+    let first = parts.next().unwrap();
+    let last = match parts.next() {
+        Some(last) => last,
+        None => return None,
+    };
+    // Be strict about digits: `parse` would otherwise accept signs.
+    let digits = |s: &str| s.len() > 0 && s.bytes().all(|b| b >= b'0' && b <= b'9');
+    match (first, last) {
+        ("", suffix) if digits(suffix) => suffix.parse().map(Last),
+        (first, "") if digits(first) => first.parse().map(AllFrom),
+        (first, last) if digits(first) && digits(last) => {
+            match (first.parse(), last.parse()) {
+                (Some(first), Some(last)) if first <= last => Some(FromTo(first, last)),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+impl super::HeaderConvertible for Range {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<Range> {
+        let unit = match reader.read_token() {
+            Some(unit) => unit.to_ascii_lowercase(),
+            None => return None,
+        };
+        if reader.next() != Some(b'=') {
+            return None;
+        }
+        let set = reader.collect_to_string();
+        if &unit[] != "bytes" {
+            return Some(OtherRange(unit, String::from_str(set.trim())));
+        }
+
+        let mut ranges = Vec::new();
+        for spec in set.split(',') {
+            let spec = spec.trim();
+            if spec.is_empty() {
+                // RFC 7230, section 7: empty list elements are to be ignored.
+                continue;
+            }
+            match parse_byte_range_spec(spec) {
+                Some(range) => ranges.push(range),
+                None => return None,
+            }
+        }
+        if ranges.is_empty() {
+            None
+        } else {
+            Some(Bytes(ranges))
+        }
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_range() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("bytes=0-499", Bytes(vec!(FromTo(0, 499))));
+    assert_conversion_correct("bytes=500-", Bytes(vec!(AllFrom(500))));
+    assert_conversion_correct("bytes=-500", Bytes(vec!(Last(500))));
+    assert_conversion_correct("bytes=0-0,-1", Bytes(vec!(FromTo(0, 0), Last(1))));
+    assert_conversion_correct("items=1-5", OtherRange(String::from_str("items"),
+                                                      String::from_str("1-5")));
+    assert_interpretation_correct("Bytes=500-600, 601-999 ,, -20",
+                                  Bytes(vec!(FromTo(500, 600), FromTo(601, 999), Last(20))));
+
+    assert_invalid::<Range>("");
+    assert_invalid::<Range>("bytes");
+    assert_invalid::<Range>("bytes=");
+    assert_invalid::<Range>("bytes=-");
+    assert_invalid::<Range>("bytes=5-1");
+    assert_invalid::<Range>("bytes=a-b");
+    assert_invalid::<Range>("bytes=+1-2");
+    assert_invalid::<Range>("bytes=1-2-3");
+}
+
+#[test]
+fn test_to_satisfiable_range() {
+    assert_eq!(FromTo(0, 499).to_satisfiable_range(10000), Some((0, 499)));
+    assert_eq!(FromTo(9500, 10500).to_satisfiable_range(10000), Some((9500, 9999)));
+    assert_eq!(FromTo(10000, 10500).to_satisfiable_range(10000), None);
+    assert_eq!(AllFrom(9500).to_satisfiable_range(10000), Some((9500, 9999)));
+    assert_eq!(AllFrom(0).to_satisfiable_range(0), None);
+    assert_eq!(Last(500).to_satisfiable_range(10000), Some((9500, 9999)));
+    assert_eq!(Last(20000).to_satisfiable_range(10000), Some((0, 9999)));
+    assert_eq!(Last(0).to_satisfiable_range(10000), None);
+    assert_eq!(Last(1).to_satisfiable_range(0), None);
+}
@@ -0,0 +1,111 @@
+//! The Referrer-Policy response header, defined in Referrer Policy, section 4.1.
+//!
+//! Referrer-Policy = 1#policy-token
+//! policy-token    = referrer-policy / extension-token
+
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::io::IoResult;
+
+pub use self::ReferrerPolicy::{NoReferrer, NoReferrerWhenDowngrade, SameOrigin, Origin,
+                               StrictOrigin, OriginWhenCrossOrigin, StrictOriginWhenCrossOrigin,
+                               UnsafeUrl, ExtensionPolicy};
+
+/// What the user agent may send in the Referer header of requests made from a page.
+///
+/// The header is a list so that new policies can be introduced with a fallback: the user agent
+/// uses the last policy in the list that it understands (see `effective_policy`).
+#[derive(Clone, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+    NoReferrer,
+    NoReferrerWhenDowngrade,
+    SameOrigin,
+    Origin,
+    StrictOrigin,
+    OriginWhenCrossOrigin,
+    StrictOriginWhenCrossOrigin,
+    UnsafeUrl,
+    /// A policy not known to this library, in lowercase.
+    ExtensionPolicy(String),
+}
+
+impl ReferrerPolicy {
+    fn as_str(&self) -> &str {
+        match *self {
+            NoReferrer => "no-referrer",
+            NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            SameOrigin => "same-origin",
+            Origin => "origin",
+            StrictOrigin => "strict-origin",
+            OriginWhenCrossOrigin => "origin-when-cross-origin",
+            StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            UnsafeUrl => "unsafe-url",
+            ExtensionPolicy(ref s) => &s[],
+        }
+    }
+}
+
+/// The policy a user agent would follow given a Referrer-Policy header: the last one in the list
+/// which is not an extension policy.
+pub fn effective_policy(policies: &[ReferrerPolicy]) -> Option<&ReferrerPolicy> {
+    policies.iter().rev().find(|policy| match **policy {
+        ExtensionPolicy(_) => false,
+        _ => true,
+    })
+}
+
+impl fmt::Show for ReferrerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl super::CommaListHeaderConvertible for ReferrerPolicy {}
+
+impl super::HeaderConvertible for ReferrerPolicy {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<ReferrerPolicy> {
+        let token = match reader.read_token() {
+            Some(token) => token.to_ascii_lowercase(),
+            None => return None,
+        };
+        Some(match &token[] {
+            "no-referrer" => NoReferrer,
+            "no-referrer-when-downgrade" => NoReferrerWhenDowngrade,
+            "same-origin" => SameOrigin,
+            "origin" => Origin,
+            "strict-origin" => StrictOrigin,
+            "origin-when-cross-origin" => OriginWhenCrossOrigin,
+            "strict-origin-when-cross-origin" => StrictOriginWhenCrossOrigin,
+            "unsafe-url" => UnsafeUrl,
+            _ => ExtensionPolicy(token),
+        })
+    }
+
+    fn to_stream<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        writer.write(self.as_str().as_bytes())
+    }
+
+    fn http_value(&self) -> String {
+        String::from_str(self.as_str())
+    }
+}
+
+#[test]
+fn test_referrer_policy() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("no-referrer", vec!(NoReferrer));
+    assert_conversion_correct("no-referrer, strict-origin-when-cross-origin",
+                              vec!(NoReferrer, StrictOriginWhenCrossOrigin));
+    assert_interpretation_correct("Same-Origin ,x-future", vec!(SameOrigin,
+        ExtensionPolicy(String::from_str("x-future"))));
+
+    assert_invalid::<Vec<ReferrerPolicy>>("");
+    assert_invalid::<Vec<ReferrerPolicy>>("origin unsafe-url");
+    assert_invalid::<Vec<ReferrerPolicy>>("origin, , unsafe-url");
+
+    assert_eq!(effective_policy(&[Origin, ExtensionPolicy(String::from_str("x-future"))]),
+               Some(&Origin));
+    assert_eq!(effective_policy(&[ExtensionPolicy(String::from_str("x-future"))]), None);
+}
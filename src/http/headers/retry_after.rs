@@ -0,0 +1,77 @@
+//! The Retry-After response header, defined in RFC 7231, section 7.1.3.
+//!
+//! Retry-After = HTTP-date / delay-seconds
+
+use std::fmt;
+use time::Tm;
+use headers::{HeaderConvertible, parse_header_value};
+
+pub use self::RetryAfter::{Delay, Date};
+
+/// How long the client ought to wait before making another request, as with a `503 Service
+/// Unavailable` or `429 Too Many Requests` response, or before following a redirect.
+#[derive(Clone, PartialEq, Eq)]
+pub enum RetryAfter {
+    /// A number of seconds after the response was received.
+    Delay(u64),
+    /// A time, which may be in the past.
+    Date(Tm),
+}
+
+impl RetryAfter {
+    /// The number of seconds left to wait at the time `now`, in seconds since the epoch.
+    pub fn seconds_from(&self, now: i64) -> u64 {
+        match *self {
+            Delay(seconds) => seconds,
+            Date(ref tm) => {
+                let at = tm.to_timespec().sec;
+                if at > now { (at - now) as u64 } else { 0 }
+            },
+        }
+    }
+}
+
+impl fmt::Show for RetryAfter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.http_value()[])
+    }
+}
+
+impl super::HeaderConvertible for RetryAfter {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<RetryAfter> {
+        let value = reader.collect_to_string();
+        if value.len() > 0 && value.bytes().all(|b| b >= b'0' && b <= b'9') {
+            value.parse().map(Delay)
+        } else {
+            parse_header_value(&value[]).map(Date)
+        }
+    }
+
+    fn http_value(&self) -> String {
+        match *self {
+            Delay(seconds) => seconds.to_string(),
+            Date(ref tm) => tm.http_value(),
+        }
+    }
+}
+
+#[test]
+fn test_retry_after() {
+    use time;
+    use headers::test_utils::{assert_conversion_correct, assert_invalid};
+    let date: Tm = parse_header_value("Fri, 31 Dec 1999 23:59:59 GMT").unwrap();
+    assert_conversion_correct("120", Delay(120));
+    assert_conversion_correct("0", Delay(0));
+    assert_conversion_correct("Fri, 31 Dec 1999 23:59:59 GMT", Date(date.clone()));
+
+    assert_invalid::<RetryAfter>("");
+    assert_invalid::<RetryAfter>("-1");
+    assert_invalid::<RetryAfter>("1.5");
+    assert_invalid::<RetryAfter>("soon");
+
+    let at = date.to_timespec().sec;
+    assert_eq!(Date(date.clone()).seconds_from(at - 60), 60);
+    assert_eq!(Date(date).seconds_from(at + 60), 0);
+    assert_eq!(Delay(5).seconds_from(time::get_time().sec), 5);
+}
@@ -0,0 +1,125 @@
+//! The Strict-Transport-Security response header, defined in RFC 6797, section 6.1.
+//!
+//! Strict-Transport-Security = directive *( ";" [ directive ] )
+//! directive                 = directive-name [ "=" directive-value ]
+//! directive-value           = token / quoted-string
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+/// The HTTP Strict Transport Security policy of a host.
+#[derive(Clone, PartialEq, Eq)]
+pub struct StrictTransportSecurity {
+    /// The number of seconds for which the host is to be regarded as one which may only be
+    /// reached over HTTPS. Zero tells the user agent to forget the policy.
+    pub max_age: u64,
+    /// Whether the policy applies to the subdomains of the host as well.
+    pub include_subdomains: bool,
+    /// Whether the host asks to be included in browsers' built-in preload lists. This is not part
+    /// of RFC 6797, but is widely understood.
+    pub preload: bool,
+}
+
+impl StrictTransportSecurity {
+    /// A policy with the given lifetime, which applies to the host alone and is not preloaded.
+    pub fn new(max_age: u64) -> StrictTransportSecurity {
+        StrictTransportSecurity {
+            max_age: max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+}
+
+impl fmt::Show for StrictTransportSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "max-age={}", self.max_age));
+        if self.include_subdomains {
+            try!(f.write_str("; includeSubDomains"));
+        }
+        if self.preload {
+            try!(f.write_str("; preload"));
+        }
+        Ok(())
+    }
+}
+
+impl super::HeaderConvertible for StrictTransportSecurity {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<StrictTransportSecurity> {
+        let value = reader.collect_to_string();
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut preload = false;
+        let mut seen: Vec<String> = Vec::new();
+        for directive in value.split(';') {
+            let directive = directive.trim();
+            if directive.is_empty() {
+                continue;
+            }
+            let mut parts = directive.splitn(1, '=');
+            let name = parts.next().unwrap().trim().to_ascii_lowercase();
+            let value = parts.next().map(|value| value.trim());
+            // Each directive may appear at most once (RFC 6797, section 6.1, item 2).
+            if seen.contains(&name) {
+                return None;
+            }
+            match (&name[], value) {
+                ("max-age", Some(value)) => {
+                    // A quoted-string of digits has no escapes to worry about.
+                    let value = if value.len() >= 2 && value.starts_with("\"") &&
+                                       value.ends_with("\"") {
+                        &value[1..value.len() - 1]
+                    } else {
+                        value
+                    };
+                    if value.is_empty() || !value.bytes().all(|b| b >= b'0' && b <= b'9') {
+                        return None;
+                    }
+                    max_age = value.parse();
+                },
+                ("max-age", None) => return None,
+                ("includesubdomains", None) => include_subdomains = true,
+                ("preload", None) => preload = true,
+                ("includesubdomains", Some(_)) | ("preload", Some(_)) => return None,
+                // Unknown directives are ignored (RFC 6797, section 6.1, item 5).
+                _ => (),
+            }
+            seen.push(name);
+        }
+        max_age.map(|max_age| StrictTransportSecurity {
+            max_age: max_age,
+            include_subdomains: include_subdomains,
+            preload: preload,
+        })
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_strict_transport_security() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("max-age=31536000", StrictTransportSecurity::new(31536000));
+    assert_conversion_correct("max-age=0; includeSubDomains; preload", StrictTransportSecurity {
+        max_age: 0,
+        include_subdomains: true,
+        preload: true,
+    });
+    assert_interpretation_correct("includesubdomains ;MAX-AGE=\"60\"; x-unknown=1;",
+                                  StrictTransportSecurity {
+        max_age: 60,
+        include_subdomains: true,
+        preload: false,
+    });
+
+    assert_invalid::<StrictTransportSecurity>("");
+    assert_invalid::<StrictTransportSecurity>("includeSubDomains");
+    assert_invalid::<StrictTransportSecurity>("max-age");
+    assert_invalid::<StrictTransportSecurity>("max-age=-1");
+    assert_invalid::<StrictTransportSecurity>("max-age=1; max-age=2");
+    assert_invalid::<StrictTransportSecurity>("max-age=1; includeSubDomains=yes");
+}
@@ -0,0 +1,45 @@
+//! The X-Content-Type-Options response header, defined in the Fetch standard, section 3.5.
+//!
+//! X-Content-Type-Options = "nosniff"
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+/// The only value of X-Content-Type-Options: the user agent must not sniff the media type of the
+/// response, but use the Content-Type given.
+#[derive(Clone, PartialEq, Eq, Copy)]
+pub struct NoSniff;
+
+impl fmt::Show for NoSniff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("nosniff")
+    }
+}
+
+impl super::HeaderConvertible for NoSniff {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>) -> Option<NoSniff> {
+        // Only the first value of a list counts (Fetch, section 3.5).
+        let value = reader.collect_to_string();
+        match value.split(',').next() {
+            Some(first) if first.trim().eq_ignore_ascii_case("nosniff") => Some(NoSniff),
+            _ => None,
+        }
+    }
+
+    fn http_value(&self) -> String {
+        String::from_str("nosniff")
+    }
+}
+
+#[test]
+fn test_x_content_type_options() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("nosniff", NoSniff);
+    assert_interpretation_correct("NoSniff", NoSniff);
+    assert_interpretation_correct("nosniff, foo", NoSniff);
+
+    assert_invalid::<NoSniff>("");
+    assert_invalid::<NoSniff>("sniff");
+    assert_invalid::<NoSniff>("foo, nosniff");
+}
@@ -0,0 +1,71 @@
+//! The X-Frame-Options response header, defined in RFC 7034, section 2.1.
+//!
+//! X-Frame-Options = "DENY" / "SAMEORIGIN" / ( "ALLOW-FROM" RWS SERIALIZED-ORIGIN )
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+pub use self::XFrameOptions::{Deny, SameOrigin, AllowFrom};
+
+/// Whether, and by whom, a response may be displayed in a frame.
+#[derive(Clone, PartialEq, Eq)]
+pub enum XFrameOptions {
+    /// The response may not be framed at all.
+    Deny,
+    /// The response may only be framed by pages of the same origin.
+    SameOrigin,
+    /// The response may only be framed by pages of the given origin, such as
+    /// `https://example.com`. Few browsers ever supported this; Content-Security-Policy's
+    /// `frame-ancestors` directive is the modern replacement.
+    AllowFrom(String),
+}
+
+impl fmt::Show for XFrameOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Deny => f.write_str("DENY"),
+            SameOrigin => f.write_str("SAMEORIGIN"),
+            AllowFrom(ref origin) => write!(f, "ALLOW-FROM {}", origin),
+        }
+    }
+}
+
+impl super::HeaderConvertible for XFrameOptions {
+    fn from_stream<R: Reader>(reader: &mut super::HeaderValueByteIterator<R>)
+            -> Option<XFrameOptions> {
+        let value = reader.collect_to_string();
+        let value = value.trim();
+        let mut parts = value.splitn(1, |c: char| c == ' ' || c == '\t');
+        let keyword = parts.next().unwrap().to_ascii_lowercase();
+        let origin = parts.next().map(|origin| origin.trim()).unwrap_or("");
+        match (&keyword[], origin) {
+            ("deny", "") => Some(Deny),
+            ("sameorigin", "") => Some(SameOrigin),
+            ("allow-from", origin) if origin != "" => Some(AllowFrom(String::from_str(origin))),
+            _ => None,
+        }
+    }
+
+    fn http_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[test]
+fn test_x_frame_options() {
+    use headers::test_utils::{assert_conversion_correct, assert_interpretation_correct,
+                              assert_invalid};
+    assert_conversion_correct("DENY", Deny);
+    assert_conversion_correct("SAMEORIGIN", SameOrigin);
+    assert_conversion_correct("ALLOW-FROM https://example.com",
+                              AllowFrom(String::from_str("https://example.com")));
+    assert_interpretation_correct("deny", Deny);
+    assert_interpretation_correct("SameOrigin ", SameOrigin);
+    assert_interpretation_correct("allow-from  https://example.com",
+                                  AllowFrom(String::from_str("https://example.com")));
+
+    assert_invalid::<XFrameOptions>("");
+    assert_invalid::<XFrameOptions>("ALLOWALL");
+    assert_invalid::<XFrameOptions>("DENY SAMEORIGIN");
+    assert_invalid::<XFrameOptions>("ALLOW-FROM");
+}
@@ -10,11 +10,15 @@
 extern crate url;
 extern crate time;
 extern crate collections;
+extern crate "rustc-serialize" as rustc_serialize;
+#[cfg(feature = "ssl")] extern crate openssl;
 
 pub mod buffer;
 pub mod client;
 pub mod common;
 pub mod connecter;
+pub mod form;
+pub mod multipart;
 pub mod server;
 pub mod method;
 pub mod headers;
@@ -87,4 +87,23 @@ impl Method {
             _         => ExtensionMethod(String::from_str(method)),
         })
     }
+
+    /// Whether the method is idempotent (RFC 7231, section 4.2.2): sending a request several times
+    /// has the same effect as sending it once, so it can be sent again if the response is lost.
+    pub fn is_idempotent(&self) -> bool {
+        match *self {
+            Options | Get | Head | Put | Delete | Trace => true,
+            Post | Connect | Patch | ExtensionMethod(_) => false,
+        }
+    }
+}
+
+#[test]
+fn test_is_idempotent() {
+    assert!(Get.is_idempotent());
+    assert!(Put.is_idempotent());
+    assert!(Delete.is_idempotent());
+    assert!(!Post.is_idempotent());
+    assert!(!Patch.is_idempotent());
+    assert!(!ExtensionMethod(String::from_str("LOCK")).is_idempotent());
 }
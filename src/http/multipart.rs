@@ -0,0 +1,670 @@
+/*!
+
+Multipart bodies in the `multipart/form-data` format (RFC 7578), as used by HTML forms which upload
+files.
+
+`Multipart` parses such a body from any `Reader`, part by part. Each `Part` carries its own
+headers (the field name and file name from its Content-Disposition, and its Content-Type) and is
+itself a `Reader` of its content. `MultipartLimits` bounds the size of each part, of the whole body
+and of the part headers, and the number of parts, as they are parsed.
+
+On the server, `Request::multipart` parses the body of a request. That body has already been read
+into memory in full when the request was loaded, so the limit on how much a client can make the
+server hold is `Config::max_body_size`, not `MultipartLimits`.
+
+```rust,ignore
+fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+    let mut multipart = match request.multipart() {
+        Ok(multipart) => multipart,
+        Err(_) => { /* 400 Bad Request */ return; },
+    };
+    while let Some(mut part) = multipart.next_part().unwrap() {
+        match part.filename() {
+            Some(_) => { part.save(&Path::new("/var/uploads/upload.bin")).unwrap(); },
+            None => println!("{:?} = {}", part.name(), part.read_to_string().unwrap()),
+        }
+    }
+}
+```
+
+On the client, a `MultipartBody` is built up of fields and files, and sent with
+`RequestWriter::write_multipart`. Files added by path are only read as the body is written.
+
+*/
+
+use std::ascii::AsciiExt;
+use std::cmp::min;
+use std::io::{EndOfFile, File, InvalidInput, IoError, IoResult};
+use std::io::fs::PathExtensions;
+use std::rand;
+
+use headers::{Extensions, HeaderConvertible, parse_header_value};
+use headers::content_disposition::ContentDisposition;
+use headers::content_type::MediaType;
+use server::static_files::guess_media_type;
+
+/// The size of the reads made from the underlying reader.
+const CHUNK_SIZE: usize = 8192;
+
+/// Limits upon a multipart body, to bound the resources a client can make the server spend on it.
+#[derive(Clone, Copy)]
+pub struct MultipartLimits {
+    /// The maximum size of the content of any one part, in bytes; 10 MiB by default.
+    pub max_part_size: u64,
+    /// The maximum size of the whole body, in bytes; 50 MiB by default.
+    pub max_total_size: u64,
+    /// The maximum number of parts; a hundred by default.
+    pub max_parts: usize,
+    /// The maximum size of the headers of any one part, in bytes; 8 KiB by default.
+    pub max_header_size: usize,
+}
+
+impl MultipartLimits {
+    pub fn new() -> MultipartLimits {
+        MultipartLimits {
+            max_part_size: 10 * 1024 * 1024,
+            max_total_size: 50 * 1024 * 1024,
+            max_parts: 100,
+            max_header_size: 8 * 1024,
+        }
+    }
+}
+
+/// The boundary of a `multipart/form-data` body with the given Content-Type, or `None` if it is
+/// not such a body.
+pub fn boundary(content_type: Option<&MediaType>) -> Option<String> {
+    match content_type {
+        Some(content_type) if content_type.type_[].eq_ignore_ascii_case("multipart") &&
+                content_type.subtype[].eq_ignore_ascii_case("form-data") => {
+            content_type.parameters.iter()
+                        .find(|&&(ref name, _)| name[].eq_ignore_ascii_case("boundary"))
+                        .map(|&(_, ref value)| value.clone())
+                        .and_then(|value| if valid_boundary(&value[]) { Some(value) } else { None })
+        },
+        _ => None,
+    }
+}
+
+/// Whether a boundary is valid: 1 to 70 characters of those allowed by RFC 2046, section 5.1.1,
+/// not ending with a space.
+fn valid_boundary(boundary: &str) -> bool {
+    boundary.len() >= 1 && boundary.len() <= 70 && !boundary.ends_with(" ") &&
+        boundary.bytes().all(|b| match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'\'' | b'(' | b')' | b'+' | b'_' | b',' |
+            b'-' | b'.' | b'/' | b':' | b'=' | b'?' | b' ' => true,
+            _ => false,
+        })
+}
+
+fn multipart_error(desc: &'static str, detail: Option<String>) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: desc,
+        detail: detail,
+    }
+}
+
+/// Find the first occurrence of `needle` in `haystack`.
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.len() > haystack.len() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Where the parser is in the body.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Before the first boundary.
+    Preamble,
+    /// In the content of a part.
+    InPart,
+    /// Just after a boundary, before the headers of the next part (or the closing `--`).
+    AfterBoundary,
+    /// After the closing boundary.
+    Finished,
+}
+
+/// A streaming parser of a `multipart/form-data` body.
+pub struct Multipart<R> {
+    reader: R,
+    /// `CR LF -- boundary`, which ends the content of each part.
+    delimiter: Vec<u8>,
+    limits: MultipartLimits,
+    /// Bytes read but not yet consumed, from `pos` on.
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    state: State,
+    parts: usize,
+    part_size: u64,
+    total_size: u64,
+}
+
+impl<R: Reader> Multipart<R> {
+    /// Parse the body read from `reader`, whose parts are separated by `boundary`.
+    pub fn new(reader: R, boundary: &str, limits: MultipartLimits) -> Multipart<R> {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.push_all(boundary.as_bytes());
+        Multipart {
+            reader: reader,
+            delimiter: delimiter,
+            limits: limits,
+            // The first boundary need not follow a line break; pretending that one comes before
+            // the body lets it be found like the rest.
+            buf: b"\r\n".to_vec(),
+            pos: 0,
+            eof: false,
+            state: State::Preamble,
+            parts: 0,
+            part_size: 0,
+            total_size: 0,
+        }
+    }
+
+    /// The next part of the body, or `None` once the closing boundary has been reached. Whatever
+    /// was left unread of the previous part is skipped.
+    pub fn next_part<'a>(&'a mut self) -> IoResult<Option<Part<'a, R>>> {
+        loop {
+            match self.state {
+                State::Preamble | State::InPart => try!(self.skip_content()),
+                State::AfterBoundary => break,
+                State::Finished => return Ok(None),
+            }
+        }
+
+        // The boundary is followed either by `--` to close the body or by optional white space
+        // and a line break.
+        try!(self.fill(2));
+        if self.available() >= 2 && &self.buf[self.pos..self.pos + 2] == b"--" {
+            self.state = State::Finished;
+            return Ok(None);
+        }
+        let line = try!(self.read_line());
+        if !line.iter().all(|&b| b == b' ' || b == b'\t') {
+            return Err(multipart_error("malformed multipart boundary", None));
+        }
+
+        self.parts += 1;
+        if self.parts > self.limits.max_parts {
+            return Err(multipart_error("too many multipart parts", None));
+        }
+        let headers = try!(self.read_headers());
+        self.state = State::InPart;
+        self.part_size = 0;
+        Ok(Some(Part {
+            headers: headers,
+            multipart: self,
+        }))
+    }
+
+    fn available(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Read until at least `wanted` bytes are available, or the end of the input is reached.
+    fn fill(&mut self, wanted: usize) -> IoResult<()> {
+        while self.available() < wanted && !self.eof {
+            if self.pos > 0 && self.pos >= self.buf.len() / 2 {
+                self.buf = self.buf[self.pos..].to_vec();
+                self.pos = 0;
+            }
+            match self.reader.push(CHUNK_SIZE, &mut self.buf) {
+                Ok(_) => (),
+                Err(ref e) if e.kind == EndOfFile => self.eof = true,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Consume `n` bytes, counting them against the total size.
+    fn consume(&mut self, n: usize) -> IoResult<()> {
+        self.pos += n;
+        self.total_size += n as u64;
+        if self.total_size > self.limits.max_total_size {
+            return Err(multipart_error("multipart body too large", None));
+        }
+        Ok(())
+    }
+
+    /// Read a line of the part headers, without its line ending (CR LF or a bare LF).
+    fn read_line(&mut self) -> IoResult<Vec<u8>> {
+        let mut searched = 0;
+        loop {
+            match self.buf[self.pos + searched..].iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    let end = self.pos + searched + i;
+                    let mut line = self.buf[self.pos..end].to_vec();
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    try!(self.consume(end + 1 - self.pos));
+                    return Ok(line);
+                },
+                None => searched = self.available(),
+            }
+            if searched > self.limits.max_header_size {
+                return Err(multipart_error("multipart part headers too large", None));
+            }
+            if self.eof {
+                return Err(multipart_error("unexpected end of multipart body", None));
+            }
+            let wanted = searched + 1;
+            try!(self.fill(wanted));
+        }
+    }
+
+    /// Read the headers of a part, up to and including the empty line which ends them.
+    fn read_headers(&mut self) -> IoResult<PartHeaders> {
+        let mut headers = PartHeaders::new();
+        let mut lines: Vec<Vec<u8>> = Vec::new();
+        let mut size = 0;
+        loop {
+            let line = try!(self.read_line());
+            size += line.len() + 2;
+            if size > self.limits.max_header_size {
+                return Err(multipart_error("multipart part headers too large", None));
+            }
+            if line.is_empty() {
+                break;
+            }
+            // A line beginning with white space continues the previous one (obsolete folding).
+            if line[0] == b' ' || line[0] == b'\t' {
+                match lines.last_mut() {
+                    Some(previous) => {
+                        previous.push_all(&line[]);
+                        continue;
+                    },
+                    None => return Err(multipart_error("malformed multipart part header", None)),
+                }
+            }
+            lines.push(line);
+        }
+        for line in lines.iter() {
+            let line = String::from_utf8_lossy(&line[]).into_owned();
+            let colon = match line.find(':') {
+                Some(colon) if colon > 0 => colon,
+                _ => return Err(multipart_error("malformed multipart part header", Some(line))),
+            };
+            let name = line[..colon].trim();
+            let value = line[colon + 1..].trim();
+            if name.eq_ignore_ascii_case("content-disposition") {
+                headers.content_disposition = parse_header_value(value);
+            } else if name.eq_ignore_ascii_case("content-type") {
+                headers.content_type = parse_header_value(value);
+            } else {
+                headers.extensions.append(String::from_str(name), String::from_str(value));
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Read content of the current part into `buf`, returning `EndOfFile` at the end of the part.
+    fn read_content(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.state != State::InPart && self.state != State::Preamble {
+            return Err(IoError {
+                kind: EndOfFile,
+                desc: "end of multipart part",
+                detail: None,
+            });
+        }
+        let delimiter_len = self.delimiter.len();
+        try!(self.fill(delimiter_len + 1));
+        let (end, found) = match find_bytes(&self.buf[self.pos..], &self.delimiter[]) {
+            Some(i) => (i, true),
+            // Anything but the last few bytes cannot be the start of the delimiter.
+            None if !self.eof => (self.available() + 1 - delimiter_len, false),
+            None => return Err(multipart_error("unexpected end of multipart body", None)),
+        };
+        if end == 0 && found {
+            try!(self.consume(delimiter_len));
+            self.state = State::AfterBoundary;
+            return self.read_content(buf);
+        }
+        let n = min(end, buf.len());
+        buf[..n].clone_from_slice(&self.buf[self.pos..self.pos + n]);
+        try!(self.consume(n));
+        if self.state == State::InPart {
+            self.part_size += n as u64;
+            if self.part_size > self.limits.max_part_size {
+                return Err(multipart_error("multipart part too large", None));
+            }
+        }
+        Ok(n)
+    }
+
+    /// Skip the rest of the preamble or of the current part.
+    fn skip_content(&mut self) -> IoResult<()> {
+        let mut buf = [0u8; CHUNK_SIZE];
+        loop {
+            match self.read_content(&mut buf) {
+                Ok(_) => (),
+                Err(ref e) if e.kind == EndOfFile => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// The headers of a part. Only Content-Disposition and Content-Type are meaningful in
+/// `multipart/form-data`; any others are kept as they are in `extensions`.
+#[derive(Clone)]
+pub struct PartHeaders {
+    pub content_disposition: Option<ContentDisposition>,
+    pub content_type: Option<MediaType>,
+    pub extensions: Extensions,
+}
+
+impl PartHeaders {
+    pub fn new() -> PartHeaders {
+        PartHeaders {
+            content_disposition: None,
+            content_type: None,
+            extensions: Extensions::new(),
+        }
+    }
+}
+
+/// A part of a multipart body: its headers, and a `Reader` of its content.
+pub struct Part<'a, R: 'a> {
+    pub headers: PartHeaders,
+    multipart: &'a mut Multipart<R>,
+}
+
+impl<'a, R: Reader> Part<'a, R> {
+    /// The name of the form field.
+    pub fn name(&self) -> Option<&str> {
+        self.headers.content_disposition.as_ref().and_then(|cd| cd.name())
+    }
+
+    /// The name of the uploaded file, if the part is a file. This is only a suggestion from the
+    /// client; see `ContentDisposition::filename`.
+    pub fn filename(&self) -> Option<String> {
+        self.headers.content_disposition.as_ref().and_then(|cd| cd.filename())
+    }
+
+    /// The Content-Type of the part; `text/plain` if it does not give one.
+    pub fn content_type(&self) -> MediaType {
+        match self.headers.content_type {
+            Some(ref content_type) => content_type.clone(),
+            None => MediaType::new(String::from_str("text"), String::from_str("plain"),
+                                   Vec::new()),
+        }
+    }
+
+    /// Write the rest of the content to a new file at `path`, returning the number of bytes
+    /// written.
+    pub fn save(&mut self, path: &Path) -> IoResult<u64> {
+        let mut file = try!(File::create(path));
+        let mut buf = [0u8; CHUNK_SIZE];
+        let mut written = 0;
+        loop {
+            match self.read(&mut buf) {
+                Ok(n) => {
+                    try!(file.write(&buf[..n]));
+                    written += n as u64;
+                },
+                Err(ref e) if e.kind == EndOfFile => return Ok(written),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<'a, R: Reader> Reader for Part<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.multipart.read_content(buf)
+    }
+}
+
+/// The content of a part of a `MultipartBody`.
+#[derive(Clone)]
+enum Content {
+    Bytes(Vec<u8>),
+    /// A file, read as the body is written, and its size.
+    File(Path, u64),
+}
+
+/// A `multipart/form-data` body to send, built up of fields and files.
+#[derive(Clone)]
+pub struct MultipartBody {
+    boundary: String,
+    /// The headers of each part, with its boundary, and its content.
+    parts: Vec<(String, Content)>,
+}
+
+impl MultipartBody {
+    /// An empty body, with a random boundary.
+    pub fn new() -> MultipartBody {
+        MultipartBody::with_boundary(format!("{:016x}{:016x}", rand::random::<u64>(),
+                                             rand::random::<u64>()))
+    }
+
+    /// An empty body with the given boundary, which must not occur in any of the content.
+    pub fn with_boundary(boundary: String) -> MultipartBody {
+        assert!(valid_boundary(&boundary[]));
+        MultipartBody {
+            boundary: boundary,
+            parts: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, disposition: ContentDisposition, content_type: Option<MediaType>,
+            content: Content) {
+        let mut headers = format!("--{}\r\nContent-Disposition: {}\r\n", self.boundary,
+                                  disposition.http_value());
+        match content_type {
+            Some(content_type) => {
+                headers.push_str(&format!("Content-Type: {}\r\n", content_type.http_value())[]);
+            },
+            None => (),
+        }
+        headers.push_str("\r\n");
+        self.parts.push((headers, content));
+    }
+
+    /// Add a text field.
+    pub fn add_field(&mut self, name: &str, value: &str) {
+        self.push(ContentDisposition::form_data(name, None), None,
+                  Content::Bytes(value.as_bytes().to_vec()));
+    }
+
+    /// Add a file with the given content.
+    pub fn add_file(&mut self, name: &str, filename: &str, content_type: MediaType,
+                    content: Vec<u8>) {
+        self.push(ContentDisposition::form_data(name, Some(filename)), Some(content_type),
+                  Content::Bytes(content));
+    }
+
+    /// Add the file at `path`, with its Content-Type guessed from its extension. The file is not
+    /// read until the body is written.
+    pub fn add_file_path(&mut self, name: &str, path: &Path) -> IoResult<()> {
+        let size = try!(path.stat()).size;
+        let filename = path.filename_str().unwrap_or("file");
+        self.push(ContentDisposition::form_data(name, Some(filename)),
+                  Some(guess_media_type(path)), Content::File(path.clone(), size));
+        Ok(())
+    }
+
+    /// The Content-Type of the body, giving its boundary.
+    pub fn media_type(&self) -> MediaType {
+        MediaType::new(String::from_str("multipart"), String::from_str("form-data"),
+                       vec!((String::from_str("boundary"), self.boundary.clone())))
+    }
+
+    fn closing(&self) -> String {
+        format!("--{}--\r\n", self.boundary)
+    }
+
+    /// The length of the encoded body, in bytes.
+    pub fn len(&self) -> u64 {
+        let mut total = self.closing().len() as u64;
+        for &(ref headers, ref content) in self.parts.iter() {
+            let size = match *content {
+                Content::Bytes(ref bytes) => bytes.len() as u64,
+                Content::File(_, size) => size,
+            };
+            total += headers.len() as u64 + size + 2;
+        }
+        total
+    }
+
+    /// Write the encoded body.
+    pub fn write_to<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        for &(ref headers, ref content) in self.parts.iter() {
+            try!(writer.write(headers.as_bytes()));
+            match *content {
+                Content::Bytes(ref bytes) => try!(writer.write(&bytes[])),
+                Content::File(ref path, size) => {
+                    // Exactly as many bytes as were promised, lest the file have changed since.
+                    let mut file = try!(File::open(path));
+                    let mut buf = [0u8; CHUNK_SIZE];
+                    let mut remaining = size;
+                    while remaining > 0 {
+                        let want = min(remaining, buf.len() as u64) as usize;
+                        let n = try!(file.read(&mut buf[..want]));
+                        try!(writer.write(&buf[..n]));
+                        remaining -= n as u64;
+                    }
+                },
+            }
+            try!(writer.write(b"\r\n"));
+        }
+        writer.write(self.closing().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufReader, MemWriter, InvalidInput};
+    use headers::content_type::MediaType;
+    use super::{Multipart, MultipartBody, MultipartLimits, boundary};
+
+    static BODY: &'static [u8] = b"preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Holiday\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach.jpg\"\r\n\
+        Content-Type: image/jpeg\r\n\
+        X-Extra: 1\r\n\
+        \r\n\
+        \xff\xd8--Xy\r\n--Xy\xff\xd9\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    fn media_type(parameters: Vec<(String, String)>) -> MediaType {
+        MediaType::new(String::from_str("multipart"), String::from_str("form-data"), parameters)
+    }
+
+    #[test]
+    fn test_boundary() {
+        let boundary_param = |b: &str| vec!((String::from_str("boundary"), String::from_str(b)));
+        assert_eq!(boundary(Some(&media_type(boundary_param("XyZ")))),
+                   Some(String::from_str("XyZ")));
+        assert_eq!(boundary(Some(&media_type(Vec::new()))), None);
+        assert_eq!(boundary(Some(&media_type(boundary_param("a\"b")))), None);
+        assert_eq!(boundary(Some(&media_type(boundary_param("")))), None);
+        assert_eq!(boundary(None), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut multipart = Multipart::new(BufReader::new(BODY), "XyZ", MultipartLimits::new());
+        {
+            let mut part = multipart.next_part().unwrap().unwrap();
+            assert_eq!(part.name(), Some("title"));
+            assert_eq!(part.filename(), None);
+            assert_eq!(part.content_type().subtype, "plain");
+            assert_eq!(part.read_to_string().unwrap(), "Holiday");
+        }
+        {
+            // Read in small pieces, so that the delimiter is split across reads.
+            let mut part = multipart.next_part().unwrap().unwrap();
+            assert_eq!(part.name(), Some("photo"));
+            assert_eq!(part.filename(), Some(String::from_str("beach.jpg")));
+            assert_eq!(part.content_type().subtype, "jpeg");
+            assert_eq!(part.headers.extensions.get("x-extra"), Some("1"));
+            let mut content = Vec::new();
+            let mut buf = [0u8; 3];
+            loop {
+                match part.read(&mut buf) {
+                    Ok(n) => content.push_all(&buf[..n]),
+                    Err(_) => break,
+                }
+            }
+            assert_eq!(content, b"\xff\xd8--Xy\r\n--Xy\xff\xd9".to_vec());
+        }
+        assert!(multipart.next_part().unwrap().is_none());
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_skip_unread_parts() {
+        let mut multipart = Multipart::new(BufReader::new(BODY), "XyZ", MultipartLimits::new());
+        assert!(multipart.next_part().unwrap().is_some());
+        let name = multipart.next_part().unwrap().unwrap().name().map(|n| String::from_str(n));
+        assert_eq!(name, Some(String::from_str("photo")));
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = MultipartLimits { max_part_size: 4, ..MultipartLimits::new() };
+        let mut multipart = Multipart::new(BufReader::new(BODY), "XyZ", limits);
+        assert_eq!(multipart.next_part().unwrap().unwrap().read_to_end().unwrap_err().kind,
+                   InvalidInput);
+
+        let limits = MultipartLimits { max_parts: 1, ..MultipartLimits::new() };
+        let mut multipart = Multipart::new(BufReader::new(BODY), "XyZ", limits);
+        assert!(multipart.next_part().is_ok());
+        assert!(multipart.next_part().is_err());
+
+        let limits = MultipartLimits { max_header_size: 32, ..MultipartLimits::new() };
+        let mut multipart = Multipart::new(BufReader::new(BODY), "XyZ", limits);
+        assert!(multipart.next_part().is_err());
+
+        let limits = MultipartLimits { max_total_size: 80, ..MultipartLimits::new() };
+        let mut multipart = Multipart::new(BufReader::new(BODY), "XyZ", limits);
+        assert!(multipart.next_part().is_ok());
+        assert!(multipart.next_part().is_err());
+
+        let mut multipart = Multipart::new(BufReader::new(&BODY[..70]), "XyZ",
+                                           MultipartLimits::new());
+        assert!(multipart.next_part().unwrap().unwrap().read_to_end().is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let mut body = MultipartBody::with_boundary(String::from_str("XyZ"));
+        body.add_field("title", "Holiday");
+        body.add_file("photo", "beach.jpg",
+                      MediaType::new(String::from_str("image"), String::from_str("jpeg"),
+                                     Vec::new()),
+                      b"\xff\xd8\xff\xd9".to_vec());
+        let mut writer = MemWriter::new();
+        body.write_to(&mut writer).unwrap();
+        let written = writer.into_inner();
+        assert_eq!(written.len() as u64, body.len());
+        assert_eq!(&written[], b"--XyZ\r\n\
+            Content-Disposition: form-data;name=title\r\n\
+            \r\n\
+            Holiday\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data;name=photo;filename=beach.jpg\r\n\
+            Content-Type: image/jpeg\r\n\
+            \r\n\
+            \xff\xd8\xff\xd9\r\n\
+            --XyZ--\r\n");
+        assert_eq!(body.media_type().parameters[0].1, "XyZ");
+
+        let mut multipart = Multipart::new(BufReader::new(&written[]), "XyZ",
+                                           MultipartLimits::new());
+        assert_eq!(multipart.next_part().unwrap().unwrap().read_to_string().unwrap(), "Holiday");
+        assert_eq!(multipart.next_part().unwrap().unwrap().read_to_end().unwrap(),
+                   b"\xff\xd8\xff\xd9".to_vec());
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+}
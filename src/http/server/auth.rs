@@ -0,0 +1,450 @@
+/*!
+
+HTTP authentication for server handlers (RFC 7235).
+
+An `Authentication` value holds the realm, the schemes to offer and a `CredentialStore` of user
+names and passwords. It is set up once, held in the `Server`, and its `authenticate` method called
+for each request needing authentication: if the request carries valid credentials, the user name
+is returned; otherwise a `401 Unauthorized` response challenging the client is written and `None`
+returned, and the handler should write nothing more.
+
+```rust,ignore
+#[derive(Clone)]
+struct App {
+    auth: Authentication,
+}
+
+impl Server for App {
+    fn get_config(&self) -> Config { /* ... */ }
+
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        let user = match self.auth.authenticate(&request, response) {
+            Some(user) => user,
+            None => return,
+        };
+        response.write_content_auto(/* ... */);
+    }
+}
+```
+
+Alternatively, `RequireAuth` wraps a whole `Server`, passing on only authenticated requests.
+
+Digest (RFC 7616) is offered with SHA-256 and MD5 when the `ssl` feature is enabled, with
+`qop=auth`. Nonces are issued by the server and remembered for `nonce_lifetime` seconds, along with
+the highest nonce count seen with each, so that replayed credentials are rejected; credentials with
+an expired nonce are answered with a fresh challenge marked `stale=true`, which clients answer
+without asking the user again. Every challenge issues a nonce, so at most `max_nonces` are
+remembered, the oldest being forgotten (and so treated as expired) to make way for new ones. Basic
+(RFC 7617) is offered too by default; as it sends the password in the clear, turn it off unless the
+server is only reachable over HTTPS.
+
+*/
+
+use std::collections::HashMap;
+use std::rand::{OsRng, Rng};
+use std::sync::{Arc, Mutex};
+use time;
+use rustc_serialize::base64::{ToBase64, URL_SAFE};
+
+use headers::authorization::{Credentials, Challenge, Challenges, AuthParams, DigestAlgorithm};
+use server::{Server, Config};
+use server::request::Request;
+use server::request::RequestUri::{AbsolutePath, AbsoluteUri};
+use server::response::ResponseWriter;
+use status;
+
+/// Somewhere to look up the passwords of users.
+pub trait CredentialStore: Send + Sync {
+    /// The password of a user, or `None` if there is no such user.
+    fn password(&self, username: &str) -> Option<String>;
+}
+
+/// A `CredentialStore` held in memory.
+#[derive(Clone)]
+pub struct MemoryCredentials {
+    users: HashMap<String, String>,
+}
+
+impl MemoryCredentials {
+    /// An empty store.
+    pub fn new() -> MemoryCredentials {
+        MemoryCredentials {
+            users: HashMap::new(),
+        }
+    }
+
+    /// Add a user, replacing any existing user of that name.
+    pub fn insert(&mut self, username: String, password: String) {
+        self.users.insert(username, password);
+    }
+}
+
+impl CredentialStore for MemoryCredentials {
+    fn password(&self, username: &str) -> Option<String> {
+        self.users.get(username).map(|password| password.clone())
+    }
+}
+
+/// The result of checking the credentials of a request.
+#[derive(PartialEq, Eq, Show)]
+enum Verification {
+    Verified(String),
+    /// The Digest credentials are correct but their nonce has expired.
+    Stale,
+    Failed,
+}
+
+/// The nonces issued, with when they were issued and the highest nonce count used with each.
+type Nonces = HashMap<String, (i64, u32)>;
+
+/// Authentication of the requests to a server.
+#[derive(Clone)]
+pub struct Authentication {
+    /// The protection space, shown to users by browsers.
+    pub realm: String,
+    /// Whether to offer Basic authentication; `true` by default.
+    pub basic: bool,
+    /// The Digest algorithms to offer, in order of preference; SHA-256 and MD5 by default (none
+    /// without the `ssl` feature).
+    pub digest: Vec<DigestAlgorithm>,
+    /// The number of seconds for which a Digest nonce may be used; five minutes by default.
+    pub nonce_lifetime: i64,
+    /// The most Digest nonces to remember at once; 10000 by default.
+    pub max_nonces: usize,
+    store: Arc<Box<CredentialStore + Send + Sync>>,
+    nonces: Arc<Mutex<Nonces>>,
+}
+
+impl Authentication {
+    /// Authentication against the users of `store`, with the default schemes.
+    pub fn new<T: CredentialStore + Send + Sync>(realm: &str, store: T) -> Authentication {
+        Authentication {
+            realm: String::from_str(realm),
+            basic: true,
+            digest: default_digest(),
+            nonce_lifetime: 5 * 60,
+            max_nonces: 10000,
+            store: Arc::new(Box::new(store) as Box<CredentialStore + Send + Sync>),
+            nonces: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Check the credentials of a request, returning the user name if they are valid. If they are
+    /// not, or there are none, a `401 Unauthorized` response is written.
+    ///
+    /// This must be called before the response headers are written.
+    pub fn authenticate(&self, request: &Request, response: &mut ResponseWriter)
+            -> Option<String> {
+        let stale = match self.verify(request, time::get_time().sec) {
+            Verification::Verified(username) => return Some(username),
+            Verification::Stale => true,
+            Verification::Failed => false,
+        };
+        response.status = status::Status::Unauthorized;
+        response.headers.www_authenticate =
+            Some(self.challenges(stale, time::get_time().sec));
+        response.headers.content_length = Some(0);
+        // Like any other handler, the challenge ignores a failure to write.
+        let _ = response.write_headers();
+        None
+    }
+
+    fn verify(&self, request: &Request, now: i64) -> Verification {
+        match request.headers.authorization {
+            Some(ref credentials) if credentials.is_scheme("Basic") && self.basic => {
+                match credentials.basic_user_pass() {
+                    Some((username, password)) => {
+                        match self.store.password(&username[]) {
+                            Some(ref expected) if constant_time_eq(expected.as_bytes(),
+                                                                   password.as_bytes()) => {
+                                Verification::Verified(username)
+                            },
+                            _ => Verification::Failed,
+                        }
+                    },
+                    None => Verification::Failed,
+                }
+            },
+            Some(ref credentials) if credentials.is_scheme("Digest") => {
+                self.verify_digest(credentials, request, now)
+            },
+            _ => Verification::Failed,
+        }
+    }
+
+    fn verify_digest(&self, credentials: &Credentials, request: &Request, now: i64)
+            -> Verification {
+        let param = |name| credentials.param(name);
+        let (username, realm, nonce, uri, response, nc, cnonce) =
+            match (param("username"), param("realm"), param("nonce"), param("uri"),
+                   param("response"), param("nc"), param("cnonce")) {
+                (Some(u), Some(r), Some(n), Some(i), Some(s), Some(c), Some(k)) => {
+                    (u, r, n, i, s, c, k)
+                },
+                _ => return Verification::Failed,
+            };
+        let algorithm = match DigestAlgorithm::from_param(param("algorithm")) {
+            Some(algorithm) if self.digest.contains(&algorithm) => algorithm,
+            _ => return Verification::Failed,
+        };
+        if param("qop") != Some("auth") || realm != &self.realm[] ||
+                !request_uri_matches(request, uri) {
+            return Verification::Failed;
+        }
+        let nonce_count = match ::std::num::from_str_radix::<u32>(nc, 16) {
+            Some(nonce_count) if nc.len() == 8 => nonce_count,
+            _ => return Verification::Failed,
+        };
+        let password = match self.store.password(username) {
+            Some(password) => password,
+            None => return Verification::Failed,
+        };
+        let expected = digest_response(algorithm, username, &self.realm[], &password[], nonce,
+                                       nc, cnonce, &format!("{:?}", request.method)[], uri);
+        if !constant_time_eq(expected.as_bytes(), response.as_bytes()) {
+            return Verification::Failed;
+        }
+
+        let mut nonces = self.nonces.lock().unwrap();
+        match nonces.get_mut(nonce) {
+            Some(&mut (issued, _)) if issued + self.nonce_lifetime <= now => Verification::Stale,
+            Some(&mut (_, ref mut last_count)) => {
+                // Each request must use a higher nonce count than the last, or it is a replay.
+                if nonce_count <= *last_count {
+                    Verification::Failed
+                } else {
+                    *last_count = nonce_count;
+                    Verification::Verified(String::from_str(username))
+                }
+            },
+            // Perhaps the server has been restarted since it issued the nonce.
+            None => Verification::Stale,
+        }
+    }
+
+    /// The challenges to send, issuing a new nonce for the Digest challenges.
+    fn challenges(&self, stale: bool, now: i64) -> Challenges {
+        let mut challenges = Vec::new();
+        if !self.digest.is_empty() {
+            let nonce = self.new_nonce(now);
+            let param = |name: &str, value: &str| (String::from_str(name), String::from_str(value));
+            for algorithm in self.digest.iter() {
+                let mut params = vec!(param("realm", &self.realm[]),
+                                      param("qop", "auth"),
+                                      param("algorithm", algorithm.name()),
+                                      param("nonce", &nonce[]));
+                if stale {
+                    params.push(param("stale", "true"));
+                }
+                challenges.push(Challenge {
+                    scheme: String::from_str("Digest"),
+                    data: AuthParams(params),
+                });
+            }
+        }
+        if self.basic {
+            challenges.push(Challenge::basic(&self.realm[]));
+        }
+        Challenges(challenges)
+    }
+
+    fn new_nonce(&self, now: i64) -> String {
+        let mut rng = OsRng::new().ok().expect("failed to open the OS random number generator");
+        let mut nonce = [0u8; 24];
+        rng.fill_bytes(&mut nonce);
+        let nonce = nonce.to_base64(URL_SAFE);
+        let mut nonces = self.nonces.lock().unwrap();
+        let lifetime = self.nonce_lifetime;
+        let expired: Vec<String> = nonces.iter()
+                                         .filter(|&(_, &(issued, _))| issued + lifetime <= now)
+                                         .map(|(nonce, _)| nonce.clone())
+                                         .collect();
+        for old in expired.iter() {
+            nonces.remove(old);
+        }
+        // Anyone may ask for a challenge, so the nonces cannot be allowed to pile up.
+        while !nonces.is_empty() && nonces.len() >= self.max_nonces {
+            let oldest = nonces.iter().min_by(|&(_, &(issued, _))| issued)
+                               .map(|(nonce, _)| nonce.clone()).unwrap();
+            nonces.remove(&oldest);
+        }
+        nonces.insert(nonce.clone(), (now, 0));
+        nonce
+    }
+}
+
+/// Whether the `uri` of Digest credentials is the request target of the request.
+fn request_uri_matches(request: &Request, uri: &str) -> bool {
+    match request.request_uri {
+        AbsolutePath(ref path) => format!("{:?}", path) == uri,
+        AbsoluteUri(ref url) => url.serialize() == uri || {
+            let path = url.serialize_path().unwrap_or(String::new());
+            match url.query {
+                Some(ref query) => format!("{}?{}", path, query) == uri,
+                None => path == uri,
+            }
+        },
+        _ => false,
+    }
+}
+
+#[cfg(feature = "ssl")]
+fn default_digest() -> Vec<DigestAlgorithm> {
+    use headers::authorization::{Md5, Sha256};
+    vec!(Sha256, Md5)
+}
+
+#[cfg(not(feature = "ssl"))]
+fn default_digest() -> Vec<DigestAlgorithm> {
+    Vec::new()
+}
+
+#[cfg(feature = "ssl")]
+fn digest_response(algorithm: DigestAlgorithm, username: &str, realm: &str, password: &str,
+                   nonce: &str, nc: &str, cnonce: &str, method: &str, uri: &str) -> String {
+    let ha1 = algorithm.ha1(username, realm, password, nonce, cnonce);
+    algorithm.response(&ha1[], nonce, nc, cnonce, Some("auth"), method, uri)
+}
+
+/// Without the `ssl` feature no Digest algorithms can be offered, so this is never called.
+#[cfg(not(feature = "ssl"))]
+fn digest_response(_: DigestAlgorithm, _: &str, _: &str, _: &str, _: &str, _: &str, _: &str,
+                   _: &str, _: &str) -> String {
+    unreachable!()
+}
+
+/// Compare two byte strings in time depending only upon their length.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (*x ^ *y)) == 0
+}
+
+/// A `Server` which only passes on requests authenticated by `Authentication::authenticate`,
+/// challenging the rest.
+#[derive(Clone)]
+pub struct RequireAuth<S> {
+    pub auth: Authentication,
+    pub server: S,
+}
+
+impl<S: Server> Server for RequireAuth<S> {
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        if self.auth.authenticate(&request, response).is_some() {
+            self.server.handle_request(request, response);
+        }
+    }
+
+    fn get_config(&self) -> Config {
+        self.server.get_config()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use headers::authorization::Credentials;
+    use headers::request::HeaderCollection;
+    use method::Method::Get;
+    use server::request::{Request, RequestPath};
+    use server::request::RequestUri::AbsolutePath;
+    use super::{Authentication, MemoryCredentials, Verification};
+
+    fn authentication() -> Authentication {
+        let mut users = MemoryCredentials::new();
+        users.insert(String::from_str("Mufasa"), String::from_str("Circle of Life"));
+        Authentication::new("http-auth@example.org", users)
+    }
+
+    fn request(authorization: Option<Credentials>) -> Request {
+        let mut headers = HeaderCollection::new();
+        headers.authorization = authorization;
+        Request {
+            remote_addr: None,
+            headers: headers,
+            body: Vec::new(),
+            method: Get,
+            request_uri: AbsolutePath(RequestPath::parse("/dir/index.html").unwrap()),
+            close_connection: true,
+            version: (1, 1),
+            raw_headers: None,
+        }
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut auth = authentication();
+        assert_eq!(auth.verify(&request(None), 0), Verification::Failed);
+        assert_eq!(auth.verify(&request(Some(Credentials::basic("Mufasa", "Circle of Life"))), 0),
+                   Verification::Verified(String::from_str("Mufasa")));
+        assert_eq!(auth.verify(&request(Some(Credentials::basic("Mufasa", "circle of life"))), 0),
+                   Verification::Failed);
+        assert_eq!(auth.verify(&request(Some(Credentials::basic("Simba", ""))), 0),
+                   Verification::Failed);
+
+        auth.basic = false;
+        assert_eq!(auth.verify(&request(Some(Credentials::basic("Mufasa", "Circle of Life"))), 0),
+                   Verification::Failed);
+        assert!(auth.challenges(false, 0).find("Basic").is_none());
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn test_digest() {
+        use headers::authorization::{AuthParams, Sha256};
+        use super::digest_response;
+
+        let auth = authentication();
+        let challenges = auth.challenges(false, 1000);
+        assert_eq!(challenges.0.iter().filter(|c| c.is_scheme("Digest")).count(), 2);
+        assert!(challenges.find("Basic").is_some());
+        let nonce = String::from_str(challenges.find("Digest").unwrap().param("nonce").unwrap());
+
+        let credentials = |password: &str, nc: &str, uri: &str| {
+            let response = digest_response(Sha256, "Mufasa", "http-auth@example.org", password,
+                                           &nonce[], nc, "0a4f113b", "GET", uri);
+            let params = vec!(("username", "Mufasa"), ("realm", "http-auth@example.org"),
+                              ("uri", uri), ("algorithm", "SHA-256"), ("nonce", &nonce[]),
+                              ("nc", nc), ("cnonce", "0a4f113b"), ("qop", "auth"),
+                              ("response", &response[]));
+            Some(Credentials {
+                scheme: String::from_str("Digest"),
+                data: AuthParams(params.into_iter().map(|(name, value)| {
+                    (String::from_str(name), String::from_str(value))
+                }).collect()),
+            })
+        };
+
+        let verified = Verification::Verified(String::from_str("Mufasa"));
+        assert_eq!(auth.verify(&request(credentials("Circle of Life", "00000001",
+                                                    "/dir/index.html")), 1000),
+                   verified);
+        // The same nonce count again is a replay.
+        assert_eq!(auth.verify(&request(credentials("Circle of Life", "00000001",
+                                                    "/dir/index.html")), 1000),
+                   Verification::Failed);
+        assert_eq!(auth.verify(&request(credentials("Circle of Life", "00000002",
+                                                    "/dir/index.html")), 1000),
+                   verified);
+        assert_eq!(auth.verify(&request(credentials("wrong", "00000003", "/dir/index.html")),
+                               1000),
+                   Verification::Failed);
+        assert_eq!(auth.verify(&request(credentials("Circle of Life", "00000003", "/other")),
+                               1000),
+                   Verification::Failed);
+        assert_eq!(auth.verify(&request(credentials("Circle of Life", "00000003",
+                                                    "/dir/index.html")),
+                               1000 + auth.nonce_lifetime),
+                   Verification::Stale);
+    }
+
+    #[test]
+    fn test_max_nonces() {
+        let mut auth = authentication();
+        auth.max_nonces = 2;
+        let first = auth.new_nonce(1);
+        let second = auth.new_nonce(2);
+        let third = auth.new_nonce(3);
+        let nonces = auth.nonces.lock().unwrap();
+        assert_eq!(nonces.len(), 2);
+        assert!(!nonces.contains_key(&first));
+        assert!(nonces.contains_key(&second) && nonces.contains_key(&third));
+    }
+}
@@ -0,0 +1,216 @@
+//! Evaluation of conditional requests, as defined in RFC 7232.
+
+use time::{self, Tm};
+
+use headers::etag::EntityTag;
+use headers::if_range::{EntityTagRange, DateRange};
+use method::Method::{Get, Head};
+use server::request::Request;
+use status::Status;
+use status::Status::{NotModified, PreconditionFailed};
+
+/// Evaluate the preconditions of a request for an existing resource whose current validators
+/// are `etag` and `last_modified`, in the order given by RFC 7232, section 6.
+///
+/// If the request should go ahead, `Ok(())` is returned. Otherwise, the status to respond with is
+/// returned: `304 Not Modified` for a `GET` or `HEAD` request whose cached representation is still
+/// current, and `412 Precondition Failed` otherwise. A `304` response should carry the same
+/// `ETag`, `Last-Modified`, `Cache-Control`, `Expires` and `Vary` headers that a `200` would have,
+/// but no body. Its `Content-Length`, if it has one, must be that of the representation, not 0.
+///
+/// ```rust,ignore
+/// match check_preconditions(&request, Some(&etag), Some(&last_modified)) {
+///     Ok(()) => { /* serve the resource as usual */ },
+///     Err(status) => {
+///         if status != NotModified {
+///             response.headers.content_length = Some(0);
+///         }
+///         response.status = status;
+///         response.headers.etag = Some(etag);
+///     },
+/// }
+/// ```
+pub fn check_preconditions(request: &Request, etag: Option<&EntityTag>,
+                           last_modified: Option<&Tm>) -> Result<(), Status> {
+    let headers = &request.headers;
+    let last_modified = last_modified.map(|tm| tm.to_timespec().sec);
+
+    // 1. If-Match, or failing that 2. If-Unmodified-Since
+    match headers.if_match {
+        Some(ref if_match) => if !if_match.matches_strongly(etag) {
+            return Err(PreconditionFailed);
+        },
+        None => match (&headers.if_unmodified_since, last_modified) {
+            (&Some(ref since), Some(last_modified)) => {
+                if last_modified > since.to_timespec().sec {
+                    return Err(PreconditionFailed);
+                }
+            },
+            _ => (),
+        },
+    }
+
+    let safe = request.method == Get || request.method == Head;
+
+    // 3. If-None-Match, or failing that 4. If-Modified-Since (only for GET and HEAD)
+    match headers.if_none_match {
+        Some(ref if_none_match) => if if_none_match.matches_weakly(etag) {
+            return Err(if safe { NotModified } else { PreconditionFailed });
+        },
+        None => match (&headers.if_modified_since, last_modified) {
+            (&Some(ref since), Some(last_modified)) if safe => {
+                let since = since.to_timespec().sec;
+                // A date in the future is invalid and must be ignored.
+                if since <= time::get_time().sec && last_modified <= since {
+                    return Err(NotModified);
+                }
+            },
+            _ => (),
+        },
+    }
+
+    Ok(())
+}
+
+/// Evaluate the If-Range header of a request (RFC 7233, section 3.2) against the current
+/// validators of the resource: `true` means that the Range header is to be honoured, and `false`
+/// that the whole representation should be sent instead.
+///
+/// An entity-tag matches only by strong comparison, and a date only if it is exactly the
+/// resource's last modification date. A request without If-Range always matches.
+pub fn if_range_matches(request: &Request, etag: Option<&EntityTag>,
+                        last_modified: Option<&Tm>) -> bool {
+    match request.headers.if_range {
+        None => true,
+        Some(EntityTagRange(ref tag)) => match etag {
+            Some(etag) => tag.strong_eq(etag),
+            None => false,
+        },
+        Some(DateRange(ref date)) => match last_modified {
+            Some(last_modified) => date.to_timespec().sec == last_modified.to_timespec().sec,
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time;
+    use headers::etag::{strong_etag, weak_etag, AnyEntityTag, EntityTags};
+    use headers::if_range::{EntityTagRange, DateRange};
+    use headers::request::HeaderCollection;
+    use method::Method;
+    use method::Method::{Get, Put};
+    use server::request::Request;
+    use server::request::RequestUri::Star;
+    use status::Status::{NotModified, PreconditionFailed};
+    use super::{check_preconditions, if_range_matches};
+
+    fn request(method: Method, headers: HeaderCollection) -> Request {
+        Request {
+            remote_addr: None,
+            headers: headers,
+            body: Vec::new(),
+            method: method,
+            request_uri: Star,
+            close_connection: true,
+            version: (1, 1),
+            raw_headers: None,
+        }
+    }
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    #[test]
+    fn test_no_preconditions() {
+        let etag = strong_etag(String::from_str("a"));
+        let r = request(Get, HeaderCollection::new());
+        assert_eq!(check_preconditions(&r, Some(&etag), Some(&at(1000))), Ok(()));
+        assert_eq!(check_preconditions(&r, None, None), Ok(()));
+    }
+
+    #[test]
+    fn test_if_match() {
+        let etag = strong_etag(String::from_str("a"));
+        let mut headers = HeaderCollection::new();
+        headers.if_match = Some(EntityTags(vec!(strong_etag(String::from_str("a")))));
+        assert_eq!(check_preconditions(&request(Put, headers.clone()), Some(&etag), None), Ok(()));
+
+        // If-Match uses the strong comparison.
+        headers.if_match = Some(EntityTags(vec!(weak_etag(String::from_str("a")))));
+        assert_eq!(check_preconditions(&request(Put, headers.clone()), Some(&etag), None),
+                   Err(PreconditionFailed));
+
+        headers.if_match = Some(AnyEntityTag);
+        assert_eq!(check_preconditions(&request(Put, headers.clone()), None, None), Ok(()));
+
+        // If-Match takes precedence over If-Unmodified-Since.
+        headers.if_unmodified_since = Some(at(0));
+        assert_eq!(check_preconditions(&request(Put, headers), None, Some(&at(1000))), Ok(()));
+    }
+
+    #[test]
+    fn test_if_unmodified_since() {
+        let mut headers = HeaderCollection::new();
+        headers.if_unmodified_since = Some(at(1000));
+        let r = request(Put, headers);
+        assert_eq!(check_preconditions(&r, None, Some(&at(1000))), Ok(()));
+        assert_eq!(check_preconditions(&r, None, Some(&at(1001))), Err(PreconditionFailed));
+        assert_eq!(check_preconditions(&r, None, None), Ok(()));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = strong_etag(String::from_str("a"));
+        let mut headers = HeaderCollection::new();
+        // If-None-Match uses the weak comparison.
+        headers.if_none_match = Some(EntityTags(vec!(weak_etag(String::from_str("a")))));
+        assert_eq!(check_preconditions(&request(Get, headers.clone()), Some(&etag), None),
+                   Err(NotModified));
+        assert_eq!(check_preconditions(&request(Put, headers.clone()), Some(&etag), None),
+                   Err(PreconditionFailed));
+
+        // If-None-Match takes precedence over If-Modified-Since.
+        headers.if_modified_since = Some(at(2000));
+        let other = strong_etag(String::from_str("b"));
+        assert_eq!(check_preconditions(&request(Get, headers), Some(&other), Some(&at(1000))),
+                   Ok(()));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let mut headers = HeaderCollection::new();
+        headers.if_modified_since = Some(at(1000));
+        assert_eq!(check_preconditions(&request(Get, headers.clone()), None, Some(&at(1000))),
+                   Err(NotModified));
+        assert_eq!(check_preconditions(&request(Get, headers.clone()), None, Some(&at(1001))),
+                   Ok(()));
+        // Only GET and HEAD are affected.
+        assert_eq!(check_preconditions(&request(Put, headers.clone()), None, Some(&at(1000))),
+                   Ok(()));
+
+        // Dates in the future are ignored.
+        headers.if_modified_since = Some(at(time::get_time().sec + 3600));
+        assert_eq!(check_preconditions(&request(Get, headers), None, Some(&at(1000))), Ok(()));
+    }
+
+    #[test]
+    fn test_if_range_matches() {
+        let etag = strong_etag(String::from_str("a"));
+        let mut headers = HeaderCollection::new();
+        assert!(if_range_matches(&request(Get, headers.clone()), None, None));
+
+        headers.if_range = Some(EntityTagRange(strong_etag(String::from_str("a"))));
+        assert!(if_range_matches(&request(Get, headers.clone()), Some(&etag), None));
+        assert!(!if_range_matches(&request(Get, headers.clone()), None, Some(&at(1000))));
+        headers.if_range = Some(EntityTagRange(weak_etag(String::from_str("a"))));
+        assert!(!if_range_matches(&request(Get, headers.clone()), Some(&etag), None));
+
+        headers.if_range = Some(DateRange(at(1000)));
+        assert!(if_range_matches(&request(Get, headers.clone()), None, Some(&at(1000))));
+        assert!(!if_range_matches(&request(Get, headers.clone()), None, Some(&at(999))));
+        assert!(!if_range_matches(&request(Get, headers), Some(&etag), None));
+    }
+}
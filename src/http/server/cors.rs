@@ -0,0 +1,360 @@
+/*!
+
+Cross-Origin Resource Sharing (CORS) for server handlers.
+
+A `Cors` value describes which origins may make cross-origin requests of the server, with which
+methods and headers, and whether credentials may be included. It is set up once, held in the
+`Server`, and its `handle` method called at the start of each request: a preflight request (an
+`OPTIONS` request with an `Origin` and an `Access-Control-Request-Method`) is answered entirely,
+with `204 No Content`, and `handle` returns `true`; for any other request the CORS headers are
+added to the response and `handle` returns `false`, leaving the handler to write the response.
+
+```rust,ignore
+#[derive(Clone)]
+struct Api {
+    cors: Cors,
+}
+
+impl Server for Api {
+    fn get_config(&self) -> Config { /* ... */ }
+
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        if self.cors.handle(&request, response) {
+            return;
+        }
+        response.write_content_auto(/* ... */);
+    }
+}
+```
+
+Alternatively, `WithCors` wraps a whole `Server`, doing just this before calling its
+`handle_request`.
+
+Requests from origins which are not allowed, and preflight requests asking for methods or headers
+which are not allowed, get no CORS headers at all; the user agent then refuses to let the page
+see the response. Note that CORS does not stop the request from reaching the server: a handler
+must not rely on it to protect against cross-site request forgery.
+
+*/
+
+use std::ascii::AsciiExt;
+
+use headers::access_control::{AllowOrigin, AnyOrigin, AllowedOrigin, AllowCredentials,
+                              FieldName, Origin, NullOrigin, SerializedOrigin};
+use headers::request::HeaderCollection as RequestHeaders;
+use headers::response::HeaderCollection as ResponseHeaders;
+use method::Method;
+use method::Method::{Options, Get, Head, Post};
+use server::{Server, Config};
+use server::request::Request;
+use server::response::ResponseWriter;
+use status;
+
+pub use self::AllowedOrigins::{AllOrigins, SomeOrigins, EchoAllOrigins};
+
+/// The origins which may make cross-origin requests.
+#[derive(Clone)]
+pub enum AllowedOrigins {
+    /// Any origin at all, answered with `*`. As `*` is not permitted with credentials,
+    /// `allow_credentials` is ignored: user agents will not let pages read responses to requests
+    /// made with credentials.
+    AllOrigins,
+    /// Only the listed serialized origins, such as `https://example.com`, compared
+    /// case-insensitively.
+    SomeOrigins(Vec<String>),
+    /// Any origin, echoed back, so that with `allow_credentials` any site can read the responses
+    /// to requests made with the user's cookies. This is only for servers which are meant to be
+    /// used that way. Even so, the `null` origin (of sandboxed documents and local files, which
+    /// could be anyone's) is not allowed with credentials.
+    EchoAllOrigins,
+}
+
+impl AllowedOrigins {
+    fn is_all(&self) -> bool {
+        match *self {
+            AllOrigins => true,
+            _ => false,
+        }
+    }
+}
+
+/// A CORS policy.
+#[derive(Clone)]
+pub struct Cors {
+    /// The origins which may make cross-origin requests; none by default.
+    pub allowed_origins: AllowedOrigins,
+    /// The methods which may be used in cross-origin requests; by default `GET`, `HEAD` and
+    /// `POST`, which user agents never make a preflight request for anyway.
+    pub allowed_methods: Vec<Method>,
+    /// The request headers which may be sent, beyond those which user agents always allow (such
+    /// as Accept); none by default.
+    pub allowed_headers: Vec<String>,
+    /// The response headers which the page may read, beyond those which it always can (such as
+    /// Content-Type); none by default.
+    pub exposed_headers: Vec<String>,
+    /// Whether requests may include credentials (cookies and HTTP authentication); `false` by
+    /// default, and ignored for `AllOrigins`.
+    pub allow_credentials: bool,
+    /// How many seconds the user agent may cache the result of a preflight request for; by default
+    /// this is not sent, leaving it to the user agent (five seconds, usually).
+    pub max_age: Option<usize>,
+}
+
+impl Cors {
+    /// A policy allowing the given origins to make simple requests, without credentials.
+    pub fn new(allowed_origins: AllowedOrigins) -> Cors {
+        Cors {
+            allowed_origins: allowed_origins,
+            allowed_methods: vec!(Get, Head, Post),
+            allowed_headers: Vec::new(),
+            exposed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Handle the CORS aspects of a request, as described in the module documentation. Returns
+    /// `true` if the request was a preflight request, which has been answered in full.
+    ///
+    /// This must be called before the response headers are written.
+    pub fn handle(&self, request: &Request, response: &mut ResponseWriter) -> bool {
+        if request.method == Options && request.headers.origin.is_some() &&
+                request.headers.access_control_request_method.is_some() {
+            self.preflight(&request.headers, &mut response.headers);
+            response.status = status::Status::NoContent;
+            response.headers.content_length = Some(0);
+            // Like any other handler, the preflight ignores a failure to write.
+            let _ = response.write_headers();
+            true
+        } else {
+            self.decorate(&request.headers, &mut response.headers);
+            false
+        }
+    }
+
+    /// The value of Access-Control-Allow-Origin for a request from `origin`, if it is allowed.
+    fn allow_origin(&self, origin: &Origin) -> Option<AllowOrigin> {
+        match (&self.allowed_origins, origin) {
+            (&AllOrigins, _) => Some(AnyOrigin),
+            (&EchoAllOrigins, &NullOrigin) if self.allow_credentials => None,
+            (&EchoAllOrigins, origin) => Some(AllowedOrigin(origin.clone())),
+            (&SomeOrigins(ref origins), &SerializedOrigin(ref origin))
+                    if origins.iter().any(|o| o[].eq_ignore_ascii_case(&origin[])) => {
+                Some(AllowedOrigin(SerializedOrigin(origin.clone())))
+            },
+            (&SomeOrigins(_), _) => None,
+        }
+    }
+
+    /// Set the headers common to preflight and actual responses, returning whether the origin of
+    /// the request is allowed.
+    fn allow(&self, request: &RequestHeaders, response: &mut ResponseHeaders) -> bool {
+        let allow_origin = match request.origin {
+            Some(ref origin) => self.allow_origin(origin),
+            None => None,
+        };
+        // The answer depends upon the Origin unless it is `*`, so caches must know that.
+        match self.allowed_origins {
+            AllOrigins => (),
+            _ => add_vary_origin(response),
+        }
+        match allow_origin {
+            Some(allow_origin) => {
+                response.access_control_allow_origin = Some(allow_origin);
+                if self.allow_credentials && !self.allowed_origins.is_all() {
+                    response.access_control_allow_credentials = Some(AllowCredentials);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Answer a preflight request, setting the response headers if the origin, method and headers
+    /// requested are all allowed.
+    fn preflight(&self, request: &RequestHeaders, response: &mut ResponseHeaders) {
+        let method_allowed = match request.access_control_request_method {
+            Some(ref method) => self.allowed_methods.contains(method),
+            None => false,
+        };
+        let headers_allowed = match request.access_control_request_headers {
+            Some(ref names) => names.iter().all(|name| {
+                self.allowed_headers.iter().any(|allowed| name.matches(&allowed[]))
+            }),
+            None => true,
+        };
+        if !method_allowed || !headers_allowed {
+            return;
+        }
+        if !self.allow(request, response) {
+            return;
+        }
+        response.access_control_allow_methods = Some(self.allowed_methods.clone());
+        if !self.allowed_headers.is_empty() {
+            response.access_control_allow_headers = Some(field_names(&self.allowed_headers[]));
+        }
+        response.access_control_max_age = self.max_age;
+    }
+
+    /// Add the CORS headers to the response to an actual request.
+    fn decorate(&self, request: &RequestHeaders, response: &mut ResponseHeaders) {
+        if self.allow(request, response) && !self.exposed_headers.is_empty() {
+            response.access_control_expose_headers = Some(field_names(&self.exposed_headers[]));
+        }
+    }
+}
+
+fn field_names(names: &[String]) -> Vec<FieldName> {
+    names.iter().map(|name| FieldName(name.clone())).collect()
+}
+
+/// Add `Origin` to the Vary header of a response, unless it is already there.
+fn add_vary_origin(response: &mut ResponseHeaders) {
+    response.vary = Some(match response.vary.take() {
+        None => String::from_str("Origin"),
+        Some(vary) => {
+            if vary.split(',').any(|name| {
+                let name = name.trim();
+                name == "*" || name.eq_ignore_ascii_case("origin")
+            }) {
+                vary
+            } else {
+                format!("{}, Origin", vary)
+            }
+        },
+    });
+}
+
+/// A `Server` which handles CORS (with `Cors::handle`) before passing requests other than
+/// preflight requests on to another.
+#[derive(Clone)]
+pub struct WithCors<S> {
+    pub cors: Cors,
+    pub server: S,
+}
+
+impl<S: Server> Server for WithCors<S> {
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        if !self.cors.handle(&request, response) {
+            self.server.handle_request(request, response);
+        }
+    }
+
+    fn get_config(&self) -> Config {
+        self.server.get_config()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use headers::HeaderConvertible;
+    use headers::access_control::{FieldName, NullOrigin, SerializedOrigin};
+    use headers::request::HeaderCollection as RequestHeaders;
+    use headers::response::HeaderCollection as ResponseHeaders;
+    use method::Method::{Get, Put, Delete};
+    use super::{Cors, AllOrigins, SomeOrigins, EchoAllOrigins};
+
+    fn request_from(origin: &str) -> RequestHeaders {
+        let mut request = RequestHeaders::new();
+        request.origin = Some(SerializedOrigin(String::from_str(origin)));
+        request
+    }
+
+    #[test]
+    fn test_actual_requests() {
+        let mut cors = Cors::new(SomeOrigins(vec!(String::from_str("https://example.com"))));
+        cors.exposed_headers = vec!(String::from_str("X-Total"));
+
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&request_from("https://example.com"), &mut response);
+        assert_eq!(response.access_control_allow_origin.unwrap().http_value(),
+                   "https://example.com");
+        assert_eq!(response.access_control_expose_headers.unwrap().http_value(), "X-Total");
+        assert!(response.access_control_allow_credentials.is_none());
+        assert_eq!(response.vary, Some(String::from_str("Origin")));
+
+        let mut response = ResponseHeaders::new();
+        response.vary = Some(String::from_str("Accept-Encoding"));
+        cors.decorate(&request_from("https://evil.example"), &mut response);
+        assert!(response.access_control_allow_origin.is_none());
+        assert!(response.access_control_expose_headers.is_none());
+        assert_eq!(response.vary, Some(String::from_str("Accept-Encoding, Origin")));
+
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&RequestHeaders::new(), &mut response);
+        assert!(response.access_control_allow_origin.is_none());
+    }
+
+    #[test]
+    fn test_all_origins() {
+        let mut cors = Cors::new(AllOrigins);
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&request_from("https://example.com"), &mut response);
+        assert_eq!(response.access_control_allow_origin.unwrap().http_value(), "*");
+        assert!(response.vary.is_none());
+
+        // `*` does not allow credentials, and the origin is not echoed to get round that.
+        cors.allow_credentials = true;
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&request_from("https://example.com"), &mut response);
+        assert_eq!(response.access_control_allow_origin.unwrap().http_value(), "*");
+        assert!(response.access_control_allow_credentials.is_none());
+    }
+
+    #[test]
+    fn test_echo_all_origins() {
+        let mut cors = Cors::new(EchoAllOrigins);
+        cors.allow_credentials = true;
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&request_from("https://example.com"), &mut response);
+        assert_eq!(response.access_control_allow_origin.unwrap().http_value(),
+                   "https://example.com");
+        assert!(response.access_control_allow_credentials.is_some());
+        assert_eq!(response.vary, Some(String::from_str("Origin")));
+
+        // The null origin is refused credentials.
+        let mut request = RequestHeaders::new();
+        request.origin = Some(NullOrigin);
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&request, &mut response);
+        assert!(response.access_control_allow_origin.is_none());
+        assert!(response.access_control_allow_credentials.is_none());
+
+        cors.allow_credentials = false;
+        let mut response = ResponseHeaders::new();
+        cors.decorate(&request, &mut response);
+        assert_eq!(response.access_control_allow_origin.unwrap().http_value(), "null");
+    }
+
+    #[test]
+    fn test_preflight() {
+        let mut cors = Cors::new(SomeOrigins(vec!(String::from_str("https://example.com"))));
+        cors.allowed_methods = vec!(Get, Put);
+        cors.allowed_headers = vec!(String::from_str("Content-Type"));
+        cors.max_age = Some(600);
+
+        let mut request = request_from("https://example.com");
+        request.access_control_request_method = Some(Put);
+        request.access_control_request_headers =
+            Some(vec!(FieldName(String::from_str("content-type"))));
+        let mut response = ResponseHeaders::new();
+        cors.preflight(&request, &mut response);
+        assert!(response.access_control_allow_origin.is_some());
+        assert_eq!(response.access_control_allow_methods.unwrap().http_value(), "GET, PUT");
+        assert_eq!(response.access_control_allow_headers.unwrap().http_value(), "Content-Type");
+        assert_eq!(response.access_control_max_age, Some(600));
+
+        request.access_control_request_method = Some(Delete);
+        let mut response = ResponseHeaders::new();
+        cors.preflight(&request, &mut response);
+        assert!(response.access_control_allow_origin.is_none());
+        assert!(response.access_control_allow_methods.is_none());
+
+        request.access_control_request_method = Some(Put);
+        request.access_control_request_headers =
+            Some(vec!(FieldName(String::from_str("X-Secret"))));
+        let mut response = ResponseHeaders::new();
+        cors.preflight(&request, &mut response);
+        assert!(response.access_control_allow_origin.is_none());
+    }
+}
@@ -11,8 +11,15 @@ use buffer::BufferedStream;
 pub use self::request::{RequestBuffer, Request};
 pub use self::response::ResponseWriter;
 
+pub mod auth;
+pub mod conditional;
+pub mod cors;
 pub mod request;
 pub mod response;
+pub mod security;
+pub mod session;
+pub mod static_files;
+pub mod virtual_host;
 
 pub trait Server: Send + Clone {
 	fn handle_request(&self, request: Request, response: &mut ResponseWriter) -> ();
@@ -36,6 +43,8 @@ pub trait Server: Send + Clone {
             Ok(acceptor) => acceptor,
         };
         debug!("listening");
+        let record_raw_headers = config.record_raw_headers;
+        let max_body_size = config.max_body_size;
         let (perf_sender, perf_receiver) = channel();
         Thread::spawn(move || {
             perf_dumper(perf_receiver);
@@ -63,7 +72,9 @@ pub trait Server: Send + Clone {
                 let mut first = true;
                 loop {  // A keep-alive loop, condition at end
                     let mut time_spawned = precise_time_ns();
-                    let (request, err_status) = Request::load(&mut stream);
+                    let (request, err_status) = Request::load_limited(&mut stream,
+                                                                      record_raw_headers,
+                                                                      max_body_size);
                     let close_connection = request.close_connection;
                     let time_request_made = precise_time_ns();
                     if !first {
@@ -159,7 +170,9 @@ pub trait Server: Send + Clone {
 
             let mut stream = BufferedStream::new(stream);
             debug!("accepted connection");
-            let (request, err_status) = Request::load(&mut stream);
+            let (request, err_status) = Request::load_limited(&mut stream,
+                                                              config.record_raw_headers,
+                                                              config.max_body_size);
             let mut response = ResponseWriter::new(&mut stream);
             match err_status {
                 Ok(()) => {
@@ -187,12 +200,29 @@ pub trait Server: Send + Clone {
 }
 
 /// The necessary configuration for an HTTP server.
-///
-/// At present, only the IP address and port to bind to are needed, but it's possible that other
-/// options may turn up later.
 #[derive(Copy)]
 pub struct Config {
 	pub bind_address: SocketAddr,
+
+    /// Whether to record the header lines of each request exactly as received, in
+    /// `Request::raw_headers`.
+    pub record_raw_headers: bool,
+
+    /// The longest request body, in bytes, that will be read into `Request::body`; a request
+    /// with a longer body is answered with 413 Request Entity Too Large. 50 MiB by default, or
+    /// `None` for no limit.
+    pub max_body_size: Option<usize>,
+}
+
+impl Config {
+    /// The default configuration for a server bound to the given address.
+    pub fn new(bind_address: SocketAddr) -> Config {
+        Config {
+            bind_address: bind_address,
+            record_raw_headers: false,
+            max_body_size: Some(50 * 1024 * 1024),
+        }
+    }
 }
 
 const PERF_DUMP_FREQUENCY : u64 = 10_000;
@@ -1,18 +1,24 @@
 use url::Url;
+use url::percent_encoding::percent_decode;
 use method::Method;
 use method::Method::Options;
 use status;
-use status::Status::{BadRequest, RequestUriTooLong, HttpVersionNotSupported};
-use std::io::{Stream, IoResult};
+use status::Status::{BadRequest, RequestEntityTooLarge, RequestUriTooLong,
+                     HttpVersionNotSupported};
+use std::io::{BufReader, InvalidInput, IoError, IoResult, Stream};
 use std::io::net::ip::SocketAddr;
 use std::io::net::tcp::TcpStream;
+use std::ascii::AsciiExt;
 use std::fmt;
 use rfc2616::{CR, LF, SP};
 use headers;
 use buffer::BufferedStream;
 use common::read_http_version;
+use form::{self, Form, FormError, FormLimits};
+use multipart::{self, Multipart, MultipartLimits};
 
-use headers::HeaderLineErr;
+use headers::{HeaderLineErr, RawHeader};
+use headers::host::{Host, HostName};
 use headers::HeaderLineErr::{EndOfFile, EndOfHeaders, MalformedHeaderSyntax,
                              MalformedHeaderValue};
 
@@ -142,7 +148,15 @@ impl<'a, S: Stream> RequestBuffer<'a, S> {
     /// - `MalformedHeaderValue`: header's value is invalid; normally, ignore it.
     /// - `MalformedHeaderSyntax`: bad request; you could drop it or try returning 400 Bad Request
     pub fn read_header<T: headers::HeaderEnum>(&mut self) -> Result<T, HeaderLineErr> {
-        match headers::header_enum_from_stream(&mut *self.stream) {
+        self.read_header_recording(None)
+    }
+
+    /// Read a header as `read_header` does, also appending the header line as received to `raw`
+    /// if it is given (even if the value is malformed).
+    pub fn read_header_recording<T: headers::HeaderEnum>(&mut self,
+                                                         raw: Option<&mut Vec<RawHeader>>)
+                                                         -> Result<T, HeaderLineErr> {
+        match headers::header_enum_from_stream_recording(&mut *self.stream, raw) {
         //match headers::HeaderEnum::from_stream(self.stream) {
             (Err(m), None) => Err(m),
             (Err(m), Some(b)) => {
@@ -171,7 +185,7 @@ impl<'a, S: Stream> Reader for RequestBuffer<'a, S> {
 #[test]
 fn test_request_uri_from_string() {
     assert_eq!(RequestUri::from_string("*".to_string()), Some(RequestUri::Star));
-    assert_eq!(RequestUri::from_string("/abc".to_string()), Some(RequestUri::AbsolutePath("/abc".to_string())));
+    assert_eq!(RequestUri::from_string("/abc".to_string()), Some(RequestUri::AbsolutePath(RequestPath::parse("/abc").unwrap())));
     let url = "http://example.com/abc";
     match RequestUri::from_string(url.to_string()) {
 	Some(RequestUri::AbsoluteUri(url)) => {
@@ -181,13 +195,33 @@ fn test_request_uri_from_string() {
 	_ => panic!("Parse failed for {}", url),
     };
     assert_eq!(RequestUri::from_string("".to_string()), None);
-    assert_eq!(RequestUri::from_string(" ".to_string()), Some(Authority(" ".to_string())));
+    assert_eq!(RequestUri::from_string(" ".to_string()), None);
+    assert_eq!(RequestUri::from_string("[::1]:8080".to_string()),
+               Some(Authority(Host::parse("[::1]:8080").unwrap())));
+    assert_eq!(RequestUri::from_string("example.com:x".to_string()), None);
     Url::parse("").unwrap_err();    // Url::parse() should return error for empty string
 }
 
+#[test]
+fn test_request_path() {
+    let path = RequestPath::parse("/caf%C3%A9/a%2Fb/?q=x+y&r").unwrap();
+    assert_eq!(path.path, "/caf%C3%A9/a%2Fb/");
+    assert_eq!(path.query, Some(String::from_str("q=x+y&r")));
+    assert_eq!(path.raw_segments(), vec!("caf%C3%A9", "a%2Fb", ""));
+    assert_eq!(path.segments(), vec!(String::from_str("caf\u{e9}"), String::from_str("a/b"),
+                                     String::new()));
+    assert_eq!(format!("{:?}", path), "/caf%C3%A9/a%2Fb/?q=x+y&r");
+
+    let path = RequestPath::parse("/").unwrap();
+    assert_eq!(path.raw_segments(), vec!(""));
+    assert_eq!(path.query, None);
+    assert_eq!(RequestPath::parse("a/b"), None);
+}
+
 #[test]
 fn test_read_request_line() {
     use method::Method::{Get, Options, Connect, ExtensionMethod};
+    use headers::host::Domain;
     use buffer::BufferedStream;
     use memstream::MemReaderFakeStream;
 
@@ -200,15 +234,18 @@ fn test_read_request_line() {
         }}
     }
 
-    tt!("GET / HTTP/1.1\n" => Ok((Get, AbsolutePath(String::from_str("/")), (1, 1))));
-    tt!("GET / HTTP/1.1\r\n" => Ok((Get, AbsolutePath(String::from_str("/")), (1, 1))));
-    tt!("OPTIONS /foo/bar HTTP/1.1\r\n" => Ok((Options, AbsolutePath(String::from_str("/foo/bar")), (1, 1))));
+    tt!("GET / HTTP/1.1\n" => Ok((Get, AbsolutePath(RequestPath::parse("/").unwrap()), (1, 1))));
+    tt!("GET / HTTP/1.1\r\n" => Ok((Get, AbsolutePath(RequestPath::parse("/").unwrap()), (1, 1))));
+    tt!("OPTIONS /foo/bar HTTP/1.1\r\n" => Ok((Options, AbsolutePath(RequestPath::parse("/foo/bar").unwrap()), (1, 1))));
     tt!("OPTIONS * HTTP/1.1\r\n" => Ok((Options, Star, (1, 1))));
     tt!("CONNECT example.com HTTP/1.1\r\n" => Ok((Connect,
-                                                Authority(String::from_str("example.com")),
+                                                Authority(Host {
+                                                    name: Domain(String::from_str("example.com")),
+                                                    port: None,
+                                                }),
                                                 (1, 1))));
-    tt!("FOO /\r\n" => Ok((ExtensionMethod(String::from_str("FOO")), AbsolutePath(String::from_str("/")), (0, 9))));
-    tt!("FOO /\n" => Ok((ExtensionMethod(String::from_str("FOO")), AbsolutePath(String::from_str("/")), (0, 9))));
+    tt!("FOO /\r\n" => Ok((ExtensionMethod(String::from_str("FOO")), AbsolutePath(RequestPath::parse("/").unwrap()), (0, 9))));
+    tt!("FOO /\n" => Ok((ExtensionMethod(String::from_str("FOO")), AbsolutePath(RequestPath::parse("/").unwrap()), (0, 9))));
     tt!("get    http://example.com/ HTTP/42.17\r\n"
             => Ok((ExtensionMethod(String::from_str("get")),
                     AbsoluteUri(Url::parse("http://example.com/").unwrap()),
@@ -256,7 +293,12 @@ pub struct Request {
     pub close_connection: bool,
 
     /// The HTTP version number; typically `(1, 1)` or, less commonly, `(1, 0)`.
-    pub version: (usize, usize)
+    pub version: (usize, usize),
+
+    /// The header lines exactly as received, in order, if the server is configured to record
+    /// them (see `Config::record_raw_headers`); this includes any headers which were dropped from
+    /// `headers` because their values were malformed.
+    pub raw_headers: Option<Vec<RawHeader>>,
 }
 
 /// The URI (Request-URI in RFC 2616) as specified in the Status-Line of an HTTP request
@@ -279,16 +321,10 @@ pub enum RequestUri {
     /// 'To allow for transition to absoluteURIs in all requests in future versions of HTTP, all
     /// HTTP/1.1 servers MUST accept the absoluteURI form in requests, even though HTTP/1.1 clients
     /// will only generate them in requests to proxies.'
-    ///
-    /// TODO: this shouldn't be a string; it should be further parsed. `extra::net::url` has some
-    /// stuff which might help, but isn't public.
-    AbsolutePath(String),
+    AbsolutePath(RequestPath),
 
     /// 'The authority form is only used by the CONNECT method (CONNECT).'
-    ///
-    /// TODO: this shouldn't be a string; it should be further parsed. `extra::net::url` has some
-    /// stuff which might help, but isn't public.
-    Authority(String),
+    Authority(Host),
 }
 
 impl RequestUri {
@@ -299,7 +335,7 @@ impl RequestUri {
 	} else if &request_uri[] == "*" {
             Some(Star)
         } else if request_uri.as_bytes()[0] as char == '/' {
-            Some(AbsolutePath(request_uri))
+            RequestPath::parse(&request_uri[]).map(AbsolutePath)
         } else if request_uri.contains("/") {
             // An authority can't have a slash in it
             match Url::parse(&request_uri[]) {
@@ -307,8 +343,7 @@ impl RequestUri {
                 Err(_) => None,
             }
         } else {
-            // TODO: parse authority with extra::net::url
-            Some(Authority(request_uri))
+            Host::from_authority(&request_uri[]).map(Authority)
         }
     }
 }
@@ -318,8 +353,61 @@ impl fmt::Show for RequestUri {
         match *self {
             Star => f.write_str("*"),
             AbsoluteUri(ref url) => url.fmt(f),
-            AbsolutePath(ref s) => f.write_str(&s[]),
-            Authority(ref s) => f.write_str(&s[]),
+            AbsolutePath(ref path) => path.fmt(f),
+            Authority(ref host) => host.fmt(f),
+        }
+    }
+}
+
+/// A request target in origin form (RFC 7230, section 5.3.1): an absolute path and an optional
+/// query string, such as `/search/caf%C3%A9?q=1`. Both are kept exactly as they were received.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RequestPath {
+    /// The path, still percent-encoded; it always begins with a slash.
+    pub path: String,
+    /// The query string, without the leading `?`.
+    pub query: Option<String>,
+}
+
+impl RequestPath {
+    /// Split a request target into its path and query, or return `None` if it does not begin
+    /// with a slash.
+    pub fn parse(target: &str) -> Option<RequestPath> {
+        if !target.starts_with("/") {
+            return None;
+        }
+        let (path, query) = match target.find('?') {
+            Some(i) => (&target[..i], Some(String::from_str(&target[i + 1..]))),
+            None => (target, None),
+        };
+        Some(RequestPath {
+            path: String::from_str(path),
+            query: query,
+        })
+    }
+
+    /// The segments of the path, still percent-encoded. `/` is the single empty segment, and a
+    /// trailing slash gives a final empty segment: `/a/b/` is `["a", "b", ""]`.
+    pub fn raw_segments<'a>(&'a self) -> Vec<&'a str> {
+        self.path[1..].split('/').collect()
+    }
+
+    /// The segments of the path, percent-decoded. Invalid UTF-8 is replaced with U+FFFD.
+    ///
+    /// A decoded segment may contain a slash (from `%2F`), so these must not be joined back
+    /// together to make a file system path without care.
+    pub fn segments(&self) -> Vec<String> {
+        self.raw_segments().iter().map(|segment| {
+            String::from_utf8_lossy(&percent_decode(segment.as_bytes())[]).into_owned()
+        }).collect()
+    }
+}
+
+impl fmt::Show for RequestPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.query {
+            Some(ref query) => write!(f, "{}?{}", self.path, query),
+            None => f.write_str(&self.path[]),
         }
     }
 }
@@ -328,6 +416,20 @@ impl Request {
 
     /// Get a response from an open socket.
     pub fn load(stream: &mut BufferedStream<TcpStream>) -> (Request, Result<(), status::Status>) {
+        Request::load_recording(stream, false)
+    }
+
+    /// Get a response from an open socket, recording the raw header lines in `raw_headers` if
+    /// `record_raw_headers` is set.
+    pub fn load_recording(stream: &mut BufferedStream<TcpStream>, record_raw_headers: bool)
+                          -> (Request, Result<(), status::Status>) {
+        Request::load_limited(stream, record_raw_headers, None)
+    }
+
+    /// Get a response from an open socket, as `load_recording` does, but refusing with 413
+    /// Request Entity Too Large, without reading it, a body longer than `max_body_size` bytes.
+    pub fn load_limited(stream: &mut BufferedStream<TcpStream>, record_raw_headers: bool,
+                        max_body_size: Option<usize>) -> (Request, Result<(), status::Status>) {
         let mut buffer = RequestBuffer::new(stream);
 
         // Start out with dummy values
@@ -339,6 +441,7 @@ impl Request {
             request_uri: Star,
             close_connection: true,
             version: (0, 0),
+            raw_headers: if record_raw_headers { Some(Vec::new()) } else { None },
         };
 
         let (method, request_uri, version) = match buffer.read_request_line() {
@@ -357,18 +460,51 @@ impl Request {
             _ => return (request, Err(HttpVersionNotSupported)),
         };
 
+        // The header lines are recorded even if the server does not want them, so that the name
+        // of a header with a malformed value can be seen.
+        let mut scratch = Vec::new();
         loop {
-            match buffer.read_header() {
+            let (header, invalid_framing) = {
+                let raw = match request.raw_headers {
+                    Some(ref mut raw) => raw,
+                    None => {
+                        scratch.clear();
+                        &mut scratch
+                    },
+                };
+                let header = buffer.read_header_recording(Some(&mut *raw));
+                let invalid_framing = match raw.last() {
+                    Some(line) if line.malformed => {
+                        line.name[].eq_ignore_ascii_case("host") ||
+                        line.name[].eq_ignore_ascii_case("content-length")
+                    },
+                    _ => false,
+                };
+                (header, invalid_framing)
+            };
+            match header {
                 Err(EndOfFile) => panic!("client disconnected, nowhere to send response"),
                 Err(EndOfHeaders) => break,
                 Err(MalformedHeaderSyntax) => {
                     println!("BAD REQUEST: malformed header (TODO: is this right?)");
                     return (request, Err(BadRequest));
                 },
+                Err(MalformedHeaderValue) if invalid_framing => {
+                    // RFC 7230, sections 5.4 and 3.3.3: a Host or Content-Length header with an
+                    // invalid value gets a 400.
+                    println!("BAD REQUEST: invalid host or content length");
+                    return (request, Err(BadRequest));
+                },
                 Err(MalformedHeaderValue) => {
                     println!("Bad header encountered. TODO: handle this better.");
                     // Now just ignore the header
                 },
+                Ok(headers::request::Header::Host(_)) if request.headers.host.is_some() => {
+                    // RFC 7230, section 5.4: more than one Host header gets a 400, even if the
+                    // values agree (which the collection would keep as one).
+                    println!("BAD REQUEST: more than one host");
+                    return (request, Err(BadRequest));
+                },
                 Ok(header) => {
                     request.headers.insert(header);
                 },
@@ -380,6 +516,17 @@ impl Request {
             println!("BAD REQUEST: host is none for HTTP/1.1 request");
             return (request, Err(BadRequest));
         }
+        // Repeated Content-Length lines are only allowed if they agree (RFC 7230, section 3.3.2);
+        // otherwise the end of the body is in doubt, and the request may be smuggling another.
+        let conflicting_lengths = {
+            let length = request.headers.content_length;
+            request.headers.extensions.get_all("content-length").iter()
+                                      .any(|value| value.trim().parse() != length)
+        };
+        if conflicting_lengths {
+            println!("BAD REQUEST: conflicting content lengths");
+            return (request, Err(BadRequest));
+        }
 
         request.close_connection = close_connection;
         match request.headers.connection {
@@ -401,6 +548,9 @@ impl Request {
 
         // Read body if its length is specified
         match request.headers.content_length {
+            Some(length) if max_body_size.map_or(false, |max| length > max) => {
+                return (request, Err(RequestEntityTooLarge));
+            },
             Some(length) => {
                 match buffer.read_exact(length) {
                     Ok(body) => request.body = body,
@@ -412,6 +562,154 @@ impl Request {
 
         (request, Ok(()))
     }
+
+    /// The query string of the request URI (without the leading `?`), if it has one.
+    pub fn query_string<'a>(&'a self) -> Option<&'a str> {
+        match self.request_uri {
+            AbsolutePath(ref path) => path.query.as_ref().map(|query| &query[]),
+            AbsoluteUri(ref url) => url.query.as_ref().map(|query| &query[]),
+            Star | Authority(_) => None,
+        }
+    }
+
+    /// Decode the query string of the request URI as form data, with the default limits. A
+    /// request without a query string has an empty form.
+    pub fn query(&self) -> Result<Form, FormError> {
+        self.query_with_limits(&FormLimits::new())
+    }
+
+    pub fn query_with_limits(&self, limits: &FormLimits) -> Result<Form, FormError> {
+        form::decode(self.query_string().unwrap_or("").as_bytes(), limits)
+    }
+
+    /// The effective request URL (RFC 7230, section 5.5), for a request received over plain
+    /// HTTP; see `url_with_scheme`.
+    pub fn url(&self) -> Option<Url> {
+        self.url_with_scheme("http")
+    }
+
+    /// The effective request URL (RFC 7230, section 5.5): the request target if it is an
+    /// absolute URL, or else the URL made of `scheme`, the authority (from the request target for
+    /// CONNECT, or else from the Host header) and the path and query of the request target.
+    /// `scheme` would be `https` behind a proxy which terminates TLS, say.
+    ///
+    /// `None` is returned if there is no Host header to give the authority, or if the result is
+    /// not a valid URL.
+    pub fn url_with_scheme(&self, scheme: &str) -> Option<Url> {
+        let path = match self.request_uri {
+            AbsoluteUri(ref url) => return Some(url.clone()),
+            AbsolutePath(ref path) => format!("{:?}", path),
+            Authority(_) | Star => String::new(),
+        };
+        match self.host() {
+            Some(host) => Url::parse(&format!("{}://{:?}{}", scheme, host, path)[]).ok(),
+            None => None,
+        }
+    }
+
+    /// The host the request is for (RFC 7230, section 5.4): that of the request target if it is
+    /// an absolute URL or an authority, or else that of the Host header.
+    pub fn host(&self) -> Option<Host> {
+        match self.request_uri {
+            AbsoluteUri(ref url) => {
+                url.serialize_host().and_then(|host| HostName::parse(&host[])).map(|name| Host {
+                    name: name,
+                    port: url.port(),
+                })
+            },
+            Authority(ref host) => Some(host.clone()),
+            AbsolutePath(_) | Star => self.headers.host.clone(),
+        }
+    }
+
+    /// Decode the body of the request as `application/x-www-form-urlencoded` form data, with
+    /// the default limits. The Content-Type must say that it is a form.
+    pub fn form(&self) -> Result<Form, FormError> {
+        self.form_with_limits(&FormLimits::new())
+    }
+
+    pub fn form_with_limits(&self, limits: &FormLimits) -> Result<Form, FormError> {
+        form::decode_body(self.headers.content_type.as_ref(), &self.body[], limits)
+    }
+
+    /// Parse the body of the request as `multipart/form-data`, with the default limits. The
+    /// Content-Type must say that it is such a body, and give its boundary.
+    ///
+    /// The body is parsed from `self.body`, which already holds all of it; its size is bounded
+    /// by `Config::max_body_size` when the request is loaded.
+    pub fn multipart<'a>(&'a self) -> IoResult<Multipart<BufReader<'a>>> {
+        self.multipart_with_limits(MultipartLimits::new())
+    }
+
+    pub fn multipart_with_limits<'a>(&'a self, limits: MultipartLimits)
+                                     -> IoResult<Multipart<BufReader<'a>>> {
+        match multipart::boundary(self.headers.content_type.as_ref()) {
+            Some(boundary) => Ok(Multipart::new(BufReader::new(&self.body[]), &boundary[], limits)),
+            None => Err(IoError {
+                kind: InvalidInput,
+                desc: "not a multipart/form-data request",
+                detail: None,
+            }),
+        }
+    }
+}
+
+#[test]
+fn test_form_and_query() {
+    use form::FormError::NotAForm;
+    use method::Method::Post;
+
+    let mut request = Request {
+        remote_addr: None,
+        headers: headers::request::HeaderCollection::new(),
+        body: b"name=J%C3%BCrgen+M&tag=a".to_vec(),
+        method: Post,
+        request_uri: AbsolutePath(RequestPath::parse("/submit?page=2&q=a%26b").unwrap()),
+        close_connection: true,
+        version: (1, 1),
+        raw_headers: None,
+    };
+    assert_eq!(request.query_string(), Some("page=2&q=a%26b"));
+    assert_eq!(request.query().unwrap().get("q"), Some("a&b"));
+    assert_eq!(request.form(), Err(NotAForm));
+    request.headers.content_type = Some(Form::media_type());
+    assert_eq!(request.form().unwrap().get("name"), Some("J\u{fc}rgen M"));
+
+    request.request_uri = AbsolutePath(RequestPath::parse("/submit").unwrap());
+    assert_eq!(request.query_string(), None);
+    assert!(request.query().unwrap().is_empty());
+    request.request_uri = AbsoluteUri(Url::parse("http://example.com/?a=1").unwrap());
+    assert_eq!(request.query().unwrap().get("a"), Some("1"));
+}
+
+#[test]
+fn test_url() {
+    use method::Method::{Get, Connect};
+
+    let mut request = Request {
+        remote_addr: None,
+        headers: headers::request::HeaderCollection::new(),
+        body: Vec::new(),
+        method: Get,
+        request_uri: AbsolutePath(RequestPath::parse("/a%20b?c=d").unwrap()),
+        close_connection: true,
+        version: (1, 1),
+        raw_headers: None,
+    };
+    assert_eq!(request.url(), None);
+    request.headers.host = Host::parse("Example.com:8080");
+    assert_eq!(request.url(), Some(Url::parse("http://example.com:8080/a%20b?c=d").unwrap()));
+    assert_eq!(request.url_with_scheme("https"),
+               Some(Url::parse("https://example.com:8080/a%20b?c=d").unwrap()));
+
+    request.request_uri = AbsoluteUri(Url::parse("http://other.example/x").unwrap());
+    assert_eq!(request.url(), Some(Url::parse("http://other.example/x").unwrap()));
+    assert_eq!(request.host(), Host::parse("other.example"));
+
+    request.method = Connect;
+    request.request_uri = Authority(Host::parse("[::1]:443").unwrap());
+    assert_eq!(request.url(), Some(Url::parse("http://[::1]:443/").unwrap()));
+    assert_eq!(request.host(), Host::parse("[::1]:443"));
 }
 
 
@@ -1,10 +1,18 @@
-use std::io::IoResult;
+use std::io::{IoResult, Seek, SeekSet};
 use std::io::net::tcp::TcpStream;
+use std::rand;
 
 use buffer::BufferedStream;
+use method::Method::{Get, Head};
+use server::conditional::if_range_matches;
+use server::request::Request;
 use status;
+use headers::HeaderConvertible;
 use headers::response::HeaderCollection;
+use headers::accept_ranges::{RangeUnits, Bytes};
+use headers::content_range::{ByteRange, UnsatisfiedRange};
 use headers::content_type::MediaType;
+use headers::range;
 use headers::transfer_encoding::TransferCoding::Chunked;
 
 /*
@@ -15,6 +23,9 @@ use headers::transfer_encoding::TransferCoding::Chunked;
  * Others do this too, so there's my justification.
  */
 //const RESPONSE_HTTP_VERSION: &'static str = "HTTP/1.1";
+
+/// The most ranges a Range header may ask for before it is ignored and the whole body is sent.
+const MAX_RANGES: usize = 16;
 // Maybe we could provide a response interface
 
 pub struct ResponseWriter<'a> {
@@ -46,6 +57,101 @@ impl<'a> ResponseWriter<'a> {
         self.write(cbytes)
     }
 
+    /// Write a response with the specified Content-Type, serving the `length` bytes of `body`
+    /// in whole or in part according to the Range header of `request` (RFC 7233).
+    ///
+    /// The ETag and Last-Modified headers, if they are to be sent, should be set beforehand, as
+    /// they are what the If-Range header of the request is evaluated against. A single satisfiable
+    /// range is sent as `206 Partial Content`, several as a `multipart/byteranges` body, and a set
+    /// with no satisfiable range at all produces `416 Requested Range Not Satisfiable`. Ranges are
+    /// only honoured for `GET`; for `HEAD` only the headers are written.
+    ///
+    /// So that a request cannot have the same bytes sent many times over (RFC 7233, section 6.1),
+    /// overlapping and adjacent ranges are merged, in order of position, and a request for more
+    /// than 16 ranges is answered with the whole body.
+    pub fn write_ranged<R: Reader + Seek>(&mut self, request: &Request, content_type: MediaType,
+                                          body: &mut R, length: u64) -> IoResult<()> {
+        self.headers.accept_ranges = Some(RangeUnits(vec!(Bytes)));
+
+        let ranges = match request.headers.range {
+            Some(range::Bytes(ref specs)) if request.method == Get && specs.len() <= MAX_RANGES &&
+                    if_range_matches(request, self.headers.etag.as_ref(),
+                                     self.headers.last_modified.as_ref()) => {
+                Some(coalesce(specs.iter().filter_map(|spec| spec.to_satisfiable_range(length))
+                                          .collect()))
+            },
+            _ => None,
+        };
+
+        match ranges {
+            None => {
+                self.headers.content_type = Some(content_type);
+                self.headers.content_length = Some(length as usize);
+                try!(self.write_headers());
+                if request.method == Head {
+                    return Ok(());
+                }
+                self.copy_range(body, 0, length)
+            },
+            Some(ref ranges) if ranges.is_empty() => {
+                self.status = status::Status::RequestedRangeNotSatisfiable;
+                self.headers.content_range = Some(UnsatisfiedRange(length));
+                self.headers.content_length = Some(0);
+                self.write_headers()
+            },
+            Some(ref ranges) if ranges.len() == 1 => {
+                let (first, last) = ranges[0];
+                self.status = status::Status::PartialContent;
+                self.headers.content_type = Some(content_type);
+                self.headers.content_range = Some(ByteRange(first, last, Some(length)));
+                self.headers.content_length = Some((last - first + 1) as usize);
+                try!(self.write_headers());
+                self.copy_range(body, first, last + 1)
+            },
+            Some(ref ranges) => {
+                let boundary = format!("{:016x}", rand::random::<u64>());
+                let part_headers: Vec<String> = ranges.iter().map(|&(first, last)| {
+                    format!("--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                            boundary, content_type.http_value(),
+                            ByteRange(first, last, Some(length)).http_value())
+                }).collect();
+                let closing = format!("--{}--\r\n", boundary);
+                let mut total = closing.len() as u64;
+                for (part, &(first, last)) in part_headers.iter().zip(ranges.iter()) {
+                    total += part.len() as u64 + (last - first + 1) + 2;
+                }
+
+                self.status = status::Status::PartialContent;
+                self.headers.content_type = Some(MediaType::new(
+                        String::from_str("multipart"), String::from_str("byteranges"),
+                        vec!((String::from_str("boundary"), boundary.clone()))));
+                self.headers.content_length = Some(total as usize);
+                try!(self.write_headers());
+                for (part, &(first, last)) in part_headers.iter().zip(ranges.iter()) {
+                    try!(self.write(part.as_bytes()));
+                    try!(self.copy_range(body, first, last + 1));
+                    try!(self.write(b"\r\n"));
+                }
+                self.write(closing.as_bytes())
+            },
+        }
+    }
+
+    /// Copy the bytes of `body` from position `start` up to (but excluding) `end` to the response.
+    fn copy_range<R: Reader + Seek>(&mut self, body: &mut R, start: u64, end: u64)
+                                    -> IoResult<()> {
+        try!(body.seek(start as i64, SeekSet));
+        let mut buf = [0u8; 8192];
+        let mut remaining = end - start;
+        while remaining > 0 {
+            let want = if remaining < buf.len() as u64 { remaining as usize } else { buf.len() };
+            let read = try!(body.read(&mut buf[..want]));
+            try!(self.write(&buf[..read]));
+            remaining -= read as u64;
+        }
+        Ok(())
+    }
+
     /// Write the Status-Line and headers of the response, if we have not already done so.
     pub fn try_write_headers(&mut self) -> IoResult<()> {
         if !self.headers_written {
@@ -80,14 +186,19 @@ impl<'a> ResponseWriter<'a> {
         // extensible thing, whereby client and server could agree upon extra transformations to
         // apply. In such a case, chunked MUST come last. This way prevents it from being extensible
         // thus, which is suboptimal.
-        if self.headers.content_length == None {
+        // A 1xx, 204 or 304 response has no body (RFC 7230 §3.3.3), so it is never chunked; the
+        // Content-Length of a 304 is that of the representation it stands for, if given at all.
+        let code = self.status.code();
+        let chunked = self.headers.content_length == None &&
+                      !(code < 200 || code == 204 || code == 304);
+        if chunked {
             self.headers.transfer_encoding = Some(vec!(Chunked));
         } else {
             self.headers.transfer_encoding = None;
         }
         try!(self.headers.write_all(&mut *self.writer));
         self.headers_written = true;
-        if self.headers.content_length == None {
+        if chunked {
             // Flush so that the chunked body stuff can start working correctly. TODO: don't
             // actually flush it entirely, or else it'll send the headers in a separate TCP packet,
             // which is bad for performance.
@@ -119,3 +230,31 @@ impl<'a> Writer for ResponseWriter<'a> {
     }
 
 }
+
+/// Sort inclusive byte ranges and merge those which overlap or adjoin.
+fn coalesce(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges.into_iter() {
+        match merged.last_mut() {
+            Some(&mut (_, ref mut end)) if first <= *end + 1 => {
+                if last > *end {
+                    *end = last;
+                }
+                continue;
+            },
+            _ => (),
+        }
+        merged.push((first, last));
+    }
+    merged
+}
+
+#[test]
+fn test_coalesce() {
+    assert_eq!(coalesce(Vec::new()), Vec::<(u64, u64)>::new());
+    assert_eq!(coalesce(vec!((0, 99), (0, 99), (0, 99))), vec!((0, 99)));
+    assert_eq!(coalesce(vec!((500, 599), (0, 99), (50, 149), (150, 199))),
+               vec!((0, 199), (500, 599)));
+    assert_eq!(coalesce(vec!((0, 9), (20, 29), (5, 6))), vec!((0, 9), (20, 29)));
+}
@@ -0,0 +1,168 @@
+/*!
+
+Security headers applied to every response of a server.
+
+A `SecurityHeaders` value is set up once and applied to every response of a server by wrapping the
+server in `WithSecurityHeaders`, which puts the headers of the policy on each response before
+passing the request on. Handlers can still loosen or tighten the policy for particular responses by
+changing those headers, or remove one by setting it to `None`.
+
+`SecurityHeaders::new()` gives secure defaults:
+
+- `Strict-Transport-Security: max-age=31536000; includeSubDomains`
+- `Content-Security-Policy: default-src 'self'; frame-ancestors 'none'; object-src 'none'`
+- `X-Frame-Options: DENY`
+- `X-Content-Type-Options: nosniff`
+- `Referrer-Policy: strict-origin-when-cross-origin`
+
+Any of the headers can be changed, or turned off by setting it to `None`. Strict-Transport-Security
+is ignored by user agents when received over plain HTTP, but a server behind a TLS-terminating
+proxy should still send it; one which is really only reachable over HTTP may as well turn it off.
+
+```rust,ignore
+WithSecurityHeaders { security: SecurityHeaders::new(), server: App }.serve_forever();
+```
+
+A handler can instead call `apply` itself, which sets only the headers of the policy which the
+response does not already have.
+
+*/
+
+use headers::content_security_policy::ContentSecurityPolicy;
+use headers::referrer_policy::{ReferrerPolicy, StrictOriginWhenCrossOrigin};
+use headers::response::HeaderCollection;
+use headers::strict_transport_security::StrictTransportSecurity;
+use headers::x_content_type_options::NoSniff;
+use headers::x_frame_options::{XFrameOptions, Deny};
+use server::{Server, Config};
+use server::request::Request;
+use server::response::ResponseWriter;
+
+/// The security headers to add to responses.
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    /// The Strict-Transport-Security header; by default a year, including subdomains.
+    pub strict_transport_security: Option<StrictTransportSecurity>,
+    /// The Content-Security-Policy header; by default only resources of the same origin may be
+    /// loaded, plugins are forbidden and the response may not be framed.
+    pub content_security_policy: Option<ContentSecurityPolicy>,
+    /// The Content-Security-Policy-Report-Only header, for trying out a policy before enforcing
+    /// it; `None` by default.
+    pub content_security_policy_report_only: Option<ContentSecurityPolicy>,
+    /// The X-Frame-Options header, for user agents which do not support CSP's `frame-ancestors`;
+    /// `DENY` by default.
+    pub x_frame_options: Option<XFrameOptions>,
+    /// Whether to send `X-Content-Type-Options: nosniff`; `true` by default.
+    pub no_sniff: bool,
+    /// The Referrer-Policy header; by default `strict-origin-when-cross-origin`.
+    pub referrer_policy: Option<Vec<ReferrerPolicy>>,
+}
+
+impl SecurityHeaders {
+    /// The secure defaults described in the module documentation.
+    pub fn new() -> SecurityHeaders {
+        let mut hsts = StrictTransportSecurity::new(365 * 24 * 60 * 60);
+        hsts.include_subdomains = true;
+        let mut csp = ContentSecurityPolicy::new();
+        csp.set("default-src", vec!(String::from_str("'self'")));
+        csp.set("frame-ancestors", vec!(String::from_str("'none'")));
+        csp.set("object-src", vec!(String::from_str("'none'")));
+        SecurityHeaders {
+            strict_transport_security: Some(hsts),
+            content_security_policy: Some(csp),
+            content_security_policy_report_only: None,
+            x_frame_options: Some(Deny),
+            no_sniff: true,
+            referrer_policy: Some(vec!(StrictOriginWhenCrossOrigin)),
+        }
+    }
+
+    /// No security headers at all, for building a policy up from nothing.
+    pub fn none() -> SecurityHeaders {
+        SecurityHeaders {
+            strict_transport_security: None,
+            content_security_policy: None,
+            content_security_policy_report_only: None,
+            x_frame_options: None,
+            no_sniff: false,
+            referrer_policy: None,
+        }
+    }
+
+    /// Add the headers of this policy to a response, leaving alone any which the response already
+    /// has.
+    ///
+    /// This must be called before the response headers are written.
+    pub fn apply(&self, response: &mut ResponseWriter) {
+        self.apply_to(&mut response.headers);
+    }
+
+    fn apply_to(&self, headers: &mut HeaderCollection) {
+        if headers.strict_transport_security.is_none() {
+            headers.strict_transport_security = self.strict_transport_security.clone();
+        }
+        if headers.content_security_policy.is_none() {
+            headers.content_security_policy = self.content_security_policy.clone();
+        }
+        if headers.content_security_policy_report_only.is_none() {
+            headers.content_security_policy_report_only =
+                self.content_security_policy_report_only.clone();
+        }
+        if headers.x_frame_options.is_none() {
+            headers.x_frame_options = self.x_frame_options.clone();
+        }
+        if headers.x_content_type_options.is_none() && self.no_sniff {
+            headers.x_content_type_options = Some(NoSniff);
+        }
+        if headers.referrer_policy.is_none() {
+            headers.referrer_policy = self.referrer_policy.clone();
+        }
+    }
+}
+
+/// A `Server` which applies a `SecurityHeaders` policy to every response before passing the request
+/// on to another.
+#[derive(Clone)]
+pub struct WithSecurityHeaders<S> {
+    pub security: SecurityHeaders,
+    pub server: S,
+}
+
+impl<S: Server> Server for WithSecurityHeaders<S> {
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        self.security.apply(response);
+        self.server.handle_request(request, response);
+    }
+
+    fn get_config(&self) -> Config {
+        self.server.get_config()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use headers::HeaderConvertible;
+    use headers::response::HeaderCollection;
+    use headers::x_frame_options::SameOrigin;
+    use super::SecurityHeaders;
+
+    #[test]
+    fn test_apply() {
+        let mut headers = HeaderCollection::new();
+        headers.x_frame_options = Some(SameOrigin);
+        SecurityHeaders::new().apply_to(&mut headers);
+        assert_eq!(headers.strict_transport_security.unwrap().http_value(),
+                   "max-age=31536000; includeSubDomains");
+        assert_eq!(headers.content_security_policy.unwrap().http_value(),
+                   "default-src 'self'; frame-ancestors 'none'; object-src 'none'");
+        assert!(headers.content_security_policy_report_only.is_none());
+        assert_eq!(headers.x_frame_options, Some(SameOrigin));
+        assert!(headers.x_content_type_options.is_some());
+        assert_eq!(headers.referrer_policy.unwrap().http_value(),
+                   "strict-origin-when-cross-origin");
+
+        let mut headers = HeaderCollection::new();
+        SecurityHeaders::none().apply_to(&mut headers);
+        assert_eq!(headers.iter().count(), 0);
+    }
+}
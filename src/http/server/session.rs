@@ -0,0 +1,533 @@
+/*!
+
+Sessions for server handlers, kept in a cookie.
+
+A `Sessions` value is set up once, held in the `Server`, and used for each request: `load` gives
+the `Session` of the request (a new, empty one if it has none, or if it has expired or been
+tampered with), and `save` writes it back to the response as a Set-Cookie header. `save` must be
+called before the response headers are written.
+
+There are two ways of keeping the session data:
+
+- **In the cookie itself** (`Sessions::signed_cookie`, `Sessions::encrypted_cookie`), signed with
+  HMAC-SHA256 so that the client cannot alter it, and optionally encrypted with AES-256 so that
+  the client cannot read it either. Nothing is stored on the server, but the data must be small
+  and a session cannot be revoked before it expires. These need the `ssl` feature.
+- **In a `SessionStore`** (`Sessions::with_store`), with the cookie holding only an unguessable
+  session ID. `MemoryStore` is provided; anything else (a database, say) can implement the trait.
+
+Sessions expire `max_age` seconds after they were last saved. When the privileges of a session
+change, such as upon logging in, call `Session::rotate` so that it gets a new ID, defeating session
+fixation. `Session::destroy` removes the session entirely, as for logging out.
+
+```rust,ignore
+#[derive(Clone)]
+struct App {
+    sessions: Sessions,
+}
+
+impl Server for App {
+    fn get_config(&self) -> Config { /* ... */ }
+
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        let mut session = self.sessions.load(&request);
+        let visits = session.get("visits").and_then(|v| v.parse()).unwrap_or(0us) + 1;
+        session.insert(String::from_str("visits"), visits.to_string());
+        self.sessions.save(&session, response);
+        response.write_content_auto(/* ... */);
+    }
+}
+```
+
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::rand::{OsRng, Rng};
+use std::sync::{Arc, Mutex};
+use time;
+use url::form_urlencoded;
+use rustc_serialize::base64::{ToBase64, URL_SAFE};
+
+use headers::cookie::{SetCookie, SetCookies};
+use headers::response::Header;
+use server::request::Request;
+use server::response::ResponseWriter;
+
+/// The data of a session: string keys mapped to string values.
+pub type SessionData = BTreeMap<String, String>;
+
+/// The session of a request.
+#[derive(Clone)]
+pub struct Session {
+    id: Option<String>,
+    data: SessionData,
+    /// When the session expires, in seconds since the epoch, if it has been saved.
+    pub expires: Option<i64>,
+    rotate: bool,
+    destroyed: bool,
+}
+
+impl Session {
+    /// A new, empty session, not yet saved.
+    pub fn new() -> Session {
+        Session {
+            id: None,
+            data: BTreeMap::new(),
+            expires: None,
+            rotate: false,
+            destroyed: false,
+        }
+    }
+
+    /// Whether the session is new, rather than having been loaded from the request.
+    pub fn is_new(&self) -> bool {
+        self.expires.is_none()
+    }
+
+    pub fn get<'a>(&'a self, key: &str) -> Option<&'a str> {
+        self.data.get(key).map(|value| &value[])
+    }
+
+    pub fn insert(&mut self, key: String, value: String) {
+        self.data.insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.data.remove(key)
+    }
+
+    pub fn data(&self) -> &SessionData {
+        &self.data
+    }
+
+    /// Give the session a new ID when it is saved (and, for cookie sessions, a fresh cookie
+    /// value). Do this whenever its privileges change, such as upon logging in.
+    pub fn rotate(&mut self) {
+        self.rotate = true;
+    }
+
+    /// Remove all the session data, and the session cookie from the client, when it is saved.
+    pub fn destroy(&mut self) {
+        self.data.clear();
+        self.destroyed = true;
+    }
+}
+
+/// Storage for session data on the server, keyed by session ID.
+pub trait SessionStore: Send + Sync {
+    /// The data of the session with the given ID and the time at which it expires, if the
+    /// session exists.
+    fn load(&self, id: &str) -> Option<(SessionData, i64)>;
+
+    /// Store the data of a session, replacing any data held for the ID.
+    fn save(&self, id: &str, data: &SessionData, expires: i64);
+
+    /// Remove a session.
+    fn remove(&self, id: &str);
+}
+
+/// A `SessionStore` keeping sessions in memory; they are lost when the process ends.
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (SessionData, i64)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remove all expired sessions. Expired sessions are never loaded, but they take up memory
+    /// until this is called.
+    pub fn remove_expired(&self) {
+        let now = time::get_time().sec;
+        let mut sessions = self.sessions.lock().unwrap();
+        let expired: Vec<String> = sessions.iter().filter(|&(_, &(_, expires))| expires <= now)
+                                           .map(|(id, _)| id.clone()).collect();
+        for id in expired.iter() {
+            sessions.remove(id);
+        }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Option<(SessionData, i64)> {
+        self.sessions.lock().unwrap().get(id).map(|&(ref data, expires)| (data.clone(), expires))
+    }
+
+    fn save(&self, id: &str, data: &SessionData, expires: i64) {
+        self.sessions.lock().unwrap().insert(String::from_str(id), (data.clone(), expires));
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(not(feature = "ssl"), allow(dead_code))]
+enum Backend {
+    SignedCookie(crypto::Keys),
+    Store(Arc<Box<SessionStore + Send + Sync>>),
+}
+
+/// The session facility of a server; see the module documentation.
+#[derive(Clone)]
+pub struct Sessions {
+    /// The name of the session cookie; `session` by default.
+    pub cookie_name: String,
+    /// The number of seconds after being saved that a session expires; a day by default.
+    pub max_age: i64,
+    /// The Path attribute of the session cookie; `/` by default.
+    pub path: String,
+    /// The Domain attribute of the session cookie; by default there is none, making the cookie
+    /// apply to the current host only.
+    pub domain: Option<String>,
+    /// Whether the session cookie should only be sent over HTTPS; `false` by default.
+    pub secure: bool,
+    backend: Backend,
+}
+
+impl Sessions {
+    fn new(backend: Backend) -> Sessions {
+        Sessions {
+            cookie_name: String::from_str("session"),
+            max_age: 24 * 60 * 60,
+            path: String::from_str("/"),
+            domain: None,
+            secure: false,
+            backend: backend,
+        }
+    }
+
+    /// Sessions kept in a cookie signed with `key`, which should be at least 32 random bytes and,
+    /// of course, kept secret.
+    #[cfg(feature = "ssl")]
+    pub fn signed_cookie(key: &[u8]) -> Sessions {
+        Sessions::new(Backend::SignedCookie(crypto::Keys::new(key, None)))
+    }
+
+    /// Sessions kept in a cookie encrypted with the AES-256 key `encryption_key` and signed with
+    /// `signing_key`; the two keys should be different.
+    #[cfg(feature = "ssl")]
+    pub fn encrypted_cookie(signing_key: &[u8], encryption_key: &[u8; 32]) -> Sessions {
+        Sessions::new(Backend::SignedCookie(crypto::Keys::new(signing_key, Some(encryption_key))))
+    }
+
+    /// Sessions kept in `store`, identified by an ID held in the cookie.
+    pub fn with_store<T: SessionStore + Send + Sync>(store: T) -> Sessions {
+        Sessions::new(Backend::Store(Arc::new(Box::new(store) as Box<SessionStore + Send + Sync>)))
+    }
+
+    /// Load the session of a request. If the request has no valid session cookie, or the session
+    /// has expired, a new empty session is returned.
+    pub fn load(&self, request: &Request) -> Session {
+        let value = match request.headers.cookie {
+            Some(ref cookie) => cookie.get(&self.cookie_name[]),
+            None => None,
+        };
+        match value {
+            Some(value) => self.load_value(value, time::get_time().sec),
+            None => Session::new(),
+        }
+    }
+
+    fn load_value(&self, value: &str, now: i64) -> Session {
+        let mut session = Session::new();
+        let loaded = match self.backend {
+            Backend::SignedCookie(ref keys) => {
+                keys.open(value).and_then(|payload| decode_payload(&payload[]))
+            },
+            Backend::Store(ref store) => {
+                session.id = Some(String::from_str(value));
+                store.load(value)
+            },
+        };
+        match loaded {
+            Some((data, expires)) if expires > now => {
+                session.data = data;
+                session.expires = Some(expires);
+                session
+            },
+            _ => Session::new(),
+        }
+    }
+
+    /// Save a session, setting the session cookie in the response. A new session with no data is
+    /// not saved, so that no cookie is set for clients which do not need one.
+    ///
+    /// This must be called before the response headers are written.
+    pub fn save(&self, session: &Session, response: &mut ResponseWriter) {
+        match self.save_cookie(session, time::get_time().sec) {
+            Some(cookie) => response.headers.insert(Header::SetCookie(SetCookies(vec!(cookie)))),
+            None => (),
+        }
+    }
+
+    fn save_cookie(&self, session: &Session, now: i64) -> Option<SetCookie> {
+        let value = if session.destroyed {
+            match (&self.backend, &session.id) {
+                (&Backend::Store(ref store), &Some(ref id)) => store.remove(&id[]),
+                _ => (),
+            }
+            if session.is_new() {
+                return None;
+            }
+            String::new()
+        } else if session.is_new() && session.data.is_empty() {
+            return None;
+        } else {
+            let expires = now + self.max_age;
+            match self.backend {
+                Backend::SignedCookie(ref keys) => {
+                    keys.seal(&encode_payload(&session.data, expires)[])
+                },
+                Backend::Store(ref store) => {
+                    let id = match session.id {
+                        Some(ref id) if !session.rotate => id.clone(),
+                        Some(ref old) => {
+                            store.remove(&old[]);
+                            new_session_id()
+                        },
+                        None => new_session_id(),
+                    };
+                    store.save(&id[], &session.data, expires);
+                    id
+                },
+            }
+        };
+
+        let mut cookie = SetCookie::new(self.cookie_name.clone(), value);
+        cookie.max_age = Some(if session.destroyed { 0 } else { self.max_age });
+        cookie.path = Some(self.path.clone());
+        cookie.domain = self.domain.clone();
+        cookie.secure = self.secure;
+        cookie.http_only = true;
+        Some(cookie)
+    }
+}
+
+/// A new random session ID: 32 bytes from the operating system's random number generator.
+fn new_session_id() -> String {
+    let mut rng = OsRng::new().ok().expect("failed to open the OS random number generator");
+    let mut id = [0u8; 32];
+    rng.fill_bytes(&mut id);
+    id.to_base64(URL_SAFE)
+}
+
+/// Encode session data and its expiry time as a line with the time and then the data in the
+/// `application/x-www-form-urlencoded` format.
+fn encode_payload(data: &SessionData, expires: i64) -> Vec<u8> {
+    let pairs: Vec<(String, String)> = data.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    format!("{}\n{}", expires, form_urlencoded::serialize_owned(&pairs[])).into_bytes()
+}
+
+fn decode_payload(payload: &[u8]) -> Option<(SessionData, i64)> {
+    let mut parts = payload.splitn(1, |&b| b == b'\n');
+    let expires = match parts.next().and_then(|e| String::from_utf8(e.to_vec()).ok()) {
+        Some(expires) => match expires.parse() {
+            Some(expires) => expires,
+            None => return None,
+        },
+        None => return None,
+    };
+    let data = match parts.next() {
+        Some(data) => form_urlencoded::parse(data).into_iter().collect(),
+        None => return None,
+    };
+    Some((data, expires))
+}
+
+#[cfg(feature = "ssl")]
+mod crypto {
+    use openssl::crypto::hash::HashType::SHA256;
+    use openssl::crypto::hmac::HMAC;
+    use openssl::crypto::symm::{self, Type};
+    use std::rand::{OsRng, Rng};
+    use rustc_serialize::base64::{ToBase64, FromBase64, URL_SAFE};
+
+    /// The keys for signing and, optionally, encrypting session cookies.
+    #[derive(Clone)]
+    pub struct Keys {
+        signing: Vec<u8>,
+        encryption: Option<Vec<u8>>,
+    }
+
+    impl Keys {
+        pub fn new(signing: &[u8], encryption: Option<&[u8; 32]>) -> Keys {
+            Keys {
+                signing: signing.to_vec(),
+                encryption: encryption.map(|key| key.to_vec()),
+            }
+        }
+
+        fn mac(&self, data: &[u8]) -> Vec<u8> {
+            let mut hmac = HMAC::new(SHA256, &self.signing[]);
+            hmac.update(data);
+            hmac.finalize()
+        }
+
+        /// Encrypt (if there is an encryption key) and sign a payload, producing a cookie value
+        /// of the form `base64(data).base64(mac)`. Encrypted data is the IV followed by the
+        /// ciphertext, and it is the encrypted data which is signed.
+        pub fn seal(&self, payload: &[u8]) -> String {
+            let data = match self.encryption {
+                Some(ref key) => {
+                    let mut rng = OsRng::new().ok().expect("failed to open the OS random number \
+                                                            generator");
+                    let mut iv = [0u8; 16];
+                    rng.fill_bytes(&mut iv);
+                    let mut data = iv.to_vec();
+                    data.push_all(&symm::encrypt(Type::AES_256_CBC, &key[], iv.to_vec(),
+                                                 payload)[]);
+                    data
+                },
+                None => payload.to_vec(),
+            };
+            format!("{}.{}", data.to_base64(URL_SAFE), self.mac(&data[]).to_base64(URL_SAFE))
+        }
+
+        /// Verify (and decrypt) a cookie value produced by `seal`.
+        pub fn open(&self, value: &str) -> Option<Vec<u8>> {
+            let mut parts = value.splitn(1, '.');
+            let data = match parts.next().unwrap().from_base64() {
+                Ok(data) => data,
+                Err(_) => return None,
+            };
+            let mac = match parts.next().map(|mac| mac.from_base64()) {
+                Some(Ok(mac)) => mac,
+                _ => return None,
+            };
+            if !constant_time_eq(&self.mac(&data[])[], &mac[]) {
+                return None;
+            }
+            match self.encryption {
+                Some(ref key) if data.len() >= 32 && data.len() % 16 == 0 => {
+                    let iv = data[..16].to_vec();
+                    Some(symm::decrypt(Type::AES_256_CBC, &key[], iv, &data[16..]))
+                },
+                Some(_) => None,
+                None => Some(data),
+            }
+        }
+    }
+
+    /// Compare two byte strings in time depending only upon their length.
+    fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (*x ^ *y)) == 0
+    }
+}
+
+/// Without the `ssl` feature there is no cryptography, and so no cookie sessions: `Keys` cannot
+/// be constructed.
+#[cfg(not(feature = "ssl"))]
+mod crypto {
+    #[derive(Clone)]
+    pub enum Keys {}
+
+    impl Keys {
+        pub fn seal(&self, _: &[u8]) -> String {
+            match *self {}
+        }
+
+        pub fn open(&self, _: &str) -> Option<Vec<u8>> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use super::{Sessions, Session, MemoryStore, SessionStore, encode_payload, decode_payload};
+
+    fn session_with(key: &str, value: &str) -> Session {
+        let mut session = Session::new();
+        session.insert(String::from_str(key), String::from_str(value));
+        session
+    }
+
+    #[test]
+    fn test_payload() {
+        let mut data = BTreeMap::new();
+        data.insert(String::from_str("user"), String::from_str("a&b=c d"));
+        data.insert(String::from_str("role"), String::new());
+        assert_eq!(decode_payload(&encode_payload(&data, 1000)[]), Some((data, 1000)));
+        assert_eq!(decode_payload(b"soon\nuser=a"), None);
+        assert_eq!(decode_payload(b"1000"), None);
+    }
+
+    #[test]
+    fn test_store_sessions() {
+        let sessions = Sessions::with_store(MemoryStore::new());
+
+        // Nothing to save for a new, empty session.
+        assert!(sessions.save_cookie(&Session::new(), 1000).is_none());
+
+        let cookie = sessions.save_cookie(&session_with("user", "alice"), 1000).unwrap();
+        assert_eq!(cookie.max_age, Some(sessions.max_age));
+        assert!(cookie.http_only);
+        let session = sessions.load_value(&cookie.value[], 1000);
+        assert_eq!(session.get("user"), Some("alice"));
+        assert_eq!(session.expires, Some(1000 + sessions.max_age));
+
+        // Expired and unknown sessions come back empty.
+        assert!(sessions.load_value(&cookie.value[], 1000 + sessions.max_age).is_new());
+        assert!(sessions.load_value("forged", 1000).is_new());
+
+        // Rotation issues a new ID and forgets the old one.
+        let mut rotated = session.clone();
+        rotated.rotate();
+        let new_cookie = sessions.save_cookie(&rotated, 1000).unwrap();
+        assert!(new_cookie.value != cookie.value);
+        assert!(sessions.load_value(&cookie.value[], 1000).is_new());
+        assert_eq!(sessions.load_value(&new_cookie.value[], 1000).get("user"), Some("alice"));
+
+        let mut destroyed = sessions.load_value(&new_cookie.value[], 1000);
+        destroyed.destroy();
+        let removal = sessions.save_cookie(&destroyed, 1000).unwrap();
+        assert_eq!(removal.max_age, Some(0));
+        assert!(sessions.load_value(&new_cookie.value[], 1000).is_new());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new();
+        store.save("a", &BTreeMap::new(), 0);
+        store.save("b", &BTreeMap::new(), ::std::i64::MAX);
+        store.remove_expired();
+        assert!(store.load("a").is_none());
+        assert!(store.load("b").is_some());
+        store.remove("b");
+        assert!(store.load("b").is_none());
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn test_cookie_sessions() {
+        use rustc_serialize::base64::FromBase64;
+        let signed = Sessions::signed_cookie(b"0123456789abcdef0123456789abcdef");
+        let encrypted = Sessions::encrypted_cookie(b"0123456789abcdef0123456789abcdef",
+                                                   b"fedcba9876543210fedcba9876543210");
+        for sessions in [signed, encrypted].iter() {
+            let cookie = sessions.save_cookie(&session_with("user", "alice"), 1000).unwrap();
+            let session = sessions.load_value(&cookie.value[], 1000);
+            assert_eq!(session.get("user"), Some("alice"));
+            assert!(sessions.load_value(&cookie.value[], 1000 + sessions.max_age).is_new());
+
+            // Any change to the value invalidates it.
+            let mut tampered = cookie.value.clone().into_bytes();
+            tampered[0] = if tampered[0] == b'A' { b'B' } else { b'A' };
+            let tampered = String::from_utf8(tampered).unwrap();
+            assert!(sessions.load_value(&tampered[], 1000).is_new());
+        }
+
+        // The encrypted cookie does not reveal its contents.
+        let encrypted = Sessions::encrypted_cookie(b"0123456789abcdef0123456789abcdef",
+                                                   b"fedcba9876543210fedcba9876543210");
+        let cookie = encrypted.save_cookie(&session_with("user", "alice"), 1000).unwrap();
+        let data = cookie.value.splitn(1, '.').next().unwrap().from_base64().unwrap();
+        assert!(!String::from_utf8_lossy(&data[]).contains("alice"));
+    }
+}
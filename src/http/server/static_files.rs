@@ -0,0 +1,332 @@
+/*!
+
+Serving files from a directory.
+
+`StaticFiles` maps the path of a request onto a root directory and serves the file found there,
+taking care of everything a static file server is expected to do: `HEAD` requests, the
+`Content-Type` (guessed from the file extension), `Content-Length`, `Last-Modified` and `ETag`
+headers, conditional requests (`If-None-Match`, `If-Modified-Since` and friends) and byte ranges.
+
+Paths which would escape the root directory, whether with `..` segments or with percent-encoded
+slashes, are rejected with `400 Bad Request`. A request for a directory is answered with its
+`index.html` if there is one, or else with a listing of its contents if `directory_index` is set.
+
+```rust,ignore
+#[derive(Clone)]
+struct FileServer {
+    files: StaticFiles,
+}
+
+impl Server for FileServer {
+    fn get_config(&self) -> Config { /* ... */ }
+
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        self.files.serve(&request, response).unwrap();
+    }
+}
+```
+
+*/
+
+use std::ascii::AsciiExt;
+use std::io::{File, FileStat, IoResult, TypeDirectory, TypeFile};
+use std::io::fs::{self, PathExtensions};
+use time::{self, Timespec};
+use url::percent_encoding::{percent_decode, utf8_percent_encode, DEFAULT_ENCODE_SET};
+
+use headers::content_type::MediaType;
+use headers::etag::{EntityTag, strong_etag};
+use method::Method::{Get, Head};
+use server::conditional::check_preconditions;
+use server::request::Request;
+use server::request::RequestUri::{AbsolutePath, AbsoluteUri};
+use server::response::ResponseWriter;
+use status;
+
+/// A handler serving the files beneath a directory.
+#[derive(Clone)]
+pub struct StaticFiles {
+    /// The directory which the request path is relative to.
+    pub root: Path,
+
+    /// Whether to list the contents of directories which have no `index.html`. If this is not
+    /// set, such requests are answered with `403 Forbidden`.
+    pub directory_index: bool,
+}
+
+impl StaticFiles {
+    /// Serve the files beneath `root`, without directory listings.
+    pub fn new(root: Path) -> StaticFiles {
+        StaticFiles {
+            root: root,
+            directory_index: false,
+        }
+    }
+
+    /// Map the path of a request (which may include a query string) onto a path beneath the
+    /// root directory, or return `None` if it is not a valid path or would escape the root.
+    pub fn resolve(&self, request_path: &str) -> Option<Path> {
+        let request_path = match request_path.find('?') {
+            Some(index) => &request_path[..index],
+            None => request_path,
+        };
+        if !request_path.starts_with("/") {
+            return None;
+        }
+
+        let mut path = self.root.clone();
+        for segment in request_path.split('/') {
+            let segment = match String::from_utf8(percent_decode(segment.as_bytes())) {
+                Ok(segment) => segment,
+                Err(_) => return None,
+            };
+            // A decoded segment may not introduce new separators or any other surprises.
+            if segment.contains_char('/') || segment.contains_char('\\') ||
+                    segment.contains_char('\0') {
+                return None;
+            }
+            match &segment[] {
+                "" | "." => (),
+                ".." => return None,
+                segment => path.push(segment),
+            }
+        }
+        Some(path)
+    }
+
+    /// Serve the file or directory that the request is for. Any error returned is an error
+    /// writing the response; failure to find the file is reported to the client as
+    /// `404 Not Found`.
+    pub fn serve(&self, request: &Request, response: &mut ResponseWriter) -> IoResult<()> {
+        if request.method != Get && request.method != Head {
+            response.headers.allow = Some(vec!(Get, Head));
+            return empty_response(response, status::Status::MethodNotAllowed);
+        }
+
+        let path = match request_path(request).and_then(|path| self.resolve(&path[])) {
+            Some(path) => path,
+            None => return empty_response(response, status::Status::BadRequest),
+        };
+
+        let stat = match fs::stat(&path) {
+            Ok(stat) => stat,
+            Err(_) => return empty_response(response, status::Status::NotFound),
+        };
+        match stat.kind {
+            TypeFile => self.serve_file(request, response, &path, &stat),
+            TypeDirectory => {
+                let index = path.join("index.html");
+                match fs::stat(&index) {
+                    Ok(ref stat) if stat.kind == TypeFile => {
+                        self.serve_file(request, response, &index, stat)
+                    },
+                    _ if self.directory_index => self.serve_directory(request, response, &path),
+                    _ => empty_response(response, status::Status::Forbidden),
+                }
+            },
+            _ => empty_response(response, status::Status::NotFound),
+        }
+    }
+
+    fn serve_file(&self, request: &Request, response: &mut ResponseWriter, path: &Path,
+                  stat: &FileStat) -> IoResult<()> {
+        let etag = file_etag(stat);
+        let last_modified = time::at_utc(Timespec::new((stat.modified / 1000) as i64, 0));
+        response.headers.etag = Some(etag.clone());
+        response.headers.last_modified = Some(last_modified);
+
+        match check_preconditions(request, Some(&etag), Some(&last_modified)) {
+            Ok(()) => (),
+            Err(status) => return empty_response(response, status),
+        }
+
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return empty_response(response, status::Status::Forbidden),
+        };
+        response.write_ranged(request, guess_media_type(path), &mut file, stat.size)
+    }
+
+    fn serve_directory(&self, request: &Request, response: &mut ResponseWriter, path: &Path)
+                       -> IoResult<()> {
+        let mut entries = match fs::readdir(path) {
+            Ok(entries) => entries,
+            Err(_) => return empty_response(response, status::Status::Forbidden),
+        };
+        entries.sort_by(|a, b| a.as_vec().cmp(b.as_vec()));
+
+        // Links are relative; unless the directory was requested with a trailing slash, they must
+        // include the name of the directory itself.
+        let base = match request_path(request) {
+            Some(request_path) => match request_path.rsplitn(1, '/').next() {
+                Some(last) if !last.is_empty() => format!("{}/", last),
+                _ => String::new(),
+            },
+            None => String::new(),
+        };
+
+        let mut html = String::from_str("<!DOCTYPE html>\n<html><body><ul>\n");
+        for entry in entries.iter() {
+            let name = match entry.filename_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let slash = if entry.is_dir() { "/" } else { "" };
+            let href = utf8_percent_encode(name, DEFAULT_ENCODE_SET);
+            html.push_str(&format!("<li><a href=\"{}{}{}\">{}{}</a></li>\n",
+                                   escape_html(&base[]), href, slash, escape_html(name), slash)[]);
+        }
+        html.push_str("</ul></body></html>\n");
+
+        let content_type = MediaType::new(String::from_str("text"), String::from_str("html"),
+                                          vec!((String::from_str("charset"),
+                                                String::from_str("UTF-8"))));
+        if request.method == Head {
+            response.headers.content_type = Some(content_type);
+            response.headers.content_length = Some(html.len());
+            response.write_headers()
+        } else {
+            response.write_content_auto(content_type, html)
+        }
+    }
+}
+
+/// The path of the request, still percent-encoded, whether the request target was a path or an
+/// absolute URI; `None` for `*` and authority targets.
+fn request_path(request: &Request) -> Option<String> {
+    match request.request_uri {
+        AbsolutePath(ref request_path) => Some(request_path.path.clone()),
+        AbsoluteUri(ref url) => url.serialize_path(),
+        _ => None,
+    }
+}
+
+fn empty_response(response: &mut ResponseWriter, status: status::Status) -> IoResult<()> {
+    // The Content-Length of a 304 would be taken as that of the file, so it is left out.
+    if status != status::Status::NotModified {
+        response.headers.content_length = Some(0);
+    }
+    response.status = status;
+    response.write_headers()
+}
+
+/// A strong ETag for a file, derived from its size and modification time.
+fn file_etag(stat: &FileStat) -> EntityTag {
+    strong_etag(format!("{:x}-{:x}", stat.size, stat.modified))
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Guess the media type of a file from its extension, falling back to
+/// `application/octet-stream`. Textual types are given a `charset` of UTF-8.
+pub fn guess_media_type(path: &Path) -> MediaType {
+    let extension = path.extension_str().map(|ext| ext.to_ascii_lowercase());
+    let (type_, subtype) = match extension.as_ref().map(|ext| &ext[]) {
+        Some("html") | Some("htm") => ("text", "html"),
+        Some("css") => ("text", "css"),
+        Some("js") => ("application", "javascript"),
+        Some("json") => ("application", "json"),
+        Some("xml") => ("application", "xml"),
+        Some("txt") => ("text", "plain"),
+        Some("csv") => ("text", "csv"),
+        Some("md") => ("text", "markdown"),
+        Some("png") => ("image", "png"),
+        Some("jpg") | Some("jpeg") => ("image", "jpeg"),
+        Some("gif") => ("image", "gif"),
+        Some("svg") => ("image", "svg+xml"),
+        Some("ico") => ("image", "x-icon"),
+        Some("webp") => ("image", "webp"),
+        Some("woff") => ("font", "woff"),
+        Some("woff2") => ("font", "woff2"),
+        Some("ttf") => ("font", "ttf"),
+        Some("pdf") => ("application", "pdf"),
+        Some("zip") => ("application", "zip"),
+        Some("gz") => ("application", "gzip"),
+        Some("tar") => ("application", "x-tar"),
+        Some("wasm") => ("application", "wasm"),
+        Some("mp3") => ("audio", "mpeg"),
+        Some("ogg") => ("audio", "ogg"),
+        Some("mp4") => ("video", "mp4"),
+        Some("webm") => ("video", "webm"),
+        _ => ("application", "octet-stream"),
+    };
+    let parameters = if type_ == "text" {
+        vec!((String::from_str("charset"), String::from_str("UTF-8")))
+    } else {
+        Vec::new()
+    };
+    MediaType::new(String::from_str(type_), String::from_str(subtype), parameters)
+}
+
+#[cfg(test)]
+mod test {
+    use headers::HeaderConvertible;
+    use headers::request::HeaderCollection;
+    use method::Method::Get;
+    use headers::host::Host;
+    use server::request::{Request, RequestPath, RequestUri};
+    use server::request::RequestUri::{AbsolutePath, AbsoluteUri, Authority, Star};
+    use url::Url;
+    use super::{StaticFiles, guess_media_type, request_path};
+
+    #[test]
+    fn test_resolve() {
+        let files = StaticFiles::new(Path::new("/srv/www"));
+        assert_eq!(files.resolve("/"), Some(Path::new("/srv/www")));
+        assert_eq!(files.resolve("/a/b.txt"), Some(Path::new("/srv/www/a/b.txt")));
+        assert_eq!(files.resolve("/a//./b.txt?x=../y"), Some(Path::new("/srv/www/a/b.txt")));
+        assert_eq!(files.resolve("/a%20b"), Some(Path::new("/srv/www/a b")));
+
+        assert_eq!(files.resolve("a/b.txt"), None);
+        assert_eq!(files.resolve("/../etc/passwd"), None);
+        assert_eq!(files.resolve("/a/../../etc/passwd"), None);
+        assert_eq!(files.resolve("/%2e%2e/etc/passwd"), None);
+        assert_eq!(files.resolve("/a%2f..%2f..%2fetc/passwd"), None);
+        assert_eq!(files.resolve("/a%5c..%5c..%5cetc"), None);
+        assert_eq!(files.resolve("/a%00.txt"), None);
+        assert_eq!(files.resolve("/%ff"), None);
+    }
+
+    #[test]
+    fn test_request_path() {
+        let request = |request_uri: RequestUri| Request {
+            remote_addr: None,
+            headers: HeaderCollection::new(),
+            body: Vec::new(),
+            method: Get,
+            request_uri: request_uri,
+            close_connection: true,
+            version: (1, 1),
+            raw_headers: None,
+        };
+        let path = AbsolutePath(RequestPath::parse("/a%20b/c?x=1").unwrap());
+        assert_eq!(request_path(&request(path)), Some(String::from_str("/a%20b/c")));
+        let uri = AbsoluteUri(Url::parse("http://example.com/a%20b/c?x=1").unwrap());
+        assert_eq!(request_path(&request(uri)), Some(String::from_str("/a%20b/c")));
+        assert_eq!(request_path(&request(Star)), None);
+        let authority = Authority(Host::parse("example.com:80").unwrap());
+        assert_eq!(request_path(&request(authority)), None);
+    }
+
+    #[test]
+    fn test_guess_media_type() {
+        assert_eq!(guess_media_type(&Path::new("index.HTML")).http_value(),
+                   String::from_str("text/html;charset=UTF-8"));
+        assert_eq!(guess_media_type(&Path::new("logo.png")).http_value(),
+                   String::from_str("image/png"));
+        assert_eq!(guess_media_type(&Path::new("README")).http_value(),
+                   String::from_str("application/octet-stream"));
+    }
+}
@@ -0,0 +1,131 @@
+/*!
+
+Name-based virtual hosting: serving several sites from one server, chosen by the host each request
+is for.
+
+`VirtualHosts` is a `Server` holding a handler for each host name. The host of a request is that
+of its request target if that is an absolute URL, or else that of its Host header (see
+`Request::host`); it is compared without regard to case or port. Requests for a host with no
+handler of its own go to the default handler, if there is one, and are otherwise answered with
+`421 Misdirected Request`.
+
+```rust,ignore
+let mut hosts = VirtualHosts::new(Config::new(address));
+hosts.add("example.com", Site::new("/srv/example.com"));
+hosts.add("www.example.com", Site::new("/srv/example.com"));
+hosts.add("example.org", Site::new("/srv/example.org"));
+hosts.serve_forever();
+```
+
+*/
+
+use headers::host::HostName;
+use server::{Server, Config};
+use server::request::Request;
+use server::response::ResponseWriter;
+use status;
+
+/// A server dispatching each request to the handler for its host.
+#[derive(Clone)]
+pub struct VirtualHosts<S> {
+    /// The configuration the server is started with.
+    pub config: Config,
+    /// The handler for requests for hosts which have none of their own.
+    pub default: Option<S>,
+    hosts: Vec<(HostName, S)>,
+}
+
+impl<S: Server> VirtualHosts<S> {
+    /// A server with no hosts and no default handler.
+    pub fn new(config: Config) -> VirtualHosts<S> {
+        VirtualHosts {
+            config: config,
+            default: None,
+            hosts: Vec::new(),
+        }
+    }
+
+    /// Serve requests for `host`, a host name or IP address (IPv6 addresses in brackets), with
+    /// `server`, in place of any handler it had before.
+    ///
+    /// # Panics
+    ///
+    /// If `host` is not a valid host.
+    pub fn add(&mut self, host: &str, server: S) {
+        let host = match HostName::parse(host) {
+            Some(host) => host,
+            None => panic!("invalid virtual host {}", host),
+        };
+        self.hosts.retain(|&(ref name, _)| *name != host);
+        self.hosts.push((host, server));
+    }
+
+    /// The handler for requests for `host`, falling back to the default handler.
+    pub fn find<'a>(&'a self, host: &HostName) -> Option<&'a S> {
+        match self.hosts.iter().find(|&&(ref name, _)| name == host) {
+            Some(&(_, ref server)) => Some(server),
+            None => self.default.as_ref(),
+        }
+    }
+}
+
+impl<S: Server> Server for VirtualHosts<S> {
+    fn handle_request(&self, request: Request, response: &mut ResponseWriter) {
+        let server = match request.host() {
+            Some(host) => self.find(&host.name),
+            None => self.default.as_ref(),
+        };
+        match server {
+            Some(server) => server.handle_request(request, response),
+            None => {
+                response.status = status::Status::MisdirectedRequest;
+                response.headers.content_length = Some(0);
+            },
+        }
+    }
+
+    fn get_config(&self) -> Config {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::net::ip::{SocketAddr, Ipv4Addr, Ipv6Addr};
+    use headers::host::{HostName, Domain, Ip};
+    use server::{Server, Config};
+    use server::request::Request;
+    use server::response::ResponseWriter;
+    use super::VirtualHosts;
+
+    #[derive(Clone, PartialEq, Show)]
+    struct Site(&'static str);
+
+    impl Server for Site {
+        fn handle_request(&self, _: Request, _: &mut ResponseWriter) { }
+
+        fn get_config(&self) -> Config {
+            Config::new(SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 })
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let config = Config::new(SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8001 });
+        let mut hosts = VirtualHosts::new(config);
+        hosts.add("example.com", Site("com"));
+        hosts.add("Example.ORG", Site("org-old"));
+        hosts.add("example.org", Site("org"));
+        hosts.add("[::1]", Site("localhost"));
+
+        let domain = |name: &str| Domain(String::from_str(name));
+        assert_eq!(hosts.find(&domain("example.com")), Some(&Site("com")));
+        assert_eq!(hosts.find(&domain("example.org")), Some(&Site("org")));
+        assert_eq!(hosts.find(&Ip(Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 1))), Some(&Site("localhost")));
+        assert_eq!(hosts.find(&HostName::parse("EXAMPLE.COM").unwrap()), Some(&Site("com")));
+        assert_eq!(hosts.find(&domain("example.net")), None);
+
+        hosts.default = Some(Site("default"));
+        assert_eq!(hosts.find(&domain("example.net")), Some(&Site("default")));
+    }
+}